@@ -33,6 +33,8 @@ pub struct CPU<T: MemoryInterface + Default> {
     pub mode: Mode,
     pub operating_mode: OperatingMode,
     pub memory: Box<T>,
+    ///Cycles elapsed since power on
+    pub cycles: u64,
 }
 
 impl<T: MemoryInterface + Default> CPU<T> {
@@ -44,6 +46,7 @@ impl<T: MemoryInterface + Default> CPU<T> {
             mode: Mode::ARM,
             operating_mode: OperatingMode::User,
             memory: Box::new(T::default()),
+            cycles: 0,
         }
     }
     ///Based on the current CPU operating mode, decodes an instruction in Arm or Thumb mode
@@ -189,7 +192,7 @@ impl<T: MemoryInterface + Default> CPU<T> {
             Opcode::Arm32(STRB) => self.LDR_STR(instruction.data, OpcodeArm::STR),
             Opcode::Arm32(STRH) => self.LDR_STR_HALF(instruction.data, OpcodeArm::STRH),
             Opcode::Arm32(SUB) => self.SUB(instruction.data),
-            Opcode::Arm32(SWI) => todo!(),
+            Opcode::Arm32(SWI) => self.software_interrupt(instruction.data),
             Opcode::Arm32(SWP) => todo!(),
             Opcode::Arm32(SWPB) => todo!(),
             Opcode::Arm32(TEQ) => self.TEQ(instruction.data),
@@ -201,22 +204,102 @@ impl<T: MemoryInterface + Default> CPU<T> {
         }
     }
 
+    /// Enters the IRQ exception if the IRQ line is asserted and CPSR.I is clear.<br>
+    /// Returns whether the exception was taken
+    pub fn check_irq(&mut self) -> bool {
+        if !self.memory.irq_line() || self.psr[0].get_i() {
+            return false;
+        }
+        self.raise_irq();
+        true
+    }
+
+    /// IRQ exception entry: saves CPSR into SPSR_irq, switches to IRQ mode in Arm state
+    /// with IRQs disabled and jumps to the IRQ vector(0x18).<br>
+    /// LR_irq is set to the next instruction + 4, so the handler returns with `subs pc, lr, #4`
+    pub fn raise_irq(&mut self) {
+        let cpsr = self.psr[0];
+        self.psr[OperatingMode::IRQ] = cpsr;
+        self.psr[0].register = cpsr.register.set_bits(0..=4, OperatingMode::IRQ as u32);
+        self.psr[0].set_t(false);
+        self.psr[0].set_i(true);
+        self.update_operating_mode(false);
+        self.mode = Mode::ARM;
+
+        let next = self.registers[15];
+        self.set_register(14, next + 4);
+        self.set_register(15, 0x18);
+    }
+
+    /// SWI: the system may emulate the BIOS function itself, otherwise enters Supervisor mode
+    /// in Arm state with IRQs disabled and jumps to the SWI vector(0x08).<br>
+    /// LR_svc is set to the next instruction. The GBA BIOS reads the function number in
+    /// bits 16-23 of the comment field
+    pub fn software_interrupt(&mut self, instruction: u32) {
+        let function = instruction.bit_range(16..=23) as u8;
+        let mut args = [0; 4];
+        args.copy_from_slice(&self.registers[0..4]);
+        let outcome = self.memory.software_interrupt(function, &mut args);
+        self.registers[0..4].copy_from_slice(&args);
+        match outcome {
+            SoftwareInterrupt::Done => {}
+            SoftwareInterrupt::Again => self.jump(self.registers[15] - 4),
+            SoftwareInterrupt::Bios => {
+                let cpsr = self.psr[0];
+                self.psr[OperatingMode::Supervisor] = cpsr;
+                self.psr[0].register = cpsr
+                    .register
+                    .set_bits(0..=4, OperatingMode::Supervisor as u32);
+                self.psr[0].set_t(false);
+                self.psr[0].set_i(true);
+                self.update_operating_mode(false);
+                self.mode = Mode::ARM;
+
+                let next = self.registers[15];
+                self.set_register(14, next);
+                self.jump(0x08);
+            }
+        }
+    }
+
+    /// Moves execution to an address from inside an instruction. `step` shifts the pipeline
+    /// once the instruction is done, so the target goes in the decode stage
+    fn jump(&mut self, address: u32) {
+        self.registers[15] = address;
+        self.pipeline[1] = self.memory.fetch_32(address);
+        self.pipeline[2] = self.memory.fetch_32(address + 4);
+    }
+
     // Draft of a run loop
     pub fn run_loop(&mut self) {
         //  init
-        self.pipeline[0] = self.memory.read_32(self.registers[15]); // ex stage
-        self.pipeline[1] = self.memory.read_32(self.registers[15] + 4); //decode stage
-        self.pipeline[2] = self.memory.read_32(self.registers[15] + 8); //fetch stage
+        self.fill_pipeline();
 
         // since it's a draft, i test only 256 iterations
         // in the final version there should be some sort of check to terminate the loop
         for _ in 0..=256 {
-            // TODO: logic to switch to thumb
-            self.execute_arm(self.decode(self.pipeline[0]));
-            self.pipeline[0] = self.pipeline[1];
-            self.pipeline[1] = self.pipeline[2];
-            self.pipeline[2] = self.registers[15] + 8;
+            self.step();
+        }
+    }
+
+    ///Executes a single instruction, entering the IRQ handler first if an interrupt is pending
+    pub fn step(&mut self) {
+        if self.check_irq() {
+            self.fill_pipeline();
         }
+        // TODO: logic to switch to thumb
+        self.execute_arm(self.decode(self.pipeline[0]));
+        self.pipeline[0] = self.pipeline[1];
+        self.pipeline[1] = self.pipeline[2];
+        self.pipeline[2] = self.memory.fetch_32(self.registers[15] + 8);
+        self.cycles += self.memory.take_cycles() as u64;
+    }
+
+    ///Loads the 3 pipeline stages starting from the current PC
+    pub fn fill_pipeline(&mut self) {
+        self.pipeline[0] = self.memory.fetch_32(self.registers[15]); // ex stage
+        self.pipeline[1] = self.memory.fetch_32(self.registers[15] + 4); //decode stage
+        self.pipeline[2] = self.memory.fetch_32(self.registers[15] + 8); //fetch stage
     }
 }
 
@@ -256,6 +339,11 @@ impl PSR {
     pub fn get_t(&self) -> bool {
         self.register.bit(5)
     }
+    /// Get I(IRQ disable) flag in the PSR
+    #[inline(always)]
+    pub fn get_i(&self) -> bool {
+        self.register.bit(7)
+    }
 
     //Setters
     #[inline(always)]
@@ -293,6 +381,12 @@ impl PSR {
         let data: u32 = if value { 0xFFFF_FFFF } else { 0 };
         self.register = self.register.set_bits(5..=5, data);
     }
+    #[inline(always)]
+    /// Set the I(IRQ disable) flag in the PSR
+    pub fn set_i(&mut self, value: bool) {
+        let data: u32 = if value { 0xFFFF_FFFF } else { 0 };
+        self.register = self.register.set_bits(7..=7, data);
+    }
 
     ///Returns the current user operating mode
     pub fn get_op_mode(&self) -> OperatingMode {
@@ -367,6 +461,40 @@ pub trait MemoryInterface {
     fn write_8(&mut self, address: u32, value: u8);
     fn write_16(&mut self, address: u32, value: u16);
     fn write_32(&mut self, address: u32, value: u32);
+    ///Reads an Arm opcode. Memories with an instruction prefetcher can tell it apart from a data read
+    fn fetch_32(&self, address: u32) -> u32 {
+        self.read_32(address)
+    }
+    ///Reads a Thumb opcode. Memories with an instruction prefetcher can tell it apart from a data read
+    fn fetch_16(&self, address: u32) -> u16 {
+        self.read_16(address)
+    }
+    ///Returns the cycles spent by the accesses done since the last call, then resets the count
+    fn take_cycles(&mut self) -> u32 {
+        0
+    }
+    ///State of the IRQ line, asserted by the interrupt controller of the system
+    fn irq_line(&self) -> bool {
+        false
+    }
+    ///Lets the system emulate a BIOS function called with SWI instead of running the BIOS
+    /// * **function**: BIOS function number
+    /// * **args**: r0 to r3, the function can change them
+    fn software_interrupt(&mut self, _function: u8, _args: &mut [u32; 4]) -> SoftwareInterrupt {
+        SoftwareInterrupt::Bios
+    }
+}
+
+///What the system did with a SWI
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoftwareInterrupt {
+    ///Left to the BIOS: the CPU takes the SWI exception
+    Bios,
+    ///Emulated, the CPU goes on with the next instruction
+    Done,
+    ///Emulated but not over(e.g. waiting for an interrupt): the SWI runs again when the CPU
+    ///executes again, after the IRQ handler if one is entered
+    Again,
 }
 
 ///Enum that contains both ARM and Thumb Opcodes
@@ -0,0 +1,73 @@
+//Direct Sound FIFOs(FIFO_A at 0x0400_00A0, FIFO_B at 0x0400_00A4).
+//Each FIFO holds up to 32 signed 8 bit samples, written 4 at a time by the CPU or by sound DMA,
+//and plays one of them every time the timer selected in SOUNDCNT_H overflows.
+//Source: https://problemkaputt.de/gbatek.htm#gbasoundchannelaandbdmasound
+
+///Capacity of a FIFO, in samples(bytes)
+pub const FIFO_CAPACITY: usize = 32;
+///When a FIFO holds this many samples or less, it asks its DMA channel for more
+pub const FIFO_REFILL: usize = 16;
+
+///A Direct Sound FIFO
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fifo {
+    samples: [i8; FIFO_CAPACITY],
+    ///Index of the oldest sample
+    head: usize,
+    len: usize,
+    ///Sample currently played
+    pub current: i8,
+}
+
+impl Default for Fifo {
+    fn default() -> Self {
+        Fifo {
+            samples: [0; FIFO_CAPACITY],
+            head: 0,
+            len: 0,
+            current: 0,
+        }
+    }
+}
+
+impl Fifo {
+    ///Queues a sample. A full FIFO drops the new sample
+    pub fn push(&mut self, sample: i8) {
+        if self.len == FIFO_CAPACITY {
+            return;
+        }
+        self.samples[(self.head + self.len) % FIFO_CAPACITY] = sample;
+        self.len += 1;
+    }
+
+    ///Moves to the next sample, called on overflow of the selected timer.<br>
+    ///An empty FIFO keeps playing the last sample
+    pub fn step(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        self.current = self.samples[self.head];
+        self.head = (self.head + 1) % FIFO_CAPACITY;
+        self.len -= 1;
+    }
+
+    ///Samples waiting to be played
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///Whether the FIFO should be refilled by sound DMA
+    pub fn needs_refill(&self) -> bool {
+        self.len <= FIFO_REFILL
+    }
+
+    ///Empties the FIFO(SOUNDCNT_H reset bits)
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
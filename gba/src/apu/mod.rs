@@ -0,0 +1,110 @@
+pub mod fifo;
+pub mod psg;
+
+use crate::apu::fifo::Fifo;
+use crate::apu::psg::Psg;
+use crate::debug::AudioSwitches;
+use crate::dma::{FIFO_A, FIFO_B};
+use crate::io::{self, IoRegister};
+use crate::memory::Memory;
+
+///Sound hardware
+#[derive(Default)]
+pub struct Apu {
+    ///Direct Sound A and B
+    pub fifo: [Fifo; 2],
+    ///Channels 1 to 4
+    pub psg: Psg,
+    ///Debug switches muting channels in the mixer
+    pub switches: AudioSwitches,
+}
+
+/*****************
+ * SOUND CONTROL *
+ *****************/
+impl Memory {
+    ///Called after a write to a sound register
+    pub(crate) fn sound_write(&mut self, reg: &IoRegister, value: u32, mask: u32) {
+        match reg.address() {
+            FIFO_A | FIFO_B => {
+                let fifo = &mut self.apu.fifo[(reg.address() == FIFO_B) as usize];
+                for byte in 0..4 {
+                    if mask & (0xFF << (byte * 8)) != 0 {
+                        fifo.push((value >> (byte * 8)) as i8);
+                    }
+                }
+            }
+            _ if reg.name == "SOUNDCNT_H" => {
+                // bits 11 and 15 reset FIFO A and B, they always read as 0
+                if value & mask & 0x0800 != 0 {
+                    self.apu.fifo[0].reset();
+                }
+                if value & mask & 0x8000 != 0 {
+                    self.apu.fifo[1].reset();
+                }
+            }
+            _ => self.psg_write(reg, value, mask),
+        }
+    }
+
+    ///Called when timer 0 or 1 overflows: the FIFOs clocked by that timer play their next
+    ///sample, and ask for a sound DMA once half empty
+    pub(crate) fn sound_timer_overflow(&mut self, timer: usize) {
+        let master = self.io_stored(io::register_by_name("SOUNDCNT_X").unwrap());
+        if master & 0x80 == 0 {
+            return;
+        }
+        let control = self.io_stored(io::register_by_name("SOUNDCNT_H").unwrap());
+        for (fifo, select_bit, address) in [(0, 10, FIFO_A), (1, 14, FIFO_B)] {
+            if (control >> select_bit) & 1 != timer as u32 {
+                continue;
+            }
+            self.apu.fifo[fifo].step();
+            if self.apu.fifo[fifo].needs_refill() {
+                self.dma_sound_fifo(address);
+            }
+        }
+    }
+}
+
+/**********
+ * MIXING *
+ **********/
+impl Memory {
+    ///Current output level, left and right, from -0x200 to 0x1FF before SOUNDBIAS.<br>
+    ///The PSG channels(-15 to 15 each) are summed for each side, multiplied by the SOUNDCNT_L
+    ///master volume(1 to 8), then scaled by the SOUNDCNT_H PSG volume(25%, 50% or 100%).<br>
+    ///Direct Sound samples are doubled at 50% volume, and multiplied by 4 at 100%
+    pub fn sound_output(&mut self) -> [i16; 2] {
+        let master = self.io_stored(io::register_by_name("SOUNDCNT_X").unwrap());
+        if master & 0x80 == 0 {
+            return [0, 0];
+        }
+        self.psg_sync(self.now());
+        let levels = self.psg_levels();
+        let psg_control = self.io_stored(io::register_by_name("SOUNDCNT_L").unwrap());
+        let control = self.io_stored(io::register_by_name("SOUNDCNT_H").unwrap());
+        let mut out = [0i16; 2];
+        // right uses the volume in bits 0-2 and the enables in bits 8-11, left bits 4-6 and 12-15
+        for (side, shift) in [(0, 4), (1, 0)] {
+            let enables = psg_control >> (8 + shift);
+            let sum: i16 = (0..4)
+                .filter(|channel| enables & (1 << channel) != 0)
+                .map(|channel| levels[channel])
+                .sum();
+            let volume = ((psg_control >> shift) & 7) as i16 + 1;
+            out[side] = (sum * volume) >> (2 - (control & 3).min(2));
+        }
+        for fifo in (0..2).filter(|fifo| self.apu.switches.fifo[*fifo]) {
+            // volume in bit 2 for A and 3 for B, right and left enables in bits 8-9 and 12-13
+            let volume = if control & (4 << fifo) != 0 { 4 } else { 2 };
+            let sample = self.apu.fifo[fifo].current as i16 * volume;
+            for (side, bit) in [(1, 8), (0, 9)] {
+                if control & (1 << (bit + 4 * fifo)) != 0 {
+                    out[side] += sample;
+                }
+            }
+        }
+        out.map(|level| level.clamp(-0x200, 0x1FF))
+    }
+}
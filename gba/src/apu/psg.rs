@@ -0,0 +1,532 @@
+//PSG: the 4 channels inherited from the Game Boy, 2 squares(the first one with a frequency
+//sweep), a wave channel playing 4 bit samples from wave RAM, and a noise channel.
+//The channels are not stepped every cycle: they are advanced by the time elapsed whenever one
+//of their registers is written, when the mixer reads them and on every frame sequencer event.
+//The frame sequencer is scheduled at 512 Hz while the sound circuit is on(SOUNDCNT_X bit 7);
+//it clocks the length counters, the sweep and the volume envelopes.
+//Source: https://problemkaputt.de/gbatek.htm#gbasoundchannel1tonesweep
+//https://gbdev.io/pandocs/Audio_details.html
+use crate::io;
+use crate::memory::Memory;
+use crate::scheduler::Event;
+use std::ops::RangeInclusive;
+
+///Cycles between 2 frame sequencer steps(512 Hz)
+pub const SEQUENCER_PERIOD: u64 = 32768;
+
+pub(crate) const SOUND1CNT_L: u32 = 0x060;
+pub(crate) const SOUND1CNT_H: u32 = 0x062;
+pub(crate) const SOUND1CNT_X: u32 = 0x064;
+pub(crate) const SOUND2CNT_L: u32 = 0x068;
+pub(crate) const SOUND2CNT_H: u32 = 0x06C;
+pub(crate) const SOUND3CNT_L: u32 = 0x070;
+pub(crate) const SOUND3CNT_H: u32 = 0x072;
+pub(crate) const SOUND3CNT_X: u32 = 0x074;
+pub(crate) const SOUND4CNT_L: u32 = 0x078;
+pub(crate) const SOUND4CNT_H: u32 = 0x07C;
+pub(crate) const SOUNDCNT_L: u32 = 0x080;
+pub(crate) const SOUNDCNT_X: u32 = 0x084;
+pub(crate) const WAVE_RAM: u32 = 0x090;
+///Offsets of the PSG registers and of wave RAM
+pub(crate) const REGISTERS: RangeInclusive<u32> = SOUND1CNT_L..=0x09F;
+
+///Square waveforms for each duty setting(12.5%, 25%, 50%, 75%), one bit per step
+const DUTY: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+///Advances a channel timer, returns how many times it expired
+/// * **countdown**: cycles left before the next expiry
+/// * **period**: cycles between expiries, used to reload it
+fn advance(countdown: &mut u64, period: u64, cycles: u64) -> u64 {
+    if cycles < *countdown {
+        *countdown -= cycles;
+        return 0;
+    }
+    let after = cycles - *countdown;
+    *countdown = period - after % period;
+    1 + after / period
+}
+
+///Whether the DAC of a channel with an envelope is on: any initial volume, or a rising envelope
+fn dac_on(envelope: u16) -> bool {
+    envelope & 0xF800 != 0
+}
+
+///Volume envelope of the squares and the noise channel
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Envelope {
+    ///Current volume, 0 to 15
+    pub volume: u8,
+    increase: bool,
+    ///Envelope ticks(64 Hz) between volume steps, 0 stops the envelope
+    period: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    ///Restarts from the register: step time in bits 8-10, direction in bit 11, initial volume in
+    ///bits 12-15
+    fn trigger(&mut self, register: u16) {
+        self.volume = (register >> 12) as u8;
+        self.increase = register & 0x800 != 0;
+        self.period = ((register >> 8) & 7) as u8;
+        self.timer = self.period;
+    }
+
+    fn tick(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer -= 1;
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+///Channels 1 and 2
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Square {
+    pub enabled: bool,
+    ///Length counter, the channel stops when it reaches 0
+    pub length: u16,
+    ///Step of the waveform, 0 to 7
+    step: u8,
+    countdown: u64,
+    pub envelope: Envelope,
+    ///Channel 1 only: frequency the sweep works on
+    shadow: u16,
+    sweep_timer: u8,
+    sweep_enabled: bool,
+}
+
+impl Square {
+    ///Cycles per waveform step: the waveform plays at 131072/(2048-X) Hz
+    fn period(frequency: u16) -> u64 {
+        (2048 - (frequency & 0x7FF) as u64) * 16
+    }
+}
+
+///Channel 3
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Wave {
+    pub enabled: bool,
+    pub length: u16,
+    ///Sample played, 0 to 31, or 0 to 63 when both banks are played
+    pub position: u8,
+    countdown: u64,
+}
+
+impl Wave {
+    ///Cycles per sample: samples play at 2097152/(2048-X) Hz
+    fn period(frequency: u16) -> u64 {
+        (2048 - (frequency & 0x7FF) as u64) * 8
+    }
+}
+
+///Channel 4
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Noise {
+    pub enabled: bool,
+    pub length: u16,
+    ///Linear feedback shift register, the output is high while bit 0 is clear
+    pub lfsr: u16,
+    countdown: u64,
+    pub envelope: Envelope,
+}
+
+impl Noise {
+    ///Cycles per shift: 524288/r/2^(s+1) Hz, with r = 0 counting as 0.5
+    fn period(control: u16) -> u64 {
+        let ratio = (control & 7) as u64;
+        let shift = (control >> 4) & 0xF;
+        if ratio == 0 {
+            32 << shift
+        } else {
+            (64 * ratio) << shift
+        }
+    }
+
+    ///Shifts the register once, the feedback goes to bit 14, or to bit 6 in 7 bit mode
+    ///(SOUND4CNT_H bit 3)
+    fn shift(&mut self, short: bool) {
+        let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        let top = if short { 6 } else { 14 };
+        self.lfsr = (self.lfsr >> 1) | (feedback << top);
+    }
+}
+
+///State of the 4 PSG channels
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Psg {
+    pub square: [Square; 2],
+    pub wave: Wave,
+    pub noise: Noise,
+    ///2 banks of 32 4 bit samples, the high nibble of each byte plays first
+    pub wave_ram: [[u8; 16]; 2],
+    ///Next frame sequencer step, 0 to 7
+    pub step: u8,
+    ///Time the channels have been advanced to
+    time: u64,
+}
+
+impl Psg {
+    ///Channel enable flags, as read in SOUNDCNT_X bits 0-3
+    pub fn status(&self) -> u16 {
+        [
+            self.square[0].enabled,
+            self.square[1].enabled,
+            self.wave.enabled,
+            self.noise.enabled,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |status, (channel, on)| {
+            status | ((*on as u16) << channel)
+        })
+    }
+}
+
+/*******
+ * PSG *
+ *******/
+impl Memory {
+    fn psg_register(&self, offset: u32) -> u16 {
+        self.io_stored(io::register_at(offset).unwrap()) as u16
+    }
+
+    ///Whether the sound circuit is on, SOUNDCNT_X bit 7. While it is off the PSG registers
+    ///are cleared and cannot be written
+    pub(crate) fn psg_powered(&self) -> bool {
+        self.psg_register(SOUNDCNT_X) & 0x80 != 0
+    }
+
+    ///Advances the channels up to the given time
+    pub(crate) fn psg_sync(&mut self, time: u64) {
+        let psg = &mut self.apu.psg;
+        if time <= psg.time {
+            return;
+        }
+        let cycles = time - psg.time;
+        psg.time = time;
+        let frequencies = [
+            self.psg_register(SOUND1CNT_X),
+            self.psg_register(SOUND2CNT_H),
+        ];
+        let wave_frequency = self.psg_register(SOUND3CNT_X);
+        let wave_control = self.psg_register(SOUND3CNT_L);
+        let noise_control = self.psg_register(SOUND4CNT_H);
+        let psg = &mut self.apu.psg;
+        for (square, frequency) in psg.square.iter_mut().zip(frequencies) {
+            if square.enabled {
+                let steps = advance(&mut square.countdown, Square::period(frequency), cycles);
+                square.step = ((square.step as u64 + steps) % 8) as u8;
+            }
+        }
+        if psg.wave.enabled {
+            let wave = &mut psg.wave;
+            let steps = advance(&mut wave.countdown, Wave::period(wave_frequency), cycles);
+            // 64 samples when both banks are played(SOUND3CNT_L bit 5)
+            let samples = if wave_control & 0x20 != 0 { 64 } else { 32 };
+            wave.position = ((wave.position as u64 + steps) % samples) as u8;
+        }
+        if psg.noise.enabled {
+            let noise = &mut psg.noise;
+            let steps = advance(&mut noise.countdown, Noise::period(noise_control), cycles);
+            for _ in 0..steps {
+                noise.shift(noise_control & 8 != 0);
+            }
+        }
+    }
+
+    ///Called after a write to a PSG register or to wave RAM
+    pub(crate) fn psg_write(&mut self, reg: &io::IoRegister, value: u32, mask: u32) {
+        let offset = reg.offset;
+        if offset <= SOUNDCNT_L && !self.psg_powered() {
+            self.io_store(reg, 0);
+            return;
+        }
+        // bit 15 of SOUNDxCNT_X(or SOUND4CNT_H) restarts the channel
+        let trigger = value & mask & 0x8000 != 0;
+        match offset {
+            SOUND1CNT_H | SOUND2CNT_L => {
+                let channel = (offset == SOUND2CNT_L) as usize;
+                let register = self.psg_register(offset);
+                let square = &mut self.apu.psg.square[channel];
+                if mask & 0x3F != 0 {
+                    square.length = 64 - (register & 0x3F);
+                }
+                if mask & 0xFF00 != 0 && !dac_on(register) {
+                    square.enabled = false;
+                }
+            }
+            SOUND1CNT_X if trigger => self.psg_trigger_square(0),
+            SOUND2CNT_H if trigger => self.psg_trigger_square(1),
+            SOUND3CNT_L if self.psg_register(offset) & 0x80 == 0 => {
+                self.apu.psg.wave.enabled = false;
+            }
+            SOUND3CNT_H if mask & 0xFF != 0 => {
+                self.apu.psg.wave.length = 256 - (self.psg_register(offset) & 0xFF);
+            }
+            SOUND3CNT_X if trigger => {
+                let dac = self.psg_register(SOUND3CNT_L) & 0x80 != 0;
+                let period = Wave::period(self.psg_register(SOUND3CNT_X));
+                let wave = &mut self.apu.psg.wave;
+                wave.enabled = dac;
+                if wave.length == 0 {
+                    wave.length = 256;
+                }
+                wave.position = 0;
+                wave.countdown = period;
+            }
+            SOUND4CNT_L => {
+                let register = self.psg_register(offset);
+                let noise = &mut self.apu.psg.noise;
+                if mask & 0x3F != 0 {
+                    noise.length = 64 - (register & 0x3F);
+                }
+                if mask & 0xFF00 != 0 && !dac_on(register) {
+                    noise.enabled = false;
+                }
+            }
+            SOUND4CNT_H if trigger => {
+                let envelope = self.psg_register(SOUND4CNT_L);
+                let control = self.psg_register(SOUND4CNT_H);
+                let noise = &mut self.apu.psg.noise;
+                noise.enabled = dac_on(envelope);
+                if noise.length == 0 {
+                    noise.length = 64;
+                }
+                noise.lfsr = if control & 8 != 0 { 0x7F } else { 0x7FFF };
+                noise.countdown = Noise::period(control);
+                noise.envelope.trigger(envelope);
+            }
+            SOUNDCNT_X => self.psg_power(),
+            WAVE_RAM..=0x09F => {
+                // the CPU sees the bank that is not played
+                let bank = self.psg_cpu_bank();
+                let start = (offset - WAVE_RAM) as usize;
+                for byte in (0..4).filter(|byte| mask & (0xFF << (byte * 8)) != 0) {
+                    self.apu.psg.wave_ram[bank][start + byte] = (value >> (byte * 8)) as u8;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ///Wave RAM bank accessed by the CPU, the one SOUND3CNT_L bit 6 does not select for playback
+    fn psg_cpu_bank(&self) -> usize {
+        (self.psg_register(SOUND3CNT_L) & 0x40 == 0) as usize
+    }
+
+    ///Live value of SOUNDCNT_X and of wave RAM
+    pub(crate) fn psg_read(&self, reg: &io::IoRegister) -> Option<u32> {
+        match reg.offset {
+            SOUNDCNT_X => Some(self.io_stored(reg) & 0x80 | self.apu.psg.status() as u32),
+            WAVE_RAM..=0x09F => {
+                let start = (reg.offset - WAVE_RAM) as usize;
+                let bytes = &self.apu.psg.wave_ram[self.psg_cpu_bank()][start..start + 4];
+                Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            _ => None,
+        }
+    }
+
+    fn psg_trigger_square(&mut self, channel: usize) {
+        let (envelope, frequency) = if channel == 0 {
+            (
+                self.psg_register(SOUND1CNT_H),
+                self.psg_register(SOUND1CNT_X),
+            )
+        } else {
+            (
+                self.psg_register(SOUND2CNT_L),
+                self.psg_register(SOUND2CNT_H),
+            )
+        };
+        let sweep = self.psg_register(SOUND1CNT_L);
+        let square = &mut self.apu.psg.square[channel];
+        square.enabled = dac_on(envelope);
+        if square.length == 0 {
+            square.length = 64;
+        }
+        square.countdown = Square::period(frequency);
+        square.envelope.trigger(envelope);
+        if channel == 0 {
+            let (period, shift) = ((sweep >> 4) & 7, sweep & 7);
+            square.shadow = frequency & 0x7FF;
+            square.sweep_timer = if period == 0 { 8 } else { period as u8 };
+            square.sweep_enabled = period != 0 || shift != 0;
+            // a sweep that would overflow at once stops the channel
+            if shift != 0 && sweep_target(square.shadow, sweep) > 2047 {
+                square.enabled = false;
+            }
+        }
+    }
+
+    ///SOUNDCNT_X bit 7 written: turning the circuit off clears the PSG, turning it on starts the
+    ///frame sequencer
+    fn psg_power(&mut self) {
+        let now = self.now();
+        if self.psg_powered() {
+            if self.scheduler.time_of(Event::FrameSequencer).is_none() {
+                // the sequencer keeps its own pace, aligned on the master clock
+                let next = (now / SEQUENCER_PERIOD + 1) * SEQUENCER_PERIOD;
+                self.scheduler.schedule(Event::FrameSequencer, next);
+            }
+            return;
+        }
+        self.scheduler.cancel(Event::FrameSequencer);
+        for offset in (SOUND1CNT_L..=SOUNDCNT_L).step_by(2) {
+            if let Some(reg) = io::register_at(offset).filter(|reg| reg.offset == offset) {
+                self.io_store(reg, 0);
+            }
+        }
+        let wave_ram = self.apu.psg.wave_ram;
+        self.apu.psg = Psg {
+            wave_ram,
+            time: now,
+            ..Default::default()
+        };
+    }
+
+    ///Frame sequencer step: lengths at 256 Hz, sweep at 128 Hz, envelopes at 64 Hz
+    pub(crate) fn psg_sequencer_event(&mut self, time: u64) {
+        self.psg_sync(time);
+        let step = self.apu.psg.step;
+        if step.is_multiple_of(2) {
+            self.psg_clock_lengths();
+        }
+        if step == 2 || step == 6 {
+            self.psg_clock_sweep();
+        }
+        if step == 7 {
+            let psg = &mut self.apu.psg;
+            psg.square[0].envelope.tick();
+            psg.square[1].envelope.tick();
+            psg.noise.envelope.tick();
+        }
+        self.apu.psg.step = (step + 1) % 8;
+        self.scheduler
+            .schedule(Event::FrameSequencer, time + SEQUENCER_PERIOD);
+    }
+
+    fn psg_clock_lengths(&mut self) {
+        // length enable is bit 14 of the register with the trigger bit
+        let enabled = [SOUND1CNT_X, SOUND2CNT_H, SOUND3CNT_X, SOUND4CNT_H]
+            .map(|offset| self.psg_register(offset) & 0x4000 != 0);
+        let psg = &mut self.apu.psg;
+        let [first, second] = &mut psg.square;
+        let channels = [
+            (&mut first.length, &mut first.enabled),
+            (&mut second.length, &mut second.enabled),
+            (&mut psg.wave.length, &mut psg.wave.enabled),
+            (&mut psg.noise.length, &mut psg.noise.enabled),
+        ];
+        for ((length, on), enabled) in channels.into_iter().zip(enabled) {
+            if enabled && *length > 0 {
+                *length -= 1;
+                if *length == 0 {
+                    *on = false;
+                }
+            }
+        }
+    }
+
+    fn psg_clock_sweep(&mut self) {
+        let sweep = self.psg_register(SOUND1CNT_L);
+        let (period, shift) = ((sweep >> 4) & 7, sweep & 7);
+        let square = &mut self.apu.psg.square[0];
+        if square.sweep_timer > 0 {
+            square.sweep_timer -= 1;
+        }
+        if square.sweep_timer > 0 {
+            return;
+        }
+        square.sweep_timer = if period == 0 { 8 } else { period as u8 };
+        if !square.sweep_enabled || period == 0 {
+            return;
+        }
+        let target = sweep_target(square.shadow, sweep);
+        if target > 2047 {
+            square.enabled = false;
+            return;
+        }
+        if shift != 0 {
+            square.shadow = target;
+            // the new frequency goes back in the register, then is checked once more
+            if sweep_target(target, sweep) > 2047 {
+                square.enabled = false;
+            }
+            let reg = io::register_at(SOUND1CNT_X).unwrap();
+            let control = self.io_stored(reg);
+            self.io_store(reg, (control & !0x7FF) | target as u32);
+        }
+    }
+
+    ///Output of each channel, from -15 to 15, 0 when stopped or muted by the debug switches
+    pub(crate) fn psg_levels(&self) -> [i16; 4] {
+        let psg = &self.apu.psg;
+        let mut levels = [0; 4];
+        for (channel, control) in [SOUND1CNT_H, SOUND2CNT_L].into_iter().enumerate() {
+            let square = &psg.square[channel];
+            if square.enabled {
+                let duty = DUTY[(self.psg_register(control) >> 6) as usize & 3];
+                let volume = square.envelope.volume as i16;
+                levels[channel] = if (duty >> square.step) & 1 != 0 {
+                    volume
+                } else {
+                    -volume
+                };
+            }
+        }
+        if psg.wave.enabled {
+            let control = self.psg_register(SOUND3CNT_L);
+            let volume = self.psg_register(SOUND3CNT_H);
+            // 2 banks play one after the other, starting with the selected one
+            let bank = (((control >> 6) & 1) as usize + psg.wave.position as usize / 32) % 2;
+            let index = psg.wave.position as usize % 32;
+            let byte = psg.wave_ram[bank][index / 2];
+            let sample = if index.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0xF
+            } as i16;
+            // quarters of the volume: 75% forced by bit 15, or 0%, 100%, 50%, 25%
+            let quarters = if volume & 0x8000 != 0 {
+                3
+            } else {
+                [0, 4, 2, 1][(volume >> 13) as usize & 3]
+            };
+            levels[2] = (2 * sample - 15) * quarters / 4;
+        }
+        if psg.noise.enabled {
+            let volume = psg.noise.envelope.volume as i16;
+            levels[3] = if psg.noise.lfsr & 1 == 0 {
+                volume
+            } else {
+                -volume
+            };
+        }
+        for (level, on) in levels.iter_mut().zip(self.apu.switches.psg) {
+            if !on {
+                *level = 0;
+            }
+        }
+        levels
+    }
+}
+
+///Frequency the sweep moves channel 1 to: shift in SOUND1CNT_L bits 0-2, decrease in bit 3
+fn sweep_target(frequency: u16, sweep: u16) -> u16 {
+    let delta = frequency >> (sweep & 7);
+    if sweep & 8 != 0 {
+        frequency - delta
+    } else {
+        frequency + delta
+    }
+}
@@ -0,0 +1,199 @@
+//GamePak loading and ROM header handling.
+//Source: https://problemkaputt.de/gbatek.htm#gbacartridgeheader
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+///Size of the cartridge header, in bytes
+pub const HEADER_SIZE: usize = 0xC0;
+///Biggest ROM the GamePak bus can address(32MBytes)
+pub const MAX_ROM_SIZE: usize = 32 * 1024 * 1024;
+
+///Compressed Nintendo logo every cartridge must contain at 0x04, the BIOS refuses to boot otherwise
+pub const NINTENDO_LOGO: [u8; 156] = [
+    0x24, 0xFF, 0xAE, 0x51, 0x69, 0x9A, 0xA2, 0x21, 0x3D, 0x84, 0x82, 0x0A, 0x84, 0xE4, 0x09, 0xAD,
+    0x11, 0x24, 0x8B, 0x98, 0xC0, 0x81, 0x7F, 0x21, 0xA3, 0x52, 0xBE, 0x19, 0x93, 0x09, 0xCE, 0x20,
+    0x10, 0x46, 0x4A, 0x4A, 0xF8, 0x27, 0x31, 0xEC, 0x58, 0xC7, 0xE8, 0x33, 0x82, 0xE3, 0xCE, 0xBF,
+    0x85, 0xF4, 0xDF, 0x94, 0xCE, 0x4B, 0x09, 0xC1, 0x94, 0x56, 0x8A, 0xC0, 0x13, 0x72, 0xA7, 0xFC,
+    0x9F, 0x84, 0x4D, 0x73, 0xA3, 0xCA, 0x9A, 0x61, 0x58, 0x97, 0xA3, 0x27, 0xFC, 0x03, 0x98, 0x76,
+    0x23, 0x1D, 0xC7, 0x61, 0x03, 0x04, 0xAE, 0x56, 0xBF, 0x38, 0x84, 0x00, 0x40, 0xA7, 0x0E, 0xFD,
+    0xFF, 0x52, 0xFE, 0x03, 0x6F, 0x95, 0x30, 0xF1, 0x97, 0xFB, 0xC0, 0x85, 0x60, 0xD6, 0x80, 0x25,
+    0xA9, 0x63, 0xBE, 0x03, 0x01, 0x4E, 0x38, 0xE2, 0xF9, 0xA2, 0x34, 0xFF, 0xBB, 0x3E, 0x03, 0x44,
+    0x78, 0x00, 0x90, 0xCB, 0x88, 0x11, 0x3A, 0x94, 0x65, 0xC0, 0x7C, 0x63, 0x87, 0xF0, 0x3C, 0xAF,
+    0xD6, 0x25, 0xE4, 0x8B, 0x38, 0x0A, 0xAC, 0x72, 0x21, 0xD4, 0xF8, 0x07,
+];
+
+//Offsets of the header fields
+const ENTRY: usize = 0x00;
+const LOGO: usize = 0x04;
+const TITLE: usize = 0xA0;
+const GAME_CODE: usize = 0xAC;
+const MAKER_CODE: usize = 0xB0;
+const FIXED_VALUE: usize = 0xB2;
+const UNIT_CODE: usize = 0xB3;
+const VERSION: usize = 0xBC;
+const CHECKSUM: usize = 0xBD;
+
+///Errors that prevent a ROM from being loaded at all
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    ///The file is smaller than the header
+    TooSmall(usize),
+    ///The file does not fit in the GamePak address space
+    TooLarge(usize),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::Io(error) => write!(f, "cannot read ROM: {}", error),
+            CartridgeError::TooSmall(size) => write!(f, "ROM too small: {} bytes", size),
+            CartridgeError::TooLarge(size) => write!(f, "ROM too large: {} bytes", size),
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(error: io::Error) -> Self {
+        CartridgeError::Io(error)
+    }
+}
+
+///Problems found in a header. The ROM can still be loaded, but the real BIOS would not boot it
+#[derive(Debug, PartialEq)]
+pub enum Diagnostic {
+    LogoMismatch,
+    ChecksumMismatch {
+        expected: u8,
+        found: u8,
+    },
+    ///Byte 0xB2 must be 0x96
+    FixedValueMismatch(u8),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::LogoMismatch => write!(f, "Nintendo logo does not match"),
+            Diagnostic::ChecksumMismatch { expected, found } => write!(
+                f,
+                "header checksum is {:#04X}, expected {:#04X}",
+                found, expected
+            ),
+            Diagnostic::FixedValueMismatch(value) => {
+                write!(f, "fixed value at 0xB2 is {:#04X}, expected 0x96", value)
+            }
+        }
+    }
+}
+
+///Parsed cartridge header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    ///ARM branch to the start of the game code
+    pub entry: u32,
+    pub logo: [u8; 156],
+    pub title: String,
+    pub game_code: String,
+    pub maker_code: String,
+    pub unit_code: u8,
+    pub version: u8,
+    pub checksum: u8,
+}
+
+impl Header {
+    ///Parses the header from the first 0xC0 bytes of a ROM
+    pub fn parse(data: &[u8]) -> Header {
+        let text = |range: core::ops::Range<usize>| {
+            data[range]
+                .iter()
+                .take_while(|c| **c != 0)
+                .map(|c| *c as char)
+                .collect::<String>()
+        };
+        Header {
+            entry: u32::from_le_bytes(data[ENTRY..ENTRY + 4].try_into().unwrap()),
+            logo: data[LOGO..LOGO + 156].try_into().unwrap(),
+            title: text(TITLE..GAME_CODE),
+            game_code: text(GAME_CODE..MAKER_CODE),
+            maker_code: text(MAKER_CODE..FIXED_VALUE),
+            unit_code: data[UNIT_CODE],
+            version: data[VERSION],
+            checksum: data[CHECKSUM],
+        }
+    }
+
+    ///Returns the address the entry branch jumps to
+    pub fn entry_point(&self) -> u32 {
+        // B instruction: signed 24 bit word offset, relative to PC+8
+        let offset = ((self.entry << 8) as i32) >> 6;
+        (0x0800_0008_i64 + offset as i64) as u32
+    }
+}
+
+///Computes the complement checksum of the header bytes 0xA0 to 0xBC
+pub fn header_checksum(data: &[u8]) -> u8 {
+    data[TITLE..CHECKSUM]
+        .iter()
+        .fold(0u8, |chk, byte| chk.wrapping_sub(*byte))
+        .wrapping_sub(0x19)
+}
+
+///A GamePak ROM
+pub struct Cartridge {
+    pub rom: Vec<u8>,
+    pub header: Header,
+}
+
+impl Cartridge {
+    ///Loads a ROM file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Cartridge, CartridgeError> {
+        Cartridge::from_bytes(fs::read(path)?)
+    }
+
+    ///Builds a cartridge from a ROM already in memory
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        if rom.len() < HEADER_SIZE {
+            return Err(CartridgeError::TooSmall(rom.len()));
+        }
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(CartridgeError::TooLarge(rom.len()));
+        }
+        let header = Header::parse(&rom);
+        Ok(Cartridge { rom, header })
+    }
+
+    ///Checks the header the same way the BIOS does, returning every problem found
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut problems = Vec::new();
+        if self.header.logo != NINTENDO_LOGO {
+            problems.push(Diagnostic::LogoMismatch);
+        }
+        if self.rom[FIXED_VALUE] != 0x96 {
+            problems.push(Diagnostic::FixedValueMismatch(self.rom[FIXED_VALUE]));
+        }
+        let expected = header_checksum(&self.rom);
+        if self.header.checksum != expected {
+            problems.push(Diagnostic::ChecksumMismatch {
+                expected,
+                found: self.header.checksum,
+            });
+        }
+        problems
+    }
+
+    ///Repairs the header like `gbafix` does: rewrites the logo, the fixed value and the checksum
+    pub fn fix_header(&mut self) {
+        self.rom[LOGO..LOGO + 156].copy_from_slice(&NINTENDO_LOGO);
+        self.rom[FIXED_VALUE] = 0x96;
+        self.rom[CHECKSUM] = header_checksum(&self.rom);
+        self.header = Header::parse(&self.rom);
+    }
+
+    ///Writes the ROM back to a file, e.g. after `fix_header`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, &self.rom)
+    }
+}
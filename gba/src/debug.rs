@@ -0,0 +1,121 @@
+//Debug switches: hide parts of the picture or mute sound channels, to isolate rendering and
+//sound bugs. They only act on what the compositor and the mixer output: the registers keep what
+//the game wrote, and it reads them back unchanged.
+//They are reachable from the API(`debug_switch`), the CLI(`--disable`) and debugger consoles,
+//through the text commands of `debugger_command`.
+use crate::memory::Memory;
+
+///Parts of the picture the compositor draws, all on by default
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VideoSwitches {
+    pub bg: [bool; 4],
+    pub obj: bool,
+    ///Off: every dot is inside no window, everything shows
+    pub windows: bool,
+    ///Off: no alpha blending, brightening or darkening
+    pub blending: bool,
+}
+
+impl Default for VideoSwitches {
+    fn default() -> Self {
+        VideoSwitches {
+            bg: [true; 4],
+            obj: true,
+            windows: true,
+            blending: true,
+        }
+    }
+}
+
+///Channels the mixer plays, all on by default
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AudioSwitches {
+    ///PSG channels 1 to 4
+    pub psg: [bool; 4],
+    ///Direct Sound A and B
+    pub fifo: [bool; 2],
+}
+
+impl Default for AudioSwitches {
+    fn default() -> Self {
+        AudioSwitches {
+            psg: [true; 4],
+            fifo: [true; 2],
+        }
+    }
+}
+
+///Names accepted by `Memory::debug_switch`, for help messages
+pub const SWITCH_NAMES: &str = "bg0-bg3, obj, windows, blending, psg1-psg4, fifo-a, fifo-b";
+
+/******************
+ * DEBUG SWITCHES *
+ ******************/
+impl Memory {
+    ///Turns a debug switch on or off by name, see `SWITCH_NAMES`.<br>
+    ///Returns false if there is no such switch
+    pub fn debug_switch(&mut self, name: &str, on: bool) -> bool {
+        let video = &mut self.ppu.switches;
+        let audio = &mut self.apu.switches;
+        let switch = match name {
+            "bg0" => &mut video.bg[0],
+            "bg1" => &mut video.bg[1],
+            "bg2" => &mut video.bg[2],
+            "bg3" => &mut video.bg[3],
+            "obj" => &mut video.obj,
+            "windows" => &mut video.windows,
+            "blending" => &mut video.blending,
+            "psg1" => &mut audio.psg[0],
+            "psg2" => &mut audio.psg[1],
+            "psg3" => &mut audio.psg[2],
+            "psg4" => &mut audio.psg[3],
+            "fifo-a" => &mut audio.fifo[0],
+            "fifo-b" => &mut audio.fifo[1],
+            _ => return false,
+        };
+        *switch = on;
+        true
+    }
+
+    ///State of every switch, in the order of `SWITCH_NAMES`
+    pub fn debug_switches(&self) -> Vec<(String, bool)> {
+        let video = &self.ppu.switches;
+        let audio = &self.apu.switches;
+        let mut switches: Vec<(String, bool)> = (0..4)
+            .map(|bg| (format!("bg{}", bg), video.bg[bg]))
+            .collect();
+        switches.push(("obj".to_string(), video.obj));
+        switches.push(("windows".to_string(), video.windows));
+        switches.push(("blending".to_string(), video.blending));
+        switches.extend((0..4).map(|channel| (format!("psg{}", channel + 1), audio.psg[channel])));
+        switches.push(("fifo-a".to_string(), audio.fifo[0]));
+        switches.push(("fifo-b".to_string(), audio.fifo[1]));
+        switches
+    }
+
+    ///Runs a debugger console command, returns the text to show or an error:
+    /// * `switches`: lists the switches and their state
+    /// * `switch <name> <on|off>`: turns a switch on or off
+    pub fn debugger_command(&mut self, command: &str) -> Result<String, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words[..] {
+            ["switches"] => Ok(self
+                .debug_switches()
+                .iter()
+                .map(|(name, on)| format!("{} {}\n", name, if *on { "on" } else { "off" }))
+                .collect()),
+            ["switch", name, state @ ("on" | "off")] => {
+                if self.debug_switch(name, state == "on") {
+                    Ok(format!("{} {}\n", name, state))
+                } else {
+                    Err(format!(
+                        "unknown switch {}, expected one of {}",
+                        name, SWITCH_NAMES
+                    ))
+                }
+            }
+            ["switch", ..] => Err("usage: switch <name> <on|off>".to_string()),
+            _ => Err(format!("unknown command {}", command.trim())),
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//DMA controller: 4 channels able to move data around without the CPU.
+//While a channel is transferring the CPU is stalled, its cycles are charged like any other access.
+//Source: https://problemkaputt.de/gbatek.htm#gbadmatransfers
+use crate::interrupt::Interrupt;
+use crate::io;
+use crate::memory::{is_io, Memory};
+use crate::waitstate::AccessWidth;
+
+///Destination of the sound DMA feeding Direct Sound A
+pub const FIFO_A: u32 = 0x0400_00A0;
+///Destination of the sound DMA feeding Direct Sound B
+pub const FIFO_B: u32 = 0x0400_00A4;
+
+///How an address changes after each unit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AddressControl {
+    Increment,
+    Decrement,
+    Fixed,
+    ///Increment, then reload the initial value when a repeated transfer restarts(destination only)
+    Reload,
+}
+
+impl AddressControl {
+    fn from_bits(bits: u16) -> Self {
+        match bits & 3 {
+            0 => AddressControl::Increment,
+            1 => AddressControl::Decrement,
+            2 => AddressControl::Fixed,
+            _ => AddressControl::Reload,
+        }
+    }
+}
+
+///When a channel starts
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DmaTiming {
+    Immediate,
+    VBlank,
+    HBlank,
+    ///Sound FIFO for DMA1/DMA2, video capture for DMA3, prohibited for DMA0
+    Special,
+}
+
+///A single DMA channel
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DmaChannel {
+    pub id: usize,
+    ///DMAxCNT_H
+    pub control: u16,
+    //internal registers, latched from DMAxSAD/DAD/CNT_L when the channel gets enabled
+    pub source: u32,
+    pub dest: u32,
+    pub count: u32,
+}
+
+impl DmaChannel {
+    pub fn enabled(&self) -> bool {
+        self.control & 0x8000 != 0
+    }
+    pub fn dest_control(&self) -> AddressControl {
+        AddressControl::from_bits(self.control >> 5)
+    }
+    pub fn source_control(&self) -> AddressControl {
+        AddressControl::from_bits(self.control >> 7)
+    }
+    pub fn repeat(&self) -> bool {
+        self.control & 0x0200 != 0
+    }
+    ///Whether the unit is 32 bit(otherwise 16 bit)
+    pub fn word(&self) -> bool {
+        self.control & 0x0400 != 0
+    }
+    pub fn timing(&self) -> DmaTiming {
+        match (self.control >> 12) & 3 {
+            0 => DmaTiming::Immediate,
+            1 => DmaTiming::VBlank,
+            2 => DmaTiming::HBlank,
+            _ => DmaTiming::Special,
+        }
+    }
+    pub fn irq(&self) -> bool {
+        self.control & 0x4000 != 0
+    }
+    ///DMA0 can only read internal memory, the others can read the GamePak too
+    pub fn source_mask(&self) -> u32 {
+        if self.id == 0 {
+            0x07FF_FFFF
+        } else {
+            0x0FFF_FFFF
+        }
+    }
+    ///Only DMA3 can write to the GamePak
+    pub fn dest_mask(&self) -> u32 {
+        if self.id == 3 {
+            0x0FFF_FFFF
+        } else {
+            0x07FF_FFFF
+        }
+    }
+    ///Maximum number of units, used when the count register is 0
+    pub fn max_count(&self) -> u32 {
+        if self.id == 3 {
+            0x1_0000
+        } else {
+            0x4000
+        }
+    }
+    pub fn interrupt(&self) -> Interrupt {
+        match self.id {
+            0 => Interrupt::Dma0,
+            1 => Interrupt::Dma1,
+            2 => Interrupt::Dma2,
+            _ => Interrupt::Dma3,
+        }
+    }
+    ///Whether this is a sound FIFO transfer: 4 words to a fixed address, count and width ignored
+    pub fn is_sound(&self) -> bool {
+        (self.id == 1 || self.id == 2) && self.timing() == DmaTiming::Special
+    }
+}
+
+///The 4 DMA channels. Lower channels have higher priority
+pub struct Dma {
+    pub channels: [DmaChannel; 4],
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        let mut channels = [DmaChannel::default(); 4];
+        for (id, channel) in channels.iter_mut().enumerate() {
+            channel.id = id;
+        }
+        Dma { channels }
+    }
+}
+
+///Offset(from IO_BASE) of DMAxSAD, the other registers of the channel follow it
+pub fn register_base(id: usize) -> u32 {
+    0x0B0 + 12 * id as u32
+}
+
+/*****************
+ * DMA TRANSFERS *
+ *****************/
+impl Memory {
+    ///Called after a write to DMAxCNT_H. Enabling a channel latches its registers and,
+    ///for immediate channels, starts the transfer
+    pub(crate) fn dma_write_control(&mut self, id: usize) {
+        let base = register_base(id);
+        let control = self.io_stored(io::register_at(base + 10).unwrap()) as u16;
+        let was_enabled = self.dma.channels[id].enabled();
+        self.dma.channels[id].control = control;
+        if was_enabled || !self.dma.channels[id].enabled() {
+            return;
+        }
+        let source = self.io_stored(io::register_at(base).unwrap());
+        let dest = self.io_stored(io::register_at(base + 4).unwrap());
+        let count = self.dma_reload_count(id);
+        let channel = &mut self.dma.channels[id];
+        channel.source = source & channel.source_mask();
+        channel.dest = dest & channel.dest_mask();
+        channel.count = count;
+        if self.dma.channels[id].timing() == DmaTiming::Immediate {
+            self.dma_transfer(id);
+        }
+    }
+
+    ///Starts every enabled channel waiting for VBlank or HBlank, in priority order
+    pub fn dma_trigger(&mut self, timing: DmaTiming) {
+        for id in 0..4 {
+            let channel = self.dma.channels[id];
+            if channel.enabled() && channel.timing() == timing && timing != DmaTiming::Special {
+                self.dma_transfer(id);
+            }
+        }
+    }
+
+    ///Called when a Direct Sound FIFO runs low, refills it through DMA1 or DMA2
+    pub fn dma_sound_fifo(&mut self, fifo: u32) {
+        for id in 1..=2 {
+            let channel = self.dma.channels[id];
+            if channel.enabled() && channel.is_sound() && channel.dest == fifo {
+                self.dma_transfer(id);
+            }
+        }
+    }
+
+    ///Video capture DMA3: runs once per line from line 2 to 161, then stops by itself
+    pub fn dma_video_capture(&mut self, line: u16) {
+        let channel = self.dma.channels[3];
+        if !channel.enabled() || channel.timing() != DmaTiming::Special {
+            return;
+        }
+        match line {
+            2..=161 => self.dma_transfer(3),
+            162 => self.dma_disable(3),
+            _ => {}
+        }
+    }
+
+    fn dma_transfer(&mut self, id: usize) {
+        let mut channel = self.dma.channels[id];
+        let sound = channel.is_sound();
+        let (count, width) = match (sound, channel.word()) {
+            (true, _) => (4, AccessWidth::Word),
+            (false, true) => (channel.count, AccessWidth::Word),
+            (false, false) => (channel.count, AccessWidth::Half),
+        };
+        let unit = width as i32;
+        let mut source_step = match channel.source_control() {
+            AddressControl::Decrement => -unit,
+            AddressControl::Fixed => 0,
+            _ => unit,
+        };
+        // the GamePak can only be read sequentially
+        if (0x0800_0000..0x0E00_0000).contains(&channel.source) {
+            source_step = unit;
+        }
+        let dest_step = match channel.dest_control() {
+            _ if sound => 0,
+            AddressControl::Decrement => -unit,
+            AddressControl::Fixed => 0,
+            _ => unit,
+        };
+
+        // 2 internal cycles to start, then source and destination each run their own
+        // sequential stream: only the first unit is non sequential
+        let waitstate = self.waitstate();
+        let mut cycles = 2;
+        for i in 0..count {
+            let sequential = i > 0;
+            cycles += waitstate.access_cycles(channel.source, width, sequential);
+            cycles += waitstate.access_cycles(channel.dest, width, sequential);
+            let data = self.dma_load(channel.source, width);
+            self.dma_store(channel.dest, width, data);
+            channel.source = channel.source.wrapping_add_signed(source_step);
+            channel.dest = channel.dest.wrapping_add_signed(dest_step);
+        }
+        self.stall(cycles);
+
+        self.dma.channels[id] = channel;
+        if channel.irq() {
+            self.interrupt.request(channel.interrupt());
+        }
+        if channel.repeat() && channel.timing() != DmaTiming::Immediate {
+            self.dma.channels[id].count = self.dma_reload_count(id);
+            if channel.dest_control() == AddressControl::Reload {
+                let dest = self.io_stored(io::register_at(register_base(id) + 4).unwrap());
+                self.dma.channels[id].dest = dest & channel.dest_mask();
+            }
+        } else {
+            self.dma_disable(id);
+        }
+    }
+
+    ///Clears the enable bit, both internally and in DMAxCNT_H
+    fn dma_disable(&mut self, id: usize) {
+        self.dma.channels[id].control &= !0x8000;
+        let reg = io::register_at(register_base(id) + 10).unwrap();
+        let control = self.io_stored(reg) & !0x8000;
+        self.io_store(reg, control);
+    }
+
+    ///Returns the unit count written in DMAxCNT_L, 0 meaning the maximum
+    fn dma_reload_count(&self, id: usize) -> u32 {
+        let channel = self.dma.channels[id];
+        let count = self.io_stored(io::register_at(register_base(id) + 8).unwrap());
+        match count & (channel.max_count() - 1) {
+            0 => channel.max_count(),
+            count => count,
+        }
+    }
+
+    ///Reads a unit for a transfer. Unmapped areas(and the BIOS) return the open bus value,
+    ///that is the last unit read
+    fn dma_load(&self, address: u32, width: AccessWidth) -> u32 {
+        let address = address & !(width as u32 - 1);
+        match dma_bus_address(address, false) {
+            Some(address) if width == AccessWidth::Word => self.load_32(address),
+            Some(address) => self.load_16(address) as u32,
+            None if width == AccessWidth::Word => self.open_bus.get(),
+            None => (self.open_bus.get() >> ((address & 2) * 8)) & 0xFFFF,
+        }
+    }
+
+    ///Writes a unit for a transfer, writes to ROM and unmapped areas are lost
+    fn dma_store(&mut self, address: u32, width: AccessWidth, data: u32) {
+        let address = address & !(width as u32 - 1);
+        let Some(address) = dma_bus_address(address, true) else {
+            return;
+        };
+        let data = &data.to_le_bytes()[..width as usize];
+        self.ppu_sync(address);
+        if is_io(address) {
+            return self.io_write(address - io::IO_BASE, data);
+        }
+        for (i, byte) in data.iter().enumerate() {
+            self.store_8(address + i as u32, *byte);
+        }
+    }
+}
+
+///Where a DMA access lands, with the memory mirrors applied, or None if nothing answers.<br>
+///The GamePak is read only: EEPROM and save memory are not emulated, so writes there are lost
+fn dma_bus_address(address: u32, write: bool) -> Option<u32> {
+    let offset = address & 0x00FF_FFFF;
+    match address >> 24 {
+        0x02 => Some(0x0200_0000 | (offset & 0x3_FFFF)),
+        0x03 => Some(0x0300_0000 | (offset & 0x7FFF)),
+        0x04 if offset < 0x400 => Some(address),
+        0x05 => Some(0x0500_0000 | (offset & 0x3FF)),
+        // 128KBytes mirror, the last 32KBytes repeat the OBJ tiles
+        0x06 => match offset & 0x1_FFFF {
+            offset @ 0x1_8000.. => Some(0x0600_0000 | (offset - 0x8000)),
+            offset => Some(0x0600_0000 | offset),
+        },
+        0x07 => Some(0x0700_0000 | (offset & 0x3FF)),
+        0x08..=0x0D if !write => Some(address),
+        _ => None,
+    }
+}
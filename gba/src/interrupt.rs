@@ -0,0 +1,68 @@
+//GBA interrupt controller: IE(0x0400_0200), IF(0x0400_0202) and IME(0x0400_0208).
+//Source: https://problemkaputt.de/gbatek.htm#gbainterruptcontrol
+
+///Address of the word the interrupt handler ORs the serviced IF bits into, checked by IntrWait
+pub const BIOS_IF: u32 = 0x0300_7FF8;
+///Address of the pointer to the user interrupt handler, called by the BIOS handler
+pub const USER_HANDLER: u32 = 0x0300_7FFC;
+
+///The 14 interrupt sources, the value is the bit used in IE and IF
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interrupt {
+    VBlank = 0,
+    HBlank = 1,
+    VCount = 2,
+    Timer0 = 3,
+    Timer1 = 4,
+    Timer2 = 5,
+    Timer3 = 6,
+    Serial = 7,
+    Dma0 = 8,
+    Dma1 = 9,
+    Dma2 = 10,
+    Dma3 = 11,
+    Keypad = 12,
+    GamePak = 13,
+}
+
+impl Interrupt {
+    ///Returns the IE/IF mask of the interrupt
+    pub fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+///Keeps track of enabled and requested interrupts
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct InterruptController {
+    ///IE: interrupts allowed to reach the CPU
+    pub enabled: u16,
+    ///IF: interrupts requested by the hardware and not acknowledged yet
+    pub requested: u16,
+    ///IME: master enable
+    pub master: bool,
+}
+
+impl InterruptController {
+    ///Called by the hardware to request an interrupt
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.requested |= interrupt.mask();
+    }
+
+    ///Acknowledges the interrupts whose bits are set in the mask(IF is write-1-to-clear)
+    pub fn acknowledge(&mut self, mask: u16) {
+        self.requested &= !mask;
+    }
+
+    ///Whether any enabled interrupt is waiting, regardless of IME.<br>
+    ///This is what wakes the CPU up from Halt.
+    pub fn waiting(&self) -> bool {
+        self.enabled & self.requested & 0x3FFF != 0
+    }
+
+    ///Whether the IRQ line of the CPU is asserted.<br>
+    ///The CPU still ignores it while CPSR.I is set.
+    pub fn irq_line(&self) -> bool {
+        self.master && self.waiting()
+    }
+}
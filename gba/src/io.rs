@@ -0,0 +1,222 @@
+//Typed map of the GBA I/O registers(0x0400_0000 to 0x0400_03FF).
+//Each register knows its name, its width, which bits can be read and which bits can be written.
+//The actual values are still stored byte by byte inside `Memory`, this module only describes them.
+//Source: https://problemkaputt.de/gbatek.htm#gbaiomap
+
+///Base address of the I/O region
+pub const IO_BASE: u32 = 0x0400_0000;
+
+//Offsets(from IO_BASE) of the registers that need special handling somewhere else
+pub const DISPCNT: u32 = 0x000;
+pub const DISPSTAT: u32 = 0x004;
+pub const VCOUNT: u32 = 0x006;
+pub const KEYINPUT: u32 = 0x130;
+pub const KEYCNT: u32 = 0x132;
+pub const IE: u32 = 0x200;
+pub const IF: u32 = 0x202;
+pub const WAITCNT: u32 = 0x204;
+pub const IME: u32 = 0x208;
+pub const POSTFLG: u32 = 0x300;
+pub const HALTCNT: u32 = 0x301;
+
+///Width of an I/O register
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IoWidth {
+    Byte = 1,
+    Half = 2,
+    Word = 4,
+}
+
+///Hardware block a register belongs to.<br>
+///Memory uses it to forward reads and writes to the right peripheral.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IoOwner {
+    Lcd,
+    Sound,
+    Dma,
+    Timer,
+    Serial,
+    Keypad,
+    Interrupt,
+    System,
+}
+
+///Description of a single I/O register
+#[derive(Debug)]
+pub struct IoRegister {
+    pub name: &'static str,
+    ///Offset from IO_BASE
+    pub offset: u32,
+    pub width: IoWidth,
+    ///Bits returned on a read, the others read as 0
+    pub read_mask: u32,
+    ///Bits that can be changed by a write, the others keep their value
+    pub write_mask: u32,
+    pub owner: IoOwner,
+}
+
+impl IoRegister {
+    ///Returns the absolute address of the register
+    pub fn address(&self) -> u32 {
+        IO_BASE + self.offset
+    }
+    ///Returns whether the given offset falls inside this register
+    pub fn contains(&self, offset: u32) -> bool {
+        offset >= self.offset && offset < self.offset + self.width as u32
+    }
+}
+
+const fn reg(
+    name: &'static str,
+    offset: u32,
+    width: IoWidth,
+    read_mask: u32,
+    write_mask: u32,
+    owner: IoOwner,
+) -> IoRegister {
+    IoRegister {
+        name,
+        offset,
+        width,
+        read_mask,
+        write_mask,
+        owner,
+    }
+}
+
+use IoOwner::*;
+use IoWidth::*;
+
+///All the known I/O registers, sorted by offset
+pub static IO_REGISTERS: &[IoRegister] = &[
+    //LCD
+    reg("DISPCNT", 0x000, Half, 0xFFFF, 0xFFF7, Lcd),
+    reg("GREENSWP", 0x002, Half, 0x0001, 0x0001, Lcd),
+    reg("DISPSTAT", 0x004, Half, 0xFF3F, 0xFF38, Lcd),
+    reg("VCOUNT", 0x006, Half, 0x00FF, 0x0000, Lcd),
+    reg("BG0CNT", 0x008, Half, 0xDFFF, 0xDFFF, Lcd),
+    reg("BG1CNT", 0x00A, Half, 0xDFFF, 0xDFFF, Lcd),
+    reg("BG2CNT", 0x00C, Half, 0xFFFF, 0xFFFF, Lcd),
+    reg("BG3CNT", 0x00E, Half, 0xFFFF, 0xFFFF, Lcd),
+    reg("BG0HOFS", 0x010, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG0VOFS", 0x012, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG1HOFS", 0x014, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG1VOFS", 0x016, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG2HOFS", 0x018, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG2VOFS", 0x01A, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG3HOFS", 0x01C, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG3VOFS", 0x01E, Half, 0x0000, 0x01FF, Lcd),
+    reg("BG2PA", 0x020, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG2PB", 0x022, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG2PC", 0x024, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG2PD", 0x026, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG2X", 0x028, Word, 0x0000_0000, 0x0FFF_FFFF, Lcd),
+    reg("BG2Y", 0x02C, Word, 0x0000_0000, 0x0FFF_FFFF, Lcd),
+    reg("BG3PA", 0x030, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG3PB", 0x032, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG3PC", 0x034, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG3PD", 0x036, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BG3X", 0x038, Word, 0x0000_0000, 0x0FFF_FFFF, Lcd),
+    reg("BG3Y", 0x03C, Word, 0x0000_0000, 0x0FFF_FFFF, Lcd),
+    reg("WIN0H", 0x040, Half, 0x0000, 0xFFFF, Lcd),
+    reg("WIN1H", 0x042, Half, 0x0000, 0xFFFF, Lcd),
+    reg("WIN0V", 0x044, Half, 0x0000, 0xFFFF, Lcd),
+    reg("WIN1V", 0x046, Half, 0x0000, 0xFFFF, Lcd),
+    reg("WININ", 0x048, Half, 0x3F3F, 0x3F3F, Lcd),
+    reg("WINOUT", 0x04A, Half, 0x3F3F, 0x3F3F, Lcd),
+    reg("MOSAIC", 0x04C, Half, 0x0000, 0xFFFF, Lcd),
+    reg("BLDCNT", 0x050, Half, 0x3FFF, 0x3FFF, Lcd),
+    reg("BLDALPHA", 0x052, Half, 0x1F1F, 0x1F1F, Lcd),
+    reg("BLDY", 0x054, Half, 0x0000, 0x001F, Lcd),
+    //Sound
+    reg("SOUND1CNT_L", 0x060, Half, 0x007F, 0x007F, Sound),
+    reg("SOUND1CNT_H", 0x062, Half, 0xFFC0, 0xFFFF, Sound),
+    reg("SOUND1CNT_X", 0x064, Half, 0x4000, 0xC7FF, Sound),
+    reg("SOUND2CNT_L", 0x068, Half, 0xFFC0, 0xFFFF, Sound),
+    reg("SOUND2CNT_H", 0x06C, Half, 0x4000, 0xC7FF, Sound),
+    reg("SOUND3CNT_L", 0x070, Half, 0x00E0, 0x00E0, Sound),
+    reg("SOUND3CNT_H", 0x072, Half, 0xE000, 0xE0FF, Sound),
+    reg("SOUND3CNT_X", 0x074, Half, 0x4000, 0xC7FF, Sound),
+    reg("SOUND4CNT_L", 0x078, Half, 0xFF00, 0xFF3F, Sound),
+    reg("SOUND4CNT_H", 0x07C, Half, 0x40FF, 0xC0FF, Sound),
+    reg("SOUNDCNT_L", 0x080, Half, 0xFF77, 0xFF77, Sound),
+    reg("SOUNDCNT_H", 0x082, Half, 0x770F, 0xFF0F, Sound),
+    reg("SOUNDCNT_X", 0x084, Half, 0x008F, 0x0080, Sound),
+    reg("SOUNDBIAS", 0x088, Half, 0xC3FE, 0xC3FE, Sound),
+    reg("WAVE_RAM0", 0x090, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Sound),
+    reg("WAVE_RAM1", 0x094, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Sound),
+    reg("WAVE_RAM2", 0x098, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Sound),
+    reg("WAVE_RAM3", 0x09C, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Sound),
+    reg("FIFO_A", 0x0A0, Word, 0x0000_0000, 0xFFFF_FFFF, Sound),
+    reg("FIFO_B", 0x0A4, Word, 0x0000_0000, 0xFFFF_FFFF, Sound),
+    //DMA
+    reg("DMA0SAD", 0x0B0, Word, 0x0000_0000, 0x07FF_FFFF, Dma),
+    reg("DMA0DAD", 0x0B4, Word, 0x0000_0000, 0x07FF_FFFF, Dma),
+    reg("DMA0CNT_L", 0x0B8, Half, 0x0000, 0x3FFF, Dma),
+    reg("DMA0CNT_H", 0x0BA, Half, 0xF7E0, 0xF7E0, Dma),
+    reg("DMA1SAD", 0x0BC, Word, 0x0000_0000, 0x0FFF_FFFF, Dma),
+    reg("DMA1DAD", 0x0C0, Word, 0x0000_0000, 0x07FF_FFFF, Dma),
+    reg("DMA1CNT_L", 0x0C4, Half, 0x0000, 0x3FFF, Dma),
+    reg("DMA1CNT_H", 0x0C6, Half, 0xF7E0, 0xF7E0, Dma),
+    reg("DMA2SAD", 0x0C8, Word, 0x0000_0000, 0x0FFF_FFFF, Dma),
+    reg("DMA2DAD", 0x0CC, Word, 0x0000_0000, 0x07FF_FFFF, Dma),
+    reg("DMA2CNT_L", 0x0D0, Half, 0x0000, 0x3FFF, Dma),
+    reg("DMA2CNT_H", 0x0D2, Half, 0xF7E0, 0xF7E0, Dma),
+    reg("DMA3SAD", 0x0D4, Word, 0x0000_0000, 0x0FFF_FFFF, Dma),
+    reg("DMA3DAD", 0x0D8, Word, 0x0000_0000, 0x0FFF_FFFF, Dma),
+    reg("DMA3CNT_L", 0x0DC, Half, 0x0000, 0xFFFF, Dma),
+    reg("DMA3CNT_H", 0x0DE, Half, 0xFFE0, 0xFFE0, Dma),
+    //Timers
+    reg("TM0CNT_L", 0x100, Half, 0xFFFF, 0xFFFF, Timer),
+    reg("TM0CNT_H", 0x102, Half, 0x00C3, 0x00C3, Timer),
+    reg("TM1CNT_L", 0x104, Half, 0xFFFF, 0xFFFF, Timer),
+    reg("TM1CNT_H", 0x106, Half, 0x00C7, 0x00C7, Timer),
+    reg("TM2CNT_L", 0x108, Half, 0xFFFF, 0xFFFF, Timer),
+    reg("TM2CNT_H", 0x10A, Half, 0x00C7, 0x00C7, Timer),
+    reg("TM3CNT_L", 0x10C, Half, 0xFFFF, 0xFFFF, Timer),
+    reg("TM3CNT_H", 0x10E, Half, 0x00C7, 0x00C7, Timer),
+    //Serial
+    reg("SIOMULTI0", 0x120, Half, 0xFFFF, 0xFFFF, Serial),
+    reg("SIOMULTI1", 0x122, Half, 0xFFFF, 0xFFFF, Serial),
+    reg("SIOMULTI2", 0x124, Half, 0xFFFF, 0xFFFF, Serial),
+    reg("SIOMULTI3", 0x126, Half, 0xFFFF, 0xFFFF, Serial),
+    reg("SIOCNT", 0x128, Half, 0xFFFF, 0xFFFF, Serial),
+    reg("SIOMLT_SEND", 0x12A, Half, 0xFFFF, 0xFFFF, Serial),
+    //Keypad
+    reg("KEYINPUT", 0x130, Half, 0x03FF, 0x0000, Keypad),
+    reg("KEYCNT", 0x132, Half, 0xC3FF, 0xC3FF, Keypad),
+    //Serial(again)
+    reg("RCNT", 0x134, Half, 0xC1FF, 0xC1FF, Serial),
+    reg("JOYCNT", 0x140, Half, 0x0047, 0x0047, Serial),
+    reg("JOY_RECV", 0x150, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Serial),
+    reg("JOY_TRANS", 0x154, Word, 0xFFFF_FFFF, 0xFFFF_FFFF, Serial),
+    reg("JOYSTAT", 0x158, Half, 0x003A, 0x0030, Serial),
+    //Interrupt, waitstate and power-down control
+    reg("IE", 0x200, Half, 0x3FFF, 0x3FFF, Interrupt),
+    //IF is write-1-to-clear, so the plain write path must not touch it
+    reg("IF", 0x202, Half, 0x3FFF, 0x0000, Interrupt),
+    reg("WAITCNT", 0x204, Half, 0xDFFF, 0x5FFF, System),
+    reg("IME", 0x208, Half, 0x0001, 0x0001, Interrupt),
+    reg("POSTFLG", 0x300, Byte, 0x01, 0x01, System),
+    reg("HALTCNT", 0x301, Byte, 0x00, 0x00, System),
+];
+
+///Returns the register containing the given offset(from IO_BASE), if any
+pub fn register_at(offset: u32) -> Option<&'static IoRegister> {
+    // registers are sorted, so the candidate is the last one starting at or before offset
+    let index = IO_REGISTERS.partition_point(|reg| reg.offset <= offset);
+    if index == 0 {
+        return None;
+    }
+    let candidate = &IO_REGISTERS[index - 1];
+    if candidate.contains(offset) {
+        Some(candidate)
+    } else {
+        None
+    }
+}
+
+///Returns the register with the given name, if any
+pub fn register_by_name(name: &str) -> Option<&'static IoRegister> {
+    IO_REGISTERS.iter().find(|reg| reg.name == name)
+}
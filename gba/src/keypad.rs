@@ -0,0 +1,141 @@
+//Keypad: KEYINPUT(0x0400_0130) and KEYCNT(0x0400_0132).
+//The frontend feeds the keys through an `InputSource`, polled once per frame.
+//Source: https://problemkaputt.de/gbatek.htm#gbakeypadinput
+use crate::interrupt::Interrupt;
+use crate::io;
+use crate::memory::Memory;
+
+///The 10 GBA keys, the value is the bit used in KEYINPUT and KEYCNT
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    A = 0,
+    B = 1,
+    Select = 2,
+    Start = 3,
+    Right = 4,
+    Left = 5,
+    Up = 6,
+    Down = 7,
+    R = 8,
+    L = 9,
+}
+
+impl Key {
+    pub const ALL: [Key; 10] = [
+        Key::A,
+        Key::B,
+        Key::Select,
+        Key::Start,
+        Key::Right,
+        Key::Left,
+        Key::Up,
+        Key::Down,
+        Key::R,
+        Key::L,
+    ];
+
+    ///Returns the KEYINPUT/KEYCNT mask of the key
+    pub fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+///Keys mask covering the 10 keys
+pub const ALL_KEYS: u16 = 0x03FF;
+
+///Something able to tell which keys are held: a window, a gamepad, a movie file...
+pub trait InputSource {
+    ///Returns the keys held right now, as a mask of `Key` bits(1 = pressed)
+    fn pressed(&mut self) -> u16;
+    ///Keys with autofire: while held they alternate between pressed and released
+    fn turbo(&self) -> u16 {
+        0
+    }
+    ///Frames a turbo key stays pressed, then released, before toggling again
+    fn turbo_period(&self) -> u32 {
+        2
+    }
+    ///Whether opposite directions(left+right, up+down) can be held together.<br>
+    ///A real D-pad cannot do it and some games misbehave when it happens, so by default
+    ///both directions of such a pair are dropped.
+    fn allow_opposite_directions(&self) -> bool {
+        false
+    }
+}
+
+///Keypad state
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Keypad {
+    ///Keys held, 1 = pressed(KEYINPUT is the complement)
+    pub pressed: u16,
+    ///KEYCNT
+    pub control: u16,
+    ///Frames polled so far, drives turbo
+    pub frame: u64,
+}
+
+impl Keypad {
+    ///Value of KEYINPUT: active low, a pressed key reads 0
+    pub fn keyinput(&self) -> u16 {
+        !self.pressed & ALL_KEYS
+    }
+
+    ///Reads the keys from the source, applying turbo and dropping impossible combinations
+    pub fn poll(&mut self, source: &mut dyn InputSource) {
+        let mut pressed = source.pressed() & ALL_KEYS;
+        let period = source.turbo_period().max(1) as u64;
+        if (self.frame / period) % 2 == 1 {
+            pressed &= !source.turbo();
+        }
+        if !source.allow_opposite_directions() {
+            for pair in [
+                Key::Left.mask() | Key::Right.mask(),
+                Key::Up.mask() | Key::Down.mask(),
+            ] {
+                if pressed & pair == pair {
+                    pressed &= !pair;
+                }
+            }
+        }
+        self.pressed = pressed;
+        self.frame += 1;
+    }
+
+    ///Whether the KEYCNT interrupt condition holds
+    pub fn irq_condition(&self) -> bool {
+        if self.control & 0x4000 == 0 {
+            return false;
+        }
+        let selected = self.control & ALL_KEYS;
+        if self.control & 0x8000 != 0 {
+            // AND mode: every selected key must be held
+            self.pressed & selected == selected
+        } else {
+            // OR mode: any selected key
+            self.pressed & selected != 0
+        }
+    }
+}
+
+/**********
+ * KEYPAD *
+ **********/
+impl Memory {
+    ///Polls the input source, to be called once per frame
+    pub fn keypad_poll(&mut self, source: &mut dyn InputSource) {
+        self.keypad.poll(source);
+        self.keypad_check_irq();
+    }
+
+    ///Called after a write to KEYCNT
+    pub(crate) fn keypad_write_control(&mut self) {
+        self.keypad.control = self.io_stored(io::register_at(io::KEYCNT).unwrap()) as u16;
+        self.keypad_check_irq();
+    }
+
+    fn keypad_check_irq(&mut self) {
+        if self.keypad.irq_condition() {
+            self.interrupt.request(Interrupt::Keypad);
+        }
+    }
+}
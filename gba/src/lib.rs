@@ -1 +1,14 @@
+pub mod apu;
+pub mod cartridge;
+pub mod debug;
+pub mod dma;
+pub mod interrupt;
+pub mod io;
+pub mod keypad;
 pub mod memory;
+pub mod power;
+pub mod ppu;
+pub mod scheduler;
+pub mod timer;
+pub mod video;
+pub mod waitstate;
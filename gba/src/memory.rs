@@ -1,4 +1,16 @@
-use arm7tdmi::cpu::MemoryInterface;
+use crate::apu::{psg, Apu};
+use crate::cartridge::{Cartridge, Header};
+use crate::dma::{self, Dma};
+use crate::interrupt::{InterruptController, BIOS_IF};
+use crate::io::{self, IoOwner, IoRegister};
+use crate::keypad::Keypad;
+use crate::power::PowerState;
+use crate::ppu::Ppu;
+use crate::scheduler::Scheduler;
+use crate::timer::{self, Timers};
+use crate::waitstate::{AccessWidth, WaitState};
+use arm7tdmi::cpu::{MemoryInterface, SoftwareInterrupt};
+use core::cell::Cell;
 
 ///Simple GBA Memory representation
 pub struct Memory {
@@ -7,16 +19,30 @@ pub struct Memory {
     pub bios: Box<[u8; 16 * 1024]>,      //16KBytes, 0 to 0x000_03FFF
     board_wram: Box<[u8; 256 * 1024]>,   //256KBytes, 0x0200_0000 to 0x0203_FFFF
     pub chip_wram: Box<[u8; 32 * 1024]>, //32KBytes, 0x0300_0000 to 0x0300_7FFF
-    io_registers: Box<[u8; 1023]>,       //1KByte, 0x0400_0000 to 0x0400_03FE
+    pub(crate) io_registers: Box<[u8; 1024]>, //1KByte, 0x0400_0000 to 0x0400_03FF
     //internal display memory
-    palette_ram: Box<[u8; 1024]>,    //1KByte, 0x0500_0000 to 0x0500_03FF
-    video_ram: Box<[u8; 96 * 1024]>, //96KBytes, 0x0600_0000 to 0x0601_7FFF
-    obj_attributes: Box<[u8; 1024]>, //1Kbyte, 0x0700_0000 to 0x07000_03FF
+    pub(crate) palette_ram: Box<[u8; 1024]>, //1KByte, 0x0500_0000 to 0x0500_03FF
+    pub(crate) video_ram: Box<[u8; 96 * 1024]>, //96KBytes, 0x0600_0000 to 0x0601_7FFF
+    pub(crate) obj_attributes: Box<[u8; 1024]>, //1Kbyte, 0x0700_0000 to 0x07000_03FF
     //external memory, it's actually only 1 region, but it's split into 3 only for wait state
     gamepakrom1: Box<[u8; 32 * 1024 * 1024]>, //32MB, 0x0800_0000 to 0x09FF_FFFF
     gamepakrom2: Box<[u8; 32 * 1024 * 1024]>, //32MB, 0x0A00_0000 to 0x0BFF_FFFF
     gamepakrom3: Box<[u8; 32 * 1024 * 1024]>, //32MB, 0x0C00_0000 to 0x0DFF_FFFF
     gamepaksram: Box<[u8; 64 * 1024]>,        //64KBytes, 0x0E00_0000 to 0x0E00_FFFF
+    //last value seen on the data bus, returned when reading unmapped I/O
+    pub(crate) open_bus: Cell<u32>,
+    //WAITCNT configuration, prefetch buffer and cycles spent on the bus
+    waitstate: Cell<WaitState>,
+    pub interrupt: InterruptController,
+    pub dma: Dma,
+    pub timers: Timers,
+    pub keypad: Keypad,
+    pub power: PowerState,
+    ///An emulated IntrWait is halted between 2 checks of BIOS_IF
+    pub(crate) intr_wait: bool,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub scheduler: Scheduler,
 }
 impl Memory {
     pub fn init_bios(&mut self, data: Vec<u8>) {
@@ -24,6 +50,60 @@ impl Memory {
         // print!("{} e  {}", data.len(), len);
         self.bios[0..len].copy_from_slice(&data[0..len]);
     }
+    ///Maps a cartridge ROM in the GamePak region.<br>
+    ///The same ROM is visible from the 3 wait state windows, past its end reads 0
+    ///(nothing is left of a ROM loaded before).
+    pub fn load_cartridge(&mut self, cartridge: &Cartridge) {
+        let len = cartridge.rom.len();
+        for window in [
+            &mut self.gamepakrom1,
+            &mut self.gamepakrom2,
+            &mut self.gamepakrom3,
+        ] {
+            window[0..len].copy_from_slice(&cartridge.rom);
+            window[len..].fill(0);
+        }
+    }
+    ///Parses the header of the mapped cartridge
+    pub fn cartridge_header(&self) -> Header {
+        Header::parse(&self.gamepakrom1[..0xC0])
+    }
+    ///FNV-1a hash of the RAMs, I/O registers, save memory and time.<br>
+    ///Two runs in the same state give the same hash, so it tells whether a bug report's
+    ///screenshot comes from the state seen locally.
+    pub fn state_hash(&self) -> u64 {
+        let regions: [&[u8]; 8] = [
+            &self.board_wram[..],
+            &self.chip_wram[..],
+            &self.io_registers[..],
+            &self.palette_ram[..],
+            &self.video_ram[..],
+            &self.obj_attributes[..],
+            &self.gamepaksram[..],
+            &self.now().to_le_bytes(),
+        ];
+        regions
+            .iter()
+            .flat_map(|region| region.iter())
+            .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3)
+            })
+    }
+    ///ORs the given IF bits into the BIOS acknowledge word(0x0300_7FF8), like a well behaved
+    ///interrupt handler does. IntrWait and VBlankIntrWait wait on this word, not on IF.
+    pub fn acknowledge_bios_if(&mut self, mask: u16) {
+        let offset = (BIOS_IF & 0x7FFF) as usize;
+        let flags = u16::from_le_bytes([self.chip_wram[offset], self.chip_wram[offset + 1]]) | mask;
+        self.chip_wram[offset..offset + 2].copy_from_slice(&flags.to_le_bytes());
+    }
+    ///Current time, in cycles since power on
+    pub fn now(&self) -> u64 {
+        self.scheduler.now + self.waitstate.get().cycles as u64
+    }
+    ///Returns the current bus timing state(WAITCNT and prefetch buffer)
+    pub fn waitstate(&self) -> WaitState {
+        self.waitstate.get()
+    }
     pub fn dbg_dump(&self) {
         for item in self.bios.clone().chunks(4).into_iter() {
             print!("{:#X} ", item[0]);
@@ -34,13 +114,145 @@ impl Memory {
         }
     }
 }
+/**************
+ * I/O ACCESS *
+ **************/
+impl Memory {
+    ///Reads a byte from the I/O region, applying the register read mask.<br>
+    ///Unmapped offsets return the open bus value.
+    fn io_read_8(&self, offset: u32) -> u8 {
+        match io::register_at(offset) {
+            Some(reg) => {
+                let shift = (offset - reg.offset) * 8;
+                let value = match self.io_read_hook(reg) {
+                    Some(value) => value,
+                    None => self.io_stored(reg),
+                };
+                ((value & reg.read_mask) >> shift) as u8
+            }
+            None => (self.open_bus.get() >> ((offset & 3) * 8)) as u8,
+        }
+    }
+
+    ///Writes consecutive bytes to the I/O region, starting at the given offset.<br>
+    ///Only the writable bits get stored, then each register touched by the access is notified
+    ///once to its owner, with the raw written value and the mask of the written bits.
+    pub(crate) fn io_write(&mut self, offset: u32, data: &[u8]) {
+        // the PSG channels play with the old settings up to now
+        if psg::REGISTERS.contains(&offset) {
+            self.psg_sync(self.now());
+        }
+        // an access is at most a word, so it touches at most 4(byte) registers
+        let mut touched: [Option<(&'static IoRegister, u32, u32)>; 4] = [None; 4];
+        let mut count = 0usize;
+        for (i, byte) in data.iter().enumerate() {
+            let byte_offset = offset + i as u32;
+            let Some(reg) = io::register_at(byte_offset) else {
+                continue;
+            };
+            let shift = (byte_offset - reg.offset) * 8;
+            let write_mask = (reg.write_mask >> shift) as u8;
+            let stored = &mut self.io_registers[byte_offset as usize];
+            *stored = (*stored & !write_mask) | (byte & write_mask);
+
+            // a single access can span 2 registers(e.g. a word write to DMA0CNT_L/H)
+            match count.checked_sub(1).and_then(|last| touched[last].as_mut()) {
+                Some((last, value, mask)) if last.offset == reg.offset => {
+                    *value |= (*byte as u32) << shift;
+                    *mask |= 0xFF << shift;
+                }
+                _ => {
+                    touched[count] = Some((reg, (*byte as u32) << shift, 0xFF << shift));
+                    count += 1;
+                }
+            }
+        }
+        for (reg, value, mask) in touched.into_iter().flatten() {
+            self.io_write_hook(reg, value, mask);
+        }
+    }
+
+    ///Returns the value stored for the given register, without masks or side effects
+    pub fn io_stored(&self, reg: &IoRegister) -> u32 {
+        let start = reg.offset as usize;
+        self.io_registers[start..start + reg.width as usize]
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| (acc << 8) | *byte as u32)
+    }
+
+    ///Overwrites the value stored for the given register, bypassing write masks and hooks.<br>
+    ///Used by peripherals to update the bits the CPU cannot write(e.g. status flags).
+    pub fn io_store(&mut self, reg: &IoRegister, value: u32) {
+        let start = reg.offset as usize;
+        for (i, byte) in self.io_registers[start..start + reg.width as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            *byte = (value >> (i * 8)) as u8;
+        }
+    }
+
+    ///Gives the owner of a register the chance to provide a live value(e.g. timer counters)
+    fn io_read_hook(&self, reg: &IoRegister) -> Option<u32> {
+        match reg.owner {
+            IoOwner::Interrupt => match reg.offset {
+                io::IE => Some(self.interrupt.enabled as u32),
+                io::IF => Some(self.interrupt.requested as u32),
+                _ => Some(self.interrupt.master as u32),
+            },
+            IoOwner::Timer if reg.offset.is_multiple_of(4) => {
+                let id = (reg.offset - timer::register_base(0)) / 4;
+                Some(self.timer_read_counter(id as usize) as u32)
+            }
+            IoOwner::Keypad if reg.offset == io::KEYINPUT => Some(self.keypad.keyinput() as u32),
+            IoOwner::Sound => self.psg_read(reg),
+            _ => None,
+        }
+    }
+
+    ///Forwards a write to the peripheral owning the register
+    /// # Arguments
+    /// * **value**: raw value written by the CPU, aligned to the register
+    /// * **mask**: bits of the register covered by the write
+    fn io_write_hook(&mut self, reg: &IoRegister, value: u32, mask: u32) {
+        match reg.owner {
+            IoOwner::Interrupt => match reg.offset {
+                io::IE => self.interrupt.enabled = self.io_stored(reg) as u16,
+                // writing 1 to a bit of IF acknowledges that interrupt
+                io::IF => self.interrupt.acknowledge((value & mask) as u16),
+                _ => self.interrupt.master = self.io_stored(reg) & 1 != 0,
+            },
+            IoOwner::Dma => {
+                let id = ((reg.offset - dma::register_base(0)) / 12) as usize;
+                if reg.offset == dma::register_base(id) + 10 {
+                    self.dma_write_control(id);
+                }
+            }
+            IoOwner::Lcd if reg.offset == io::DISPSTAT => self.lcd_write_status(),
+            // BG2X, BG2Y, BG3X and BG3Y
+            IoOwner::Lcd if matches!(reg.offset, 0x028..=0x02F | 0x038..=0x03F) => {
+                self.ppu_latch_reference(((reg.offset - 0x028) / 0x10) as usize)
+            }
+            IoOwner::Timer => self.timer_write(reg.offset),
+            IoOwner::Keypad if reg.offset == io::KEYCNT => self.keypad_write_control(),
+            IoOwner::Sound => self.sound_write(reg, value, mask),
+            IoOwner::System if reg.offset == io::HALTCNT => self.power_write_haltcnt(value),
+            IoOwner::System if reg.offset == io::WAITCNT => {
+                let waitcnt = self.io_stored(reg) as u16;
+                self.waitstate.get_mut().write_waitcnt(waitcnt);
+            }
+            _ => {}
+        }
+    }
+}
 impl Default for Memory {
     fn default() -> Self {
-        Memory {
+        let mut memory = Memory {
             bios: vec![0; 16 * 1024].into_boxed_slice().try_into().unwrap(),
             board_wram: vec![0; 256 * 1024].into_boxed_slice().try_into().unwrap(),
             chip_wram: vec![0; 32 * 1024].into_boxed_slice().try_into().unwrap(),
-            io_registers: vec![0; 1023].into_boxed_slice().try_into().unwrap(),
+            io_registers: vec![0; 1024].into_boxed_slice().try_into().unwrap(),
             palette_ram: vec![0; 1024].into_boxed_slice().try_into().unwrap(),
             video_ram: vec![0; 96 * 1024].into_boxed_slice().try_into().unwrap(),
             obj_attributes: vec![0; 1024].into_boxed_slice().try_into().unwrap(),
@@ -57,15 +269,28 @@ impl Default for Memory {
                 .try_into()
                 .unwrap(),
             gamepaksram: vec![0; 64 * 1024].into_boxed_slice().try_into().unwrap(),
-        }
+            open_bus: Cell::new(0),
+            waitstate: Cell::new(WaitState::default()),
+            interrupt: InterruptController::default(),
+            dma: Dma::default(),
+            timers: Timers::default(),
+            keypad: Keypad::default(),
+            power: PowerState::default(),
+            intr_wait: false,
+            ppu: Ppu::default(),
+            apu: Apu::default(),
+            scheduler: Scheduler::default(),
+        };
+        memory.lcd_reset();
+        memory
     }
 }
-//TODO: handle the case where youd read/write out of bounds for each memory region
-impl MemoryInterface for Memory {
-    fn new() -> Self {
-        Memory::default()
-    }
-    fn read_8(&self, address: u32) -> u8 {
+/*******************
+ * RAW ACCESS      *
+ *******************/
+impl Memory {
+    ///Reads a byte without any timing side effect
+    fn load_8(&self, address: u32) -> u8 {
         match address {
             // 0x0000_0000..=0x000_03FFF => self.bios[address as usize],
             // 0x0200_0000..=0x0203_FFFF => self.board_wram[(address - 0x3_FFFF) as usize],
@@ -80,8 +305,9 @@ impl MemoryInterface for Memory {
             // 0x0E00_0000..=0x0E00_FFFF => self.gamepaksram[(address - 0xFFFF) as usize],
             0x0000_0000..=0x000_03FFF => self.bios[address as usize],
             0x0200_0000..=0x0203_FFFF => self.board_wram[(address - 0x0200_0000) as usize],
-            0x0300_0000..=0x0300_7FFF => self.chip_wram[(address - 0x0300_0000) as usize],
-            0x0400_0000..=0x0400_03FE => self.io_registers[(address - 0x0400_0000) as usize],
+            // IWRAM is mirrored every 32KBytes, the BIOS reaches 0x0300_7FFC through 0x03FF_FFFC
+            0x0300_0000..=0x03FF_FFFF => self.chip_wram[(address & 0x7FFF) as usize],
+            0x0400_0000..=0x0400_03FF => self.io_read_8(address - 0x0400_0000),
             0x0500_0000..=0x0500_03FF => self.palette_ram[(address - 0x0500_0000) as usize],
             0x0600_0000..=0x0601_7FFF => self.video_ram[(address - 0x0600_0000) as usize],
             0x0700_0000..=0x0700_03FF => self.obj_attributes[(address - 0x0700_0000) as usize],
@@ -92,19 +318,28 @@ impl MemoryInterface for Memory {
             _ => panic!("Invalid address: {:#X}", address),
         }
     }
-    fn read_16(&self, address: u32) -> u16 {
-        u16::from_le_bytes([self.read_8(address), self.read_8(address + 1)])
+    pub(crate) fn load_16(&self, address: u32) -> u16 {
+        let data = u16::from_le_bytes([self.load_8(address), self.load_8(address + 1)]);
+        if !is_io(address) {
+            self.open_bus.set(((data as u32) << 16) | data as u32);
+        }
+        data
     }
-    ///Returns the 32 bit value(stored in little endian) at the given address
-    fn read_32(&self, address: u32) -> u32 {
-        u32::from_le_bytes([
-            self.read_8(address),
-            self.read_8(address + 1),
-            self.read_8(address + 2),
-            self.read_8(address + 3),
-        ])
+    pub(crate) fn load_32(&self, address: u32) -> u32 {
+        let data = u32::from_le_bytes([
+            self.load_8(address),
+            self.load_8(address + 1),
+            self.load_8(address + 2),
+            self.load_8(address + 3),
+        ]);
+        if !is_io(address) {
+            self.open_bus.set(data);
+        }
+        data
     }
-    fn write_8(&mut self, address: u32, data: u8) {
+    ///Writes a byte without any timing side effect
+    pub(crate) fn store_8(&mut self, address: u32, data: u8) {
+        self.ppu_written(address);
         match address {
             // 0x0000_0000..=0x000_03FFF => self.bios[address as usize] = data,
             // 0x0200_0000..=0x0203_FFFF => self.board_wram[(address - 0x3_FFFF) as usize] = data,
@@ -118,9 +353,9 @@ impl MemoryInterface for Memory {
             // 0x0C00_0000..=0x0DFF_FFFF => self.gamepakrom3[(address - 0x1FF_FFFF) as usize] = data,
             // 0x0E00_0000..=0x0E00_FFFF => self.gamepaksram[(address - 0xFFFF) as usize] = data,
             0x0000_0000..=0x000_03FFF => self.bios[address as usize] = data,
-            0x0200_0000..=0x0203_FFFF => self.board_wram[(address - 0200_0000) as usize] = data,
-            0x0300_0000..=0x0300_7FFF => self.chip_wram[(address - 0x0300_0000) as usize] = data,
-            0x0400_0000..=0x0400_03FE => self.io_registers[(address - 0x0400_0000) as usize] = data,
+            0x0200_0000..=0x0203_FFFF => self.board_wram[(address - 0x0200_0000) as usize] = data,
+            0x0300_0000..=0x03FF_FFFF => self.chip_wram[(address & 0x7FFF) as usize] = data,
+            0x0400_0000..=0x0400_03FF => self.io_write(address - 0x0400_0000, &[data]),
             0x0500_0000..=0x0500_03FF => self.palette_ram[(address - 0x0500_0000) as usize] = data,
             0x0600_0000..=0x0601_7FFF => self.video_ram[(address - 0x0600_0000) as usize] = data,
             0x0700_0000..=0x0700_03FF => {
@@ -133,16 +368,88 @@ impl MemoryInterface for Memory {
             _ => panic!("Invalid address: {:#X}", address),
         }
     }
+
+    ///Charges the CPU for cycles where it could not access the bus(e.g. during DMA)
+    pub fn stall(&mut self, cycles: u32) {
+        self.waitstate.get_mut().cycles += cycles;
+    }
+
+    ///Adds the cost of an access to the cycles the CPU will be charged for
+    fn account(&self, address: u32, width: AccessWidth, opcode: bool) {
+        let mut waitstate = self.waitstate.get();
+        waitstate.access(address, width, opcode);
+        waitstate.cycles += self.ppu_contention(address);
+        self.waitstate.set(waitstate);
+    }
+}
+//TODO: handle the case where youd read/write out of bounds for each memory region
+impl MemoryInterface for Memory {
+    fn new() -> Self {
+        Memory::default()
+    }
+    fn read_8(&self, address: u32) -> u8 {
+        self.account(address, AccessWidth::Byte, false);
+        self.load_8(address)
+    }
+    fn read_16(&self, address: u32) -> u16 {
+        self.account(address, AccessWidth::Half, false);
+        self.load_16(address)
+    }
+    ///Returns the 32 bit value(stored in little endian) at the given address
+    fn read_32(&self, address: u32) -> u32 {
+        self.account(address, AccessWidth::Word, false);
+        self.load_32(address)
+    }
+    fn fetch_16(&self, address: u32) -> u16 {
+        self.account(address, AccessWidth::Half, true);
+        self.load_16(address)
+    }
+    fn fetch_32(&self, address: u32) -> u32 {
+        self.account(address, AccessWidth::Word, true);
+        self.load_32(address)
+    }
+    fn take_cycles(&mut self) -> u32 {
+        let cycles = self.waitstate.get_mut().take_cycles();
+        self.scheduler.now += cycles as u64;
+        self.run_events();
+        cycles
+    }
+    fn irq_line(&self) -> bool {
+        self.interrupt.irq_line()
+    }
+    fn software_interrupt(&mut self, function: u8, args: &mut [u32; 4]) -> SoftwareInterrupt {
+        self.power_bios_call(function, args)
+    }
+
+    fn write_8(&mut self, address: u32, data: u8) {
+        self.account(address, AccessWidth::Byte, false);
+        self.ppu_sync(address);
+        self.store_8(address, data);
+    }
     fn write_16(&mut self, address: u32, data: u16) {
-        // todo!();
-        self.write_8(address, data as u8);
-        self.write_8(address + 1, (data >> 8) as u8);
+        self.account(address, AccessWidth::Half, false);
+        self.ppu_sync(address);
+        // I/O registers must see the whole halfword at once
+        if is_io(address) {
+            return self.io_write(address - 0x0400_0000, &data.to_le_bytes());
+        }
+        self.store_8(address, data as u8);
+        self.store_8(address + 1, (data >> 8) as u8);
     }
     fn write_32(&mut self, address: u32, data: u32) {
-        // todo!();
-        self.write_8(address + 0, (data) as u8);
-        self.write_8(address + 1, (data >> 8) as u8);
-        self.write_8(address + 2, (data >> 16) as u8);
-        self.write_8(address + 3, (data >> 24) as u8);
+        self.account(address, AccessWidth::Word, false);
+        self.ppu_sync(address);
+        if is_io(address) {
+            return self.io_write(address - 0x0400_0000, &data.to_le_bytes());
+        }
+        self.store_8(address, (data) as u8);
+        self.store_8(address + 1, (data >> 8) as u8);
+        self.store_8(address + 2, (data >> 16) as u8);
+        self.store_8(address + 3, (data >> 24) as u8);
     }
 }
+
+///Returns whether the address falls in the I/O region
+pub(crate) fn is_io(address: u32) -> bool {
+    (0x0400_0000..=0x0400_03FF).contains(&address)
+}
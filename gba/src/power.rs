@@ -0,0 +1,101 @@
+//Low power modes entered by writing HALTCNT(0x0400_0301), normally from the BIOS Halt, Stop,
+//IntrWait and VBlankIntrWait functions. Those 4 are emulated here, the CPU can't run the BIOS.
+//Source: https://problemkaputt.de/gbatek.htm#gbasystemcontrol
+use crate::interrupt::{Interrupt, BIOS_IF};
+use crate::io;
+use crate::memory::Memory;
+use arm7tdmi::cpu::SoftwareInterrupt;
+
+///Whether the CPU is executing
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PowerState {
+    #[default]
+    Running,
+    ///CPU paused, the rest of the system keeps going. Any interrupt enabled in IE wakes it up
+    Halt,
+    ///Almost everything paused, including video, sound and timers.
+    ///Only keypad, serial and GamePak interrupts wake it up
+    Stop,
+}
+
+/*******************
+ * LOW POWER MODES *
+ *******************/
+impl Memory {
+    ///Called after a write to HALTCNT: bit 7 selects Stop, otherwise Halt
+    pub(crate) fn power_write_haltcnt(&mut self, value: u32) {
+        self.power = if value & 0x80 != 0 {
+            PowerState::Stop
+        } else {
+            PowerState::Halt
+        };
+    }
+
+    ///Wakes the CPU up if an interrupt allows it, then returns whether it is executing.<br>
+    ///IME and CPSR.I are not checked: they only decide whether the IRQ handler is entered after waking.
+    pub fn power_wake(&mut self) -> bool {
+        let wake = match self.power {
+            PowerState::Running => true,
+            PowerState::Halt => self.interrupt.waiting(),
+            PowerState::Stop => {
+                let external =
+                    Interrupt::Keypad.mask() | Interrupt::Serial.mask() | Interrupt::GamePak.mask();
+                self.interrupt.enabled & self.interrupt.requested & external != 0
+            }
+        };
+        if wake {
+            self.power = PowerState::Running;
+        }
+        wake
+    }
+}
+
+/**************
+ * BIOS CALLS *
+ **************/
+impl Memory {
+    ///BIOS functions putting the CPU to sleep, emulated without running the BIOS:
+    /// * 0x02 Halt and 0x03 Stop, like a write to HALTCNT
+    /// * 0x04 IntrWait(r0 = 1: discard the flags already set, r1: interrupts waited for) and
+    ///   0x05 VBlankIntrWait(r0 = 1, r1 = VBlank) turn IME on, then halt until the IRQ handler
+    ///   reports one of the interrupts in BIOS_IF, and clear it.
+    ///   The SWI runs again after each wake up, flags are only discarded the first time
+    ///
+    ///Other functions are left to the BIOS
+    pub(crate) fn power_bios_call(
+        &mut self,
+        function: u8,
+        args: &mut [u32; 4],
+    ) -> SoftwareInterrupt {
+        match function {
+            0x02 => self.power = PowerState::Halt,
+            0x03 => self.power = PowerState::Stop,
+            0x04 | 0x05 => {
+                let (discard, flags) = match function {
+                    0x05 => (true, Interrupt::VBlank.mask()),
+                    _ => (args[0] & 1 != 0, args[1] as u16),
+                };
+                self.interrupt.master = true;
+                self.io_store(io::register_at(io::IME).unwrap(), 1);
+                let offset = (BIOS_IF & 0x7FFF) as usize;
+                let mut bios_if =
+                    u16::from_le_bytes([self.chip_wram[offset], self.chip_wram[offset + 1]]);
+                if discard && !self.intr_wait {
+                    bios_if &= !flags;
+                }
+                let done = bios_if & flags != 0;
+                if done {
+                    bios_if &= !flags;
+                }
+                self.chip_wram[offset..offset + 2].copy_from_slice(&bios_if.to_le_bytes());
+                self.intr_wait = !done;
+                if !done {
+                    self.power = PowerState::Halt;
+                    return SoftwareInterrupt::Again;
+                }
+            }
+            _ => return SoftwareInterrupt::Bios,
+        }
+        SoftwareInterrupt::Done
+    }
+}
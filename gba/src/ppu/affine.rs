@@ -0,0 +1,87 @@
+//Affine backgrounds: rotated and scaled tile maps, BG2 in mode 1, BG2 and BG3 in mode 2.
+//Square maps of 1 byte entries, always 256 colors tiles.
+//Source: https://problemkaputt.de/gbatek.htm#lcdiobgrotationscaling
+use crate::ppu::render::{Line, VideoMemory, TRANSPARENT};
+use crate::ppu::text::BG_VRAM_SIZE;
+use crate::ppu::{AffineRef, BG2PA, BG2PC};
+
+///Map size in pixels for each BGxCNT size setting
+const SIZES: [i32; 4] = [128, 256, 512, 1024];
+
+///Affine transformation of the current line: where each screen dot samples the background
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    ///Texture step for each dot, 8.8 fixed point
+    pub pa: i32,
+    pub pc: i32,
+    ///Internal reference point, texture coordinates of the leftmost dot
+    pub origin: AffineRef,
+}
+
+impl Transform {
+    ///Reads the parameters of BG2 or BG3
+    pub fn new(video: &VideoMemory, bg: usize, origin: AffineRef) -> Self {
+        let base = 0x10 * (bg as u32 - 2);
+        Transform {
+            pa: video.io_16(BG2PA + base) as i16 as i32,
+            pc: video.io_16(BG2PC + base) as i16 as i32,
+            origin,
+        }
+    }
+
+    ///Texture coordinates of a screen dot, in whole pixels.<br>
+    ///The hardware keeps the fraction while stepping and drops it only when sampling,
+    ///so small PA/PC values still move across the texture over a line.
+    pub fn texel(&self, x: usize) -> (i32, i32) {
+        let x = x as i32;
+        (
+            (self.origin.x + self.pa * x) >> 8,
+            (self.origin.y + self.pc * x) >> 8,
+        )
+    }
+}
+
+///Map size in pixels, from BGxCNT
+pub fn map_size(control: u16) -> usize {
+    SIZES[(control >> 14) as usize] as usize
+}
+
+///Draws the current line of an affine background
+/// * **origin**: internal reference point of the background for this line
+pub fn render(video: &VideoMemory, bg: usize, origin: AffineRef, out: &mut Line) {
+    let control = video.bg_control(bg);
+    let wrap = control & 0x2000 != 0;
+    let size = SIZES[(control >> 14) as usize];
+    let transform = Transform::new(video, bg, origin);
+    for (x, pixel) in out.iter_mut().enumerate() {
+        let (mut tx, mut ty) = transform.texel(x);
+        if wrap {
+            tx &= size - 1;
+            ty &= size - 1;
+        } else if tx < 0 || ty < 0 || tx >= size || ty >= size {
+            // display area overflow: outside the map is transparent
+            *pixel = TRANSPARENT;
+            continue;
+        }
+        *pixel = pixel_at(video, control, tx as usize, ty as usize);
+    }
+}
+
+///Color of a map pixel, or TRANSPARENT
+/// * **control**: BGxCNT of the background
+pub(crate) fn pixel_at(video: &VideoMemory, control: u16, tx: usize, ty: usize) -> u16 {
+    let char_base = ((control >> 2) & 3) as usize * 0x4000;
+    let screen_base = ((control >> 8) & 0x1F) as usize * 0x800;
+    let size = map_size(control);
+    let tile = video.vram_8(screen_base + (ty / 8) * (size / 8) + tx / 8) as usize;
+    let offset = char_base + tile * 64 + (ty % 8) * 8 + tx % 8;
+    let index = if offset < BG_VRAM_SIZE {
+        video.vram_8(offset)
+    } else {
+        0
+    };
+    match index {
+        0 => TRANSPARENT,
+        index => video.bg_color(index as usize),
+    }
+}
@@ -0,0 +1,57 @@
+//Bitmap modes: BG2 is a frame buffer in VRAM instead of a tile map.
+// * mode 3: 240x160, 15 bit colors
+// * mode 4: 240x160, 8 bit palette indices, 2 pages
+// * mode 5: 160x128, 15 bit colors, 2 pages
+//The bitmap goes through the BG2 affine transformation like mode 2 backgrounds do.
+//Source: https://problemkaputt.de/gbatek.htm#lcdvrambitmapbgmodes
+use crate::ppu::affine::Transform;
+use crate::ppu::render::{Line, VideoMemory, TRANSPARENT};
+use crate::ppu::AffineRef;
+
+///Offset of the second page in modes 4 and 5
+pub const PAGE_SIZE: usize = 0xA000;
+
+///Width and height of the bitmap of the current mode
+pub fn dimensions(video: &VideoMemory) -> (usize, usize) {
+    if video.mode() == 5 {
+        (160, 128)
+    } else {
+        (240, 160)
+    }
+}
+
+///Offset of the page shown: DISPCNT bit 4 selects it, mode 3 has a single one
+pub fn page(video: &VideoMemory) -> usize {
+    if video.mode() != 3 && video.dispcnt() & 0x10 != 0 {
+        PAGE_SIZE
+    } else {
+        0
+    }
+}
+
+///Draws the BG2 line of a bitmap mode
+pub fn render(video: &VideoMemory, affine: AffineRef, out: &mut Line) {
+    let (width, height) = dimensions(video);
+    let page = page(video);
+    let transform = Transform::new(video, 2, affine);
+    for (x, pixel) in out.iter_mut().enumerate() {
+        let (tx, ty) = transform.texel(x);
+        if tx < 0 || ty < 0 || tx >= width as i32 || ty >= height as i32 {
+            *pixel = TRANSPARENT;
+            continue;
+        }
+        *pixel = pixel_at(video, page, tx as usize, ty as usize);
+    }
+}
+
+///Color of a bitmap dot, or TRANSPARENT
+pub(crate) fn pixel_at(video: &VideoMemory, page: usize, x: usize, y: usize) -> u16 {
+    let dot = y * dimensions(video).0 + x;
+    match video.mode() {
+        4 => match video.vram[page + dot] {
+            0 => TRANSPARENT,
+            index => video.bg_color(index as usize),
+        },
+        _ => video.vram_16(page + dot * 2) & 0x7FFF,
+    }
+}
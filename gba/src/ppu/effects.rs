@@ -0,0 +1,90 @@
+//Color special effects(BLDCNT): alpha blending of two layers, or a fade to white or black.
+//Source: https://problemkaputt.de/gbatek.htm#lcdiocolorspecialeffects
+use crate::ppu::render::VideoMemory;
+use crate::ppu::{BLDALPHA, BLDCNT, BLDY};
+
+///Effect selected by BLDCNT bits 6-7
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Effect {
+    None,
+    Alpha,
+    Brighten,
+    Darken,
+}
+
+///Color effects settings of a line
+#[derive(Copy, Clone, Debug)]
+pub struct Effects {
+    pub effect: Effect,
+    ///Layers blended on top(BG0-BG3, OBJ, backdrop), BLDCNT bits 0-5
+    pub first_target: u8,
+    ///Layers blended below, BLDCNT bits 8-13
+    pub second_target: u8,
+    ///Weights of the first and second target, in 16ths
+    pub eva: u16,
+    pub evb: u16,
+    ///Brightness change, in 16ths
+    pub evy: u16,
+}
+
+impl Effects {
+    pub fn new(video: &VideoMemory) -> Self {
+        let bldcnt = video.io_16(BLDCNT);
+        let alpha = video.io_16(BLDALPHA);
+        Effects {
+            effect: match (bldcnt >> 6) & 3 {
+                0 => Effect::None,
+                1 => Effect::Alpha,
+                2 => Effect::Brighten,
+                _ => Effect::Darken,
+            },
+            first_target: bldcnt as u8 & 0x3F,
+            second_target: (bldcnt >> 8) as u8 & 0x3F,
+            // coefficients above 16 count as 16
+            eva: (alpha & 0x1F).min(16),
+            evb: ((alpha >> 8) & 0x1F).min(16),
+            evy: (video.io_16(BLDY) & 0x1F).min(16),
+        }
+    }
+
+    ///Final color of a dot
+    /// # Arguments
+    /// * **top**: layer(0-3 BG, 4 OBJ, 5 backdrop) and color of the top visible dot
+    /// * **below**: the same for the visible dot under it
+    /// * **semi_transparent**: the top dot belongs to a semi-transparent sprite
+    pub fn apply(&self, top: (usize, u16), below: (usize, u16), semi_transparent: bool) -> u16 {
+        let (layer, color) = top;
+        let second = self.second_target & (1 << below.0) != 0;
+        // semi-transparent sprites are always blended when something can be blended with them
+        if semi_transparent && second {
+            return blend(color, below.1, self.eva, self.evb);
+        }
+        if self.first_target & (1 << layer) == 0 {
+            return color;
+        }
+        match self.effect {
+            Effect::None => color,
+            Effect::Alpha if second => blend(color, below.1, self.eva, self.evb),
+            Effect::Alpha => color,
+            Effect::Brighten => map_components(color, |c| c + (31 - c) * self.evy / 16),
+            Effect::Darken => map_components(color, |c| c - c * self.evy / 16),
+        }
+    }
+}
+
+///Mixes two colors, weights are in 16ths. Each component saturates at 31
+pub fn blend(first: u16, second: u16, eva: u16, evb: u16) -> u16 {
+    let component = |shift: u16| {
+        let a = (first >> shift) & 0x1F;
+        let b = (second >> shift) & 0x1F;
+        ((a * eva + b * evb) / 16).min(31) << shift
+    };
+    component(0) | component(5) | component(10)
+}
+
+fn map_components(color: u16, f: impl Fn(u16) -> u16) -> u16 {
+    [0, 5, 10]
+        .iter()
+        .map(|shift| f((color >> shift) & 0x1F) << shift)
+        .fold(0, |color, component| color | component)
+}
@@ -0,0 +1,168 @@
+//Frames handed to frontends: the PPU draws in its own screen buffer, and each finished picture
+//is copied here when VBlank starts, so readers never see a half drawn frame.
+//Colors stay in the GBA's 15 bit format until a frontend asks for the one it uploads.
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::fmt;
+
+///Output pixel formats, named after their byte order in memory
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PixelFormat {
+    ///4 bytes: red, green, blue, alpha
+    Rgba8888,
+    ///4 bytes: blue, green, red, alpha
+    Bgra8888,
+    ///4 bytes: alpha, red, green, blue
+    Argb8888,
+    ///16 bit little endian: red in bits 11-15, green in bits 5-10, blue in bits 0-4
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgb565 => 2,
+            _ => 4,
+        }
+    }
+}
+
+///Reasons a frame cannot be converted into a caller's buffer
+#[derive(Debug, PartialEq)]
+pub enum FrameBufferError {
+    ///Rows would overlap: the stride is smaller than a row of pixels
+    StrideTooSmall {
+        stride: usize,
+        needed: usize,
+    },
+    BufferTooSmall {
+        len: usize,
+        needed: usize,
+    },
+}
+
+impl fmt::Display for FrameBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameBufferError::StrideTooSmall { stride, needed } => {
+                write!(f, "stride of {} bytes, rows need {}", stride, needed)
+            }
+            FrameBufferError::BufferTooSmall { len, needed } => {
+                write!(f, "buffer of {} bytes, the frame needs {}", len, needed)
+            }
+        }
+    }
+}
+
+///Expands a 5 bit component to 8 bits, so 31 becomes 255
+fn expand(component: u16) -> u8 {
+    ((component << 3) | (component >> 2)) as u8
+}
+
+///Splits a BGR555 color in 8 bit red, green and blue
+pub fn rgb888(color: u16) -> [u8; 3] {
+    [
+        expand(color & 0x1F),
+        expand((color >> 5) & 0x1F),
+        expand((color >> 10) & 0x1F),
+    ]
+}
+
+///Last complete picture
+#[derive(Clone, Debug)]
+pub struct FrameBuffer {
+    pixels: Vec<u16>,
+    ///Number of the frame shown, counting VBlanks since power on
+    frame: u64,
+    ///Set when a frame is presented, cleared when a frontend takes it
+    ready: bool,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        FrameBuffer {
+            pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame: 0,
+            ready: false,
+        }
+    }
+}
+
+impl FrameBuffer {
+    pub const WIDTH: usize = SCREEN_WIDTH;
+    pub const HEIGHT: usize = SCREEN_HEIGHT;
+
+    ///The picture, 240x160 BGR555 colors row by row
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    ///Returns whether a new frame was presented since the last call
+    pub fn take_ready(&mut self) -> bool {
+        core::mem::take(&mut self.ready)
+    }
+
+    ///Called at VBlank: copies the finished picture
+    pub(crate) fn present(&mut self, screen: &[u16], frame: u64) {
+        self.pixels.copy_from_slice(screen);
+        self.frame = frame;
+        self.ready = true;
+    }
+
+    ///Converts the picture into a caller's buffer
+    /// # Arguments
+    /// * **format**: output pixel format
+    /// * **out**: destination, at least `stride * (HEIGHT - 1)` bytes plus a row
+    /// * **stride**: bytes from a row to the next, the padding after each row is left untouched
+    pub fn convert(
+        &self,
+        format: PixelFormat,
+        out: &mut [u8],
+        stride: usize,
+    ) -> Result<(), FrameBufferError> {
+        let row = Self::WIDTH * format.bytes_per_pixel();
+        if stride < row {
+            return Err(FrameBufferError::StrideTooSmall {
+                stride,
+                needed: row,
+            });
+        }
+        let needed = stride * (Self::HEIGHT - 1) + row;
+        if out.len() < needed {
+            return Err(FrameBufferError::BufferTooSmall {
+                len: out.len(),
+                needed,
+            });
+        }
+        for (y, colors) in self.pixels.chunks(Self::WIDTH).enumerate() {
+            let line = &mut out[y * stride..y * stride + row];
+            for (pixel, color) in line.chunks_mut(format.bytes_per_pixel()).zip(colors.iter()) {
+                let [r, g, b] = rgb888(*color);
+                match format {
+                    PixelFormat::Rgba8888 => pixel.copy_from_slice(&[r, g, b, 0xFF]),
+                    PixelFormat::Bgra8888 => pixel.copy_from_slice(&[b, g, r, 0xFF]),
+                    PixelFormat::Argb8888 => pixel.copy_from_slice(&[0xFF, r, g, b]),
+                    PixelFormat::Rgb565 => {
+                        let green = (color >> 5) & 0x1F;
+                        let packed = ((color & 0x1F) << 11)
+                            | (((green << 1) | (green >> 4)) << 5)
+                            | ((color >> 10) & 0x1F);
+                        pixel.copy_from_slice(&packed.to_le_bytes());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///Converts the picture into a new buffer, rows packed without padding
+    pub fn to_bytes(&self, format: PixelFormat) -> Vec<u8> {
+        let stride = Self::WIDTH * format.bytes_per_pixel();
+        let mut out = vec![0; stride * Self::HEIGHT];
+        self.convert(format, &mut out, stride).unwrap();
+        out
+    }
+}
@@ -0,0 +1,357 @@
+//Picture processing unit: LCD timing and, line by line, the picture itself.
+//Source: https://problemkaputt.de/gbatek.htm#lcdiodisplaystatus
+pub mod affine;
+pub mod bitmap;
+pub mod effects;
+pub mod frame;
+pub mod mosaic;
+pub mod obj;
+pub mod render;
+pub mod text;
+pub mod thread;
+pub mod window;
+
+use crate::debug::VideoSwitches;
+use crate::dma::DmaTiming;
+use crate::interrupt::Interrupt;
+use crate::io;
+use crate::memory::Memory;
+use crate::ppu::frame::FrameBuffer;
+use crate::ppu::render::VideoMemory;
+use crate::ppu::thread::RenderWorker;
+use crate::scheduler::Event;
+
+pub const SCREEN_WIDTH: usize = 240;
+pub const SCREEN_HEIGHT: usize = 160;
+///Cycles spent drawing the visible part of a line(240 dots, 4 cycles each)
+pub const HDRAW_CYCLES: u64 = 960;
+///Cycles of the horizontal blank(68 dots)
+pub const HBLANK_CYCLES: u64 = 272;
+pub const LINE_CYCLES: u64 = HDRAW_CYCLES + HBLANK_CYCLES;
+///Lines drawn on screen, the others belong to the vertical blank
+pub const VISIBLE_LINES: u16 = 160;
+pub const TOTAL_LINES: u16 = 228;
+pub const FRAME_CYCLES: u64 = LINE_CYCLES * TOTAL_LINES as u64;
+
+//DISPSTAT bits
+const VBLANK_FLAG: u16 = 1 << 0;
+const HBLANK_FLAG: u16 = 1 << 1;
+const VCOUNT_FLAG: u16 = 1 << 2;
+const VBLANK_IRQ: u16 = 1 << 3;
+const HBLANK_IRQ: u16 = 1 << 4;
+const VCOUNT_IRQ: u16 = 1 << 5;
+
+//Offsets(from IO_BASE) of the background registers.
+//The affine registers of BG3 follow the ones of BG2, 0x10 bytes later
+pub(crate) const BG0CNT: u32 = 0x008;
+pub(crate) const BG0HOFS: u32 = 0x010;
+pub(crate) const BG2PA: u32 = 0x020;
+pub(crate) const BG2PB: u32 = 0x022;
+pub(crate) const BG2PC: u32 = 0x024;
+pub(crate) const BG2PD: u32 = 0x026;
+pub(crate) const BG2X: u32 = 0x028;
+
+//Offsets of the window, mosaic and color effects registers.
+//WIN1H and WIN1V follow WIN0H and WIN0V
+pub(crate) const WIN0H: u32 = 0x040;
+pub(crate) const WIN0V: u32 = 0x044;
+pub(crate) const WININ: u32 = 0x048;
+pub(crate) const WINOUT: u32 = 0x04A;
+pub(crate) const MOSAIC: u32 = 0x04C;
+pub(crate) const BLDCNT: u32 = 0x050;
+pub(crate) const BLDALPHA: u32 = 0x052;
+pub(crate) const BLDY: u32 = 0x054;
+
+///Internal reference point of an affine background, 20.8 fixed point.<br>
+///Latched from BGxX/BGxY at VBlank and whenever they are written, then moved by
+///PB/PD after each line.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AffineRef {
+    pub x: i32,
+    pub y: i32,
+}
+
+///How the picture is drawn
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum RenderMode {
+    ///A whole line at once when it ends. Fast, but changes made while a line is being drawn
+    ///apply to all of it
+    #[default]
+    Scanline,
+    ///Dots are drawn as time passes: writes to video memory and registers only show from the dot
+    ///being output when they happen, and the CPU waits for the PPU to free the video memory bus
+    Dot,
+}
+
+///PPU state
+#[derive(Debug)]
+pub struct Ppu {
+    ///Line being drawn, 0 to 227
+    pub vcount: u16,
+    ///Time the current line started
+    pub line_start: u64,
+    ///Frames completed(VBlanks entered) since power on
+    pub frame: u64,
+    ///Internal reference points of BG2 and BG3
+    pub affine: [AffineRef; 2],
+    ///Picture drawn so far, 240x160 BGR555
+    pub screen: Vec<u16>,
+    ///Last complete picture, for frontends
+    pub frame_buffer: FrameBuffer,
+    pub render_mode: RenderMode,
+    ///Debug switches hiding parts of the picture
+    pub switches: VideoSwitches,
+    ///Dots of the current line already in the screen buffer
+    drawn: usize,
+    ///Render thread, when lines are drawn in the background
+    worker: Option<RenderWorker>,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu {
+            vcount: 0,
+            line_start: 0,
+            frame: 0,
+            affine: [AffineRef::default(); 2],
+            screen: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_buffer: FrameBuffer::default(),
+            render_mode: RenderMode::default(),
+            switches: VideoSwitches::default(),
+            drawn: 0,
+            worker: None,
+        }
+    }
+}
+
+/**************
+ * LCD TIMING *
+ **************/
+impl Memory {
+    ///Starts the LCD at the beginning of line 0
+    pub(crate) fn lcd_reset(&mut self) {
+        self.ppu = Ppu::default();
+        self.ppu.line_start = self.scheduler.now;
+        self.scheduler
+            .reschedule(Event::HBlank, self.ppu.line_start + HDRAW_CYCLES);
+        self.scheduler
+            .reschedule(Event::HDraw, self.ppu.line_start + LINE_CYCLES);
+        self.lcd_update_status(false);
+        // identity transformation, as the BIOS leaves it
+        for base in [0, 0x10] {
+            self.io_store(io::register_at(BG2PA + base).unwrap(), 0x100);
+            self.io_store(io::register_at(BG2PD + base).unwrap(), 0x100);
+        }
+    }
+
+    ///Returns the time the next VBlank starts
+    pub fn lcd_next_vblank(&self) -> u64 {
+        let lines = (VISIBLE_LINES + TOTAL_LINES - 1 - self.ppu.vcount) % TOTAL_LINES + 1;
+        self.ppu.line_start + lines as u64 * LINE_CYCLES
+    }
+
+    ///Called by the scheduler when the visible part of a line ends
+    pub(crate) fn lcd_hblank_event(&mut self, time: u64) {
+        let status = self.lcd_status() | HBLANK_FLAG;
+        self.lcd_store_status(status);
+        if status & HBLANK_IRQ != 0 {
+            self.interrupt.request(Interrupt::HBlank);
+        }
+        // HBlank DMA does not run during VBlank
+        if self.ppu.vcount < VISIBLE_LINES {
+            self.ppu_render_dots(SCREEN_WIDTH);
+            self.ppu_move_references();
+            self.dma_trigger(DmaTiming::HBlank);
+        }
+        self.scheduler.schedule(Event::HBlank, time + LINE_CYCLES);
+    }
+
+    ///Called by the scheduler when a new line starts
+    pub(crate) fn lcd_hdraw_event(&mut self, time: u64) {
+        self.ppu.vcount = (self.ppu.vcount + 1) % TOTAL_LINES;
+        self.ppu.line_start = time;
+        self.ppu.drawn = 0;
+        self.scheduler.schedule(Event::HDraw, time + LINE_CYCLES);
+        self.lcd_update_status(true);
+
+        let status = self.lcd_status();
+        if self.ppu.vcount == VISIBLE_LINES {
+            self.ppu.frame += 1;
+            self.ppu_collect_lines();
+            self.ppu
+                .frame_buffer
+                .present(&self.ppu.screen, self.ppu.frame);
+            self.ppu_latch_reference(0);
+            self.ppu_latch_reference(1);
+            if status & VBLANK_IRQ != 0 {
+                self.interrupt.request(Interrupt::VBlank);
+            }
+            self.dma_trigger(DmaTiming::VBlank);
+        }
+        self.dma_video_capture(self.ppu.vcount);
+    }
+
+    ///Called after a write to DISPSTAT, the VCount setting may have changed.<br>
+    ///The blank flags keep their value: only the line events change them
+    pub(crate) fn lcd_write_status(&mut self) {
+        let mut status = self.lcd_status() & !VCOUNT_FLAG;
+        if self.ppu.vcount == status >> 8 {
+            status |= VCOUNT_FLAG;
+        }
+        self.lcd_store_status(status);
+    }
+
+    ///Refreshes VCOUNT and the DISPSTAT flags at the start of a line
+    /// * **irq**: whether a VCount match should request its interrupt
+    fn lcd_update_status(&mut self, irq: bool) {
+        let vcount = self.ppu.vcount;
+        self.io_store(io::register_at(io::VCOUNT).unwrap(), vcount as u32);
+        let mut status = self.lcd_status() & !(VBLANK_FLAG | HBLANK_FLAG | VCOUNT_FLAG);
+        // the flag is cleared on the last line, even though it still belongs to the blank
+        if (VISIBLE_LINES..TOTAL_LINES - 1).contains(&vcount) {
+            status |= VBLANK_FLAG;
+        }
+        if vcount == status >> 8 {
+            status |= VCOUNT_FLAG;
+            if irq && status & VCOUNT_IRQ != 0 {
+                self.interrupt.request(Interrupt::VCount);
+            }
+        }
+        self.lcd_store_status(status);
+    }
+
+    fn lcd_status(&self) -> u16 {
+        self.io_stored(io::register_at(io::DISPSTAT).unwrap()) as u16
+    }
+
+    fn lcd_store_status(&mut self, status: u16) {
+        self.io_store(io::register_at(io::DISPSTAT).unwrap(), status as u32);
+    }
+}
+
+/*************
+ * RENDERING *
+ *************/
+impl Memory {
+    ///Read only view of the video memory and registers, for the renderer
+    pub fn video_memory(&self) -> VideoMemory<'_> {
+        VideoMemory {
+            io: &self.io_registers,
+            palette: &self.palette_ram,
+            vram: &self.video_ram,
+            oam: &self.obj_attributes,
+            switches: self.ppu.switches,
+        }
+    }
+
+    ///Draws the dots of the current line up to, but excluding, the given one in the screen buffer
+    fn ppu_render_dots(&mut self, to: usize) {
+        let from = self.ppu.drawn;
+        if self.ppu.vcount >= VISIBLE_LINES || to <= from {
+            return;
+        }
+        // whole lines can be left to the render thread
+        if from == 0 && to == SCREEN_WIDTH {
+            if let Some(mut worker) = self.ppu.worker.take() {
+                worker.submit(&self.video_memory(), self.ppu.affine);
+                self.ppu.worker = Some(worker);
+                self.ppu.drawn = to;
+                return;
+            }
+        }
+        let mut line = [0; SCREEN_WIDTH];
+        render::render_line(&self.video_memory(), &self.ppu.affine, &mut line);
+        let start = self.ppu.vcount as usize * SCREEN_WIDTH;
+        self.ppu.screen[start + from..start + to].copy_from_slice(&line[from..to]);
+        self.ppu.drawn = to;
+    }
+
+    ///Moves the affine reference points to the next line, by PB and PD
+    fn ppu_move_references(&mut self) {
+        for bg in 0..2 {
+            let base = 0x10 * bg as u32;
+            let pb = self.io_stored(io::register_at(BG2PB + base).unwrap()) as i16 as i32;
+            let pd = self.io_stored(io::register_at(BG2PD + base).unwrap()) as i16 as i32;
+            self.ppu.affine[bg].x += pb;
+            self.ppu.affine[bg].y += pd;
+        }
+    }
+
+    ///Called after a write to BGxX or BGxY, and at VBlank: reloads the internal reference point
+    /// * **bg**: 0 for BG2, 1 for BG3
+    pub(crate) fn ppu_latch_reference(&mut self, bg: usize) {
+        let base = BG2X + 0x10 * bg as u32;
+        // 28 bit signed values
+        let x = ((self.io_stored(io::register_at(base).unwrap()) << 4) as i32) >> 4;
+        let y = ((self.io_stored(io::register_at(base + 4).unwrap()) << 4) as i32) >> 4;
+        self.ppu.affine[bg] = AffineRef { x, y };
+    }
+}
+
+/*****************
+ * DOT RENDERING *
+ *****************/
+impl Memory {
+    ///Dot being output on the current line, SCREEN_WIDTH once the visible part is over
+    fn ppu_current_dot(&self) -> usize {
+        (((self.now() - self.ppu.line_start) / 4) as usize).min(SCREEN_WIDTH)
+    }
+
+    ///Called before every write. In dot mode, a write to video memory or to the LCD registers
+    ///first draws the dots output so far, with the old values
+    pub(crate) fn ppu_sync(&mut self, address: u32) {
+        if self.ppu.render_mode != RenderMode::Dot {
+            return;
+        }
+        if matches!(address, 0x0400_0000..=0x0400_005F | 0x0500_0000..=0x07FF_FFFF) {
+            self.ppu_render_dots(self.ppu_current_dot());
+        }
+    }
+
+    ///Extra cycles for a CPU access that has to wait for the PPU: in dot mode, palette, VRAM and
+    ///OAM are busy while a visible line is being drawn
+    pub(crate) fn ppu_contention(&self, address: u32) -> u32 {
+        let busy = self.ppu.render_mode == RenderMode::Dot
+            && matches!(address >> 24, 0x05..=0x07)
+            && self.ppu.vcount < VISIBLE_LINES
+            && self.ppu_current_dot() < SCREEN_WIDTH;
+        busy as u32
+    }
+}
+
+/**********************
+ * THREADED RENDERING *
+ **********************/
+impl Memory {
+    ///Turns the render thread on or off. The picture is the same either way, but with the thread
+    ///on, the screen buffer is only complete once VBlank starts
+    pub fn ppu_set_threaded(&mut self, threaded: bool) {
+        if threaded && self.ppu.worker.is_none() {
+            self.ppu.worker = Some(RenderWorker::spawn(&self.video_memory()));
+        } else if !threaded {
+            self.ppu_collect_lines();
+            self.ppu
+                .frame_buffer
+                .present(&self.ppu.screen, self.ppu.frame);
+            self.ppu.worker = None;
+        }
+    }
+
+    pub fn ppu_threaded(&self) -> bool {
+        self.ppu.worker.is_some()
+    }
+
+    ///Called on every write to palette, VRAM or OAM, so the render thread gets a copy
+    pub(crate) fn ppu_written(&mut self, address: u32) {
+        if let Some(worker) = self.ppu.worker.as_mut() {
+            worker.mark(address);
+        }
+    }
+
+    ///Waits for the render thread to finish the lines it was given
+    fn ppu_collect_lines(&mut self) {
+        if let Some(worker) = self.ppu.worker.as_mut() {
+            worker.collect(&mut self.ppu.screen);
+        }
+    }
+}
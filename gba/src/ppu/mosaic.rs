@@ -0,0 +1,63 @@
+//Mosaic: layers that opt in(BGxCNT bit 6, OAM attribute 0 bit 12) are drawn in blocks,
+//every dot of a block showing the dot at its top left corner.
+//Source: https://problemkaputt.de/gbatek.htm#lcdiomosaicfunction
+use crate::ppu::render::{Line, VideoMemory};
+use crate::ppu::{AffineRef, BG2PB, BG2PD, MOSAIC};
+
+///Block sizes from the MOSAIC register, in dots(1 to 16)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mosaic {
+    pub bg_width: u16,
+    pub bg_height: u16,
+    pub obj_width: u16,
+    pub obj_height: u16,
+}
+
+impl Mosaic {
+    pub fn new(video: &VideoMemory) -> Self {
+        let value = video.io_16(MOSAIC);
+        Mosaic {
+            bg_width: (value & 0xF) + 1,
+            bg_height: ((value >> 4) & 0xF) + 1,
+            obj_width: ((value >> 8) & 0xF) + 1,
+            obj_height: (value >> 12) + 1,
+        }
+    }
+
+    ///Line a mosaic background shows instead of the current one: the first of its block.<br>
+    ///The vertical counter restarts at line 0, so blocks are aligned on the top of the screen
+    pub fn bg_line(&self, line: u16) -> u16 {
+        line - line % self.bg_height
+    }
+
+    ///The same for mosaic sprites, with their own block height
+    pub fn obj_line(&self, line: u16) -> u16 {
+        line - line % self.obj_height
+    }
+
+    ///Reference point an affine mosaic background draws from: the internal one moved back to
+    ///the first line of the block. The reference points keep moving on every line meanwhile
+    /// * **bg**: 2 or 3
+    pub fn bg_origin(&self, video: &VideoMemory, bg: usize, origin: AffineRef) -> AffineRef {
+        let line = video.vcount();
+        let back = (line - self.bg_line(line)) as i32;
+        let base = 0x10 * (bg as u32 - 2);
+        let pb = video.io_16(BG2PB + base) as i16 as i32;
+        let pd = video.io_16(BG2PD + base) as i16 as i32;
+        AffineRef {
+            x: origin.x - back * pb,
+            y: origin.y - back * pd,
+        }
+    }
+
+    ///Applies the horizontal mosaic to a background line
+    pub fn apply_bg(&self, line: &mut Line) {
+        let width = self.bg_width as usize;
+        if width == 1 {
+            return;
+        }
+        for x in 0..line.len() {
+            line[x] = line[x - x % width];
+        }
+    }
+}
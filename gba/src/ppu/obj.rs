@@ -0,0 +1,287 @@
+//Sprites(OBJ): 128 entries of 3 attributes in OAM, drawn in a line buffer of their own.
+//Source: https://problemkaputt.de/gbatek.htm#lcdobjoamattributes
+use crate::ppu::mosaic::Mosaic;
+use crate::ppu::render::{Line, VideoMemory, TRANSPARENT};
+use crate::ppu::SCREEN_WIDTH;
+
+///Start of the sprite tiles in VRAM
+pub const OBJ_VRAM: usize = 0x1_0000;
+///Size of the sprite tiles area in VRAM, tile numbers wrap inside it
+pub const OBJ_VRAM_SIZE: usize = 0x8000;
+///Sprite cycles available on a line
+pub const LINE_BUDGET: u32 = 1210;
+///Sprite cycles available on a line when DISPCNT gives the HBlank to the CPU(bit 5)
+pub const HBLANK_FREE_BUDGET: u32 = 954;
+
+///Sprite dimensions for each shape(square, horizontal, vertical) and size
+const SIZES: [[(i32, i32); 4]; 3] = [
+    [(8, 8), (16, 16), (32, 32), (64, 64)],
+    [(16, 8), (32, 8), (32, 16), (64, 32)],
+    [(8, 16), (8, 32), (16, 32), (32, 64)],
+];
+
+///How a sprite is used, attribute 0 bits 10-11
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ObjMode {
+    Normal,
+    ///Alpha blended with what is below, regardless of BLDCNT's first target
+    SemiTransparent,
+    ///Not drawn, its opaque dots define the OBJ window
+    Window,
+    Prohibited,
+}
+
+///A decoded OAM entry
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub index: usize,
+    pub x: i32,
+    pub y: i32,
+    pub affine: bool,
+    ///Affine sprites only: the bounding box is twice the sprite size
+    pub double_size: bool,
+    ///Regular sprites only
+    pub disabled: bool,
+    pub mode: ObjMode,
+    pub mosaic: bool,
+    pub bpp8: bool,
+    pub width: i32,
+    pub height: i32,
+    ///Affine parameter group, 0 to 31
+    pub affine_group: usize,
+    pub hflip: bool,
+    pub vflip: bool,
+    pub tile: usize,
+    pub priority: u8,
+    pub palette: usize,
+}
+
+impl Sprite {
+    ///Decodes the attributes of an OAM entry
+    pub fn parse(index: usize, attr: [u16; 3]) -> Self {
+        let [attr0, attr1, attr2] = attr;
+        let affine = attr0 & 0x100 != 0;
+        let (width, height) = match attr0 >> 14 {
+            3 => (8, 8), // prohibited shape
+            shape => SIZES[shape as usize][(attr1 >> 14) as usize],
+        };
+        Sprite {
+            index,
+            // 9 bit signed
+            x: ((attr1 as i32 & 0x1FF) << 23) >> 23,
+            y: attr0 as i32 & 0xFF,
+            affine,
+            double_size: affine && attr0 & 0x200 != 0,
+            disabled: !affine && attr0 & 0x200 != 0,
+            mode: match (attr0 >> 10) & 3 {
+                0 => ObjMode::Normal,
+                1 => ObjMode::SemiTransparent,
+                2 => ObjMode::Window,
+                _ => ObjMode::Prohibited,
+            },
+            mosaic: attr0 & 0x1000 != 0,
+            bpp8: attr0 & 0x2000 != 0,
+            width,
+            height,
+            affine_group: ((attr1 >> 9) & 0x1F) as usize,
+            hflip: !affine && attr1 & 0x1000 != 0,
+            vflip: !affine && attr1 & 0x2000 != 0,
+            tile: (attr2 & 0x3FF) as usize,
+            priority: ((attr2 >> 10) & 3) as u8,
+            palette: (attr2 >> 12) as usize,
+        }
+    }
+
+    ///Size of the area the sprite covers on screen
+    pub fn bounds(&self) -> (i32, i32) {
+        if self.double_size {
+            (self.width * 2, self.height * 2)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    ///Whether the sprite is drawn at all
+    pub fn visible(&self) -> bool {
+        !self.disabled && self.mode != ObjMode::Prohibited
+    }
+
+    ///Row of the bounding box crossed by a screen line, if any. Sprites wrap around at line 256
+    pub fn row_at(&self, line: u16) -> Option<i32> {
+        let row = (line as i32 - self.y) & 0xFF;
+        (row < self.bounds().1).then_some(row)
+    }
+
+    ///Cycles the PPU spends on the sprite for each line it covers
+    pub fn cycles(&self) -> u32 {
+        if self.affine {
+            10 + 2 * self.bounds().0 as u32
+        } else {
+            self.width as u32
+        }
+    }
+}
+
+///Reads the 128 sprites from OAM
+pub fn sprites(video: &VideoMemory) -> Vec<Sprite> {
+    (0..128)
+        .map(|index| {
+            let attr = |n: usize| {
+                u16::from_le_bytes([
+                    video.oam[index * 8 + n * 2],
+                    video.oam[index * 8 + n * 2 + 1],
+                ])
+            };
+            Sprite::parse(index, [attr(0), attr(1), attr(2)])
+        })
+        .collect()
+}
+
+///Reads an affine parameter group: PA, PB, PC, PD, spread over the unused halfword of 4 entries
+pub fn affine_parameters(video: &VideoMemory, group: usize) -> [i32; 4] {
+    let param = |n: usize| {
+        let offset = (group * 4 + n) * 8 + 6;
+        i16::from_le_bytes([video.oam[offset], video.oam[offset + 1]]) as i32
+    };
+    [param(0), param(1), param(2), param(3)]
+}
+
+///Sprite layer of a line
+#[derive(Clone, Debug)]
+pub struct ObjLine {
+    pub pixels: Line,
+    ///Priority of the sprite drawn on each dot
+    pub priority: [u8; SCREEN_WIDTH],
+    ///Whether the dot comes from a semi-transparent sprite
+    pub semi_transparent: [bool; SCREEN_WIDTH],
+    ///Dots inside the OBJ window
+    pub window: [bool; SCREEN_WIDTH],
+    ///Whether the dot comes from a sprite with mosaic
+    pub mosaic: [bool; SCREEN_WIDTH],
+}
+
+impl Default for ObjLine {
+    fn default() -> Self {
+        ObjLine {
+            pixels: [TRANSPARENT; SCREEN_WIDTH],
+            priority: [4; SCREEN_WIDTH],
+            semi_transparent: [false; SCREEN_WIDTH],
+            window: [false; SCREEN_WIDTH],
+            mosaic: [false; SCREEN_WIDTH],
+        }
+    }
+}
+
+///Draws the sprites crossing the current line.<br>
+///Sprites are handled in OAM order within the line cycle budget: when a sprite does not fit
+///in what is left, it and all the following ones are dropped, like on hardware.
+pub fn render(video: &VideoMemory) -> ObjLine {
+    let mut out = ObjLine::default();
+    let line = video.vcount();
+    let mosaic = Mosaic::new(video);
+    let mut budget = if video.dispcnt() & 0x20 != 0 {
+        HBLANK_FREE_BUDGET
+    } else {
+        LINE_BUDGET
+    };
+    for sprite in sprites(video).iter().filter(|sprite| sprite.visible()) {
+        let Some(mut row) = sprite.row_at(line) else {
+            continue;
+        };
+        if sprite.cycles() > budget {
+            break;
+        }
+        budget -= sprite.cycles();
+        let mut mosaic_width = 1;
+        if sprite.mosaic {
+            // the vertical counter is shared by all sprites: a block that starts above the
+            // sprite repeats its first row
+            row = sprite
+                .row_at(mosaic.obj_line(line))
+                .filter(|block_row| *block_row <= row)
+                .unwrap_or(0);
+            mosaic_width = mosaic.obj_width as i32;
+        }
+        draw(video, sprite, row, mosaic_width, &mut out);
+    }
+    out
+}
+
+///Draws a line of a sprite
+/// * **row**: row of the bounding box
+/// * **mosaic_width**: horizontal mosaic block size, 1 for none
+fn draw(video: &VideoMemory, sprite: &Sprite, row: i32, mosaic_width: i32, out: &mut ObjLine) {
+    let (bound_width, bound_height) = sprite.bounds();
+    let [pa, pb, pc, pd] = if sprite.affine {
+        affine_parameters(video, sprite.affine_group)
+    } else {
+        [0x100, 0, 0, 0x100]
+    };
+    // coordinates relative to the center of the bounding box
+    let cy = row - bound_height / 2;
+    for bx in 0..bound_width {
+        let x = sprite.x + bx;
+        if !(0..SCREEN_WIDTH as i32).contains(&x) {
+            continue;
+        }
+        // a mosaic block shows the dot at its left edge, or the sprite's own first dot
+        let bx = (x - x % mosaic_width).max(sprite.x) - sprite.x;
+        let x = x as usize;
+        let cx = bx - bound_width / 2;
+        let mut tx = ((pa * cx + pb * cy) >> 8) + sprite.width / 2;
+        let mut ty = ((pc * cx + pd * cy) >> 8) + sprite.height / 2;
+        if tx < 0 || ty < 0 || tx >= sprite.width || ty >= sprite.height {
+            continue;
+        }
+        if sprite.hflip {
+            tx = sprite.width - 1 - tx;
+        }
+        if sprite.vflip {
+            ty = sprite.height - 1 - ty;
+        }
+        let Some(color) = texel(video, sprite, tx as usize, ty as usize) else {
+            continue;
+        };
+        if sprite.mode == ObjMode::Window {
+            out.window[x] = true;
+            continue;
+        }
+        // a sprite with a lower priority value wins, then the lower OAM index(drawn first)
+        if out.pixels[x] & TRANSPARENT == 0 && out.priority[x] <= sprite.priority {
+            continue;
+        }
+        out.pixels[x] = color;
+        out.priority[x] = sprite.priority;
+        out.semi_transparent[x] = sprite.mode == ObjMode::SemiTransparent;
+        out.mosaic[x] = sprite.mosaic;
+    }
+}
+
+///Color of a sprite dot, None if transparent
+pub(crate) fn texel(video: &VideoMemory, sprite: &Sprite, tx: usize, ty: usize) -> Option<u16> {
+    // tile numbers count 32 bytes units, a 256 colors tile takes 2 of them
+    let units = if sprite.bpp8 { 2 } else { 1 };
+    let row_stride = if video.dispcnt() & 0x40 != 0 {
+        // 1D mapping: the tiles of the sprite follow each other
+        sprite.width as usize / 8 * units
+    } else {
+        // 2D mapping: VRAM is a 32x32 tiles matrix
+        32
+    };
+    let tile = sprite.tile + (ty / 8) * row_stride + (tx / 8) * units;
+    // in bitmap modes the lower half of OBJ VRAM holds the frame buffer
+    if video.mode() >= 3 && tile & 0x3FF < 512 {
+        return None;
+    }
+    let base = OBJ_VRAM + (tile * 32) % OBJ_VRAM_SIZE;
+    let index = if sprite.bpp8 {
+        video.vram_8(base + (ty % 8) * 8 + tx % 8) as usize
+    } else {
+        let byte = video.vram_8(base + (ty % 8) * 4 + (tx % 8) / 2);
+        match (byte >> ((tx & 1) * 4)) & 0xF {
+            0 => 0,
+            index => sprite.palette * 16 + index as usize,
+        }
+    };
+    (index != 0).then(|| video.obj_color(index))
+}
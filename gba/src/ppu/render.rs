@@ -0,0 +1,221 @@
+//Scanline renderer: draws one line at a time from a read only view of the video memory.
+//Each layer is drawn in its own line buffer, then the buffers are merged by priority.
+use crate::debug::VideoSwitches;
+use crate::io::{DISPCNT, VCOUNT};
+use crate::ppu::effects::Effects;
+use crate::ppu::mosaic::Mosaic;
+use crate::ppu::obj::{self, ObjLine};
+use crate::ppu::{affine, bitmap, text, window};
+use crate::ppu::{AffineRef, BG0CNT, SCREEN_WIDTH};
+
+///Marks a pixel of a layer line that lets the layers below show through.<br>
+///Colors are 15 bit, so the top bit is free.
+pub const TRANSPARENT: u16 = 0x8000;
+///Color of a line during forced blank
+pub const WHITE: u16 = 0x7FFF;
+///Layer numbers used by windows and color effects after the 4 backgrounds
+pub const OBJ_LAYER: usize = 4;
+pub const BACKDROP_LAYER: usize = 5;
+
+///One line of a layer, or of the screen, in BGR555
+pub type Line = [u16; SCREEN_WIDTH];
+
+///Read only view of everything the PPU draws from
+#[derive(Copy, Clone)]
+pub struct VideoMemory<'a> {
+    pub io: &'a [u8; 1024],
+    pub palette: &'a [u8; 1024],
+    pub vram: &'a [u8; 96 * 1024],
+    pub oam: &'a [u8; 1024],
+    ///Debug switches, applied on top of the registers
+    pub switches: VideoSwitches,
+}
+
+impl VideoMemory<'_> {
+    ///Reads a 16 bit I/O register, as stored
+    pub fn io_16(&self, offset: u32) -> u16 {
+        let offset = offset as usize;
+        u16::from_le_bytes([self.io[offset], self.io[offset + 1]])
+    }
+    pub fn dispcnt(&self) -> u16 {
+        self.io_16(DISPCNT)
+    }
+    ///Line being drawn
+    pub fn vcount(&self) -> u16 {
+        self.io_16(VCOUNT)
+    }
+    ///BGxCNT of a background
+    pub fn bg_control(&self, bg: usize) -> u16 {
+        self.io_16(BG0CNT + 2 * bg as u32)
+    }
+    ///Video mode, DISPCNT bits 0-2
+    pub fn mode(&self) -> u16 {
+        self.dispcnt() & 7
+    }
+    ///Whether a layer is enabled in DISPCNT(0-3 backgrounds, 4 OBJ), and not hidden by the
+    ///debug switches
+    pub fn layer_enabled(&self, layer: usize) -> bool {
+        let shown = match layer {
+            0..=3 => self.switches.bg[layer],
+            _ => self.switches.obj,
+        };
+        shown && self.dispcnt() & (0x100 << layer) != 0
+    }
+    ///Color of an entry of the background palette
+    pub fn bg_color(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.palette[index * 2], self.palette[index * 2 + 1]]) & 0x7FFF
+    }
+    ///Color of an entry of the sprite palette
+    pub fn obj_color(&self, index: usize) -> u16 {
+        self.bg_color(256 + index)
+    }
+    ///Reads a byte of VRAM, 0 past its end
+    pub fn vram_8(&self, offset: usize) -> u8 {
+        self.vram.get(offset).copied().unwrap_or(0)
+    }
+    pub fn vram_16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.vram[offset], self.vram[offset + 1]])
+    }
+}
+
+///A background line, drawn in its own buffer and ready to be merged with the others
+#[derive(Clone, Debug)]
+pub struct BgLine {
+    ///Background number, 0 to 3
+    pub bg: usize,
+    ///BGxCNT priority, lower is drawn on top
+    pub priority: u16,
+    pub pixels: Line,
+}
+
+impl BgLine {
+    fn new(video: &VideoMemory, bg: usize) -> Self {
+        BgLine {
+            bg,
+            priority: video.bg_control(bg) & 3,
+            pixels: [TRANSPARENT; SCREEN_WIDTH],
+        }
+    }
+}
+
+///Draws a whole screen line
+/// # Arguments
+/// * **video**: video memory and registers, VCOUNT tells the line
+/// * **affine**: internal reference points of BG2 and BG3 for this line
+/// * **out**: the line, in BGR555
+pub fn render_line(video: &VideoMemory, affine: &[AffineRef; 2], out: &mut Line) {
+    // forced blank: the LCD gets no data and shows white
+    if video.dispcnt() & 0x80 != 0 {
+        out.fill(WHITE);
+        return;
+    }
+    let layers = render_backgrounds(video, affine);
+    // sprites hidden by the debug switch are still drawn: they make the OBJ window
+    let sprites = if video.dispcnt() & 0x1000 != 0 {
+        obj::render(video)
+    } else {
+        ObjLine::default()
+    };
+    compose(video, &layers, &sprites, out);
+}
+
+///Draws the enabled backgrounds of the current mode, each in its own line buffer.<br>
+///The lines come out sorted from top to bottom: by priority, then by background number.
+pub fn render_backgrounds(video: &VideoMemory, affine: &[AffineRef; 2]) -> Vec<BgLine> {
+    let text_layers = match video.mode() {
+        0 => 0..4,
+        1 => 0..2,
+        _ => 0..0,
+    };
+    let mosaic = Mosaic::new(video);
+    let has_mosaic = |bg: usize| video.bg_control(bg) & 0x40 != 0;
+    let mut layers = Vec::new();
+    for bg in text_layers.filter(|bg| video.layer_enabled(*bg)) {
+        let mut layer = BgLine::new(video, bg);
+        let line = if has_mosaic(bg) {
+            mosaic.bg_line(video.vcount())
+        } else {
+            video.vcount()
+        };
+        text::render(video, bg, line, &mut layer.pixels);
+        layers.push(layer);
+    }
+    let affine_layers = match video.mode() {
+        1 => 2..3,
+        2 => 2..4,
+        _ => 0..0,
+    };
+    for bg in affine_layers.filter(|bg| video.layer_enabled(*bg)) {
+        let mut layer = BgLine::new(video, bg);
+        let origin = if has_mosaic(bg) {
+            mosaic.bg_origin(video, bg, affine[bg - 2])
+        } else {
+            affine[bg - 2]
+        };
+        affine::render(video, bg, origin, &mut layer.pixels);
+        layers.push(layer);
+    }
+    if (3..=5).contains(&video.mode()) && video.layer_enabled(2) {
+        let mut layer = BgLine::new(video, 2);
+        let origin = if has_mosaic(2) {
+            mosaic.bg_origin(video, 2, affine[0])
+        } else {
+            affine[0]
+        };
+        bitmap::render(video, origin, &mut layer.pixels);
+        layers.push(layer);
+    }
+    for layer in layers.iter_mut().filter(|layer| has_mosaic(layer.bg)) {
+        mosaic.apply_bg(&mut layer.pixels);
+    }
+    layers.sort_by_key(|layer| (layer.priority, layer.bg));
+    layers
+}
+
+///Merges the layer lines, sorted from top to bottom, applying windows and color effects.<br>
+///Every pixel shows the first opaque layer the window lets through, or the backdrop.
+///Sprites go above the backgrounds of the same priority
+fn compose(video: &VideoMemory, layers: &[BgLine], sprites: &ObjLine, out: &mut Line) {
+    let backdrop = video.bg_color(0);
+    let masks = window::masks(video, sprites);
+    let effects = Effects::new(video);
+    let show_sprites = video.layer_enabled(OBJ_LAYER);
+    for (x, pixel) in out.iter_mut().enumerate() {
+        let mask = masks[x];
+        let mut sprite =
+            (show_sprites && mask & (1 << OBJ_LAYER) != 0 && sprites.pixels[x] & TRANSPARENT == 0)
+                .then_some((sprites.priority[x] as u16, sprites.pixels[x]));
+        // the two top visible dots, as (layer, color)
+        let mut visible = [(BACKDROP_LAYER, backdrop); 2];
+        let mut found = 0;
+        for layer in layers {
+            if found == 2 {
+                break;
+            }
+            if mask & (1 << layer.bg) == 0 || layer.pixels[x] & TRANSPARENT != 0 {
+                continue;
+            }
+            if let Some((_, color)) = sprite.filter(|(priority, _)| *priority <= layer.priority) {
+                visible[found] = (OBJ_LAYER, color);
+                found += 1;
+                sprite = None;
+                if found == 2 {
+                    break;
+                }
+            }
+            visible[found] = (layer.bg, layer.pixels[x]);
+            found += 1;
+        }
+        if let Some((_, color)) = sprite.filter(|_| found < 2) {
+            visible[found] = (OBJ_LAYER, color);
+        }
+
+        let [top, below] = visible;
+        *pixel = if mask & window::EFFECTS != 0 && video.switches.blending {
+            let semi_transparent = top.0 == OBJ_LAYER && sprites.semi_transparent[x];
+            effects.apply(top, below, semi_transparent)
+        } else {
+            top.1
+        };
+    }
+}
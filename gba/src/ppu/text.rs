@@ -0,0 +1,92 @@
+//Text backgrounds: scrollable tile maps, BG0-BG3 in mode 0 and BG0-BG1 in mode 1.
+//The map is made of 32x32 entries screen blocks(2KBytes each), 1 to 4 of them depending on the size.
+//Source: https://problemkaputt.de/gbatek.htm#lcdvrambgscreendataformatbgmap
+use crate::ppu::render::{Line, VideoMemory, TRANSPARENT};
+use crate::ppu::BG0HOFS;
+
+///Background tiles can only come from the first 64KBytes of VRAM, the rest belongs to OBJ
+pub const BG_VRAM_SIZE: usize = 0x1_0000;
+///Size of a screen block, in bytes
+pub const SCREEN_BLOCK_SIZE: usize = 0x800;
+
+///Map size in pixels for each BGxCNT size setting
+const SIZES: [(usize, usize); 4] = [(256, 256), (512, 256), (256, 512), (512, 512)];
+
+///Decoded BGxCNT of a text background
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextControl {
+    ///Start of the tile data
+    pub char_base: usize,
+    ///Start of the first screen block
+    pub screen_base: usize,
+    ///256 colors tiles, otherwise 16 colors with 16 palette banks
+    pub bpp8: bool,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TextControl {
+    pub fn from_bgcnt(value: u16) -> Self {
+        let (width, height) = SIZES[(value >> 14) as usize];
+        TextControl {
+            char_base: ((value >> 2) & 3) as usize * 0x4000,
+            screen_base: ((value >> 8) & 0x1F) as usize * SCREEN_BLOCK_SIZE,
+            bpp8: value & 0x80 != 0,
+            width,
+            height,
+        }
+    }
+
+    ///VRAM offset of the map entry covering a map pixel.<br>
+    ///Blocks are laid out left to right, then top to bottom: a 512x256 map is SB0|SB1,
+    ///a 256x512 one is SB0 over SB1, a 512x512 one is SB0|SB1 over SB2|SB3.
+    pub fn entry_offset(&self, x: usize, y: usize) -> usize {
+        let block = x / 256 + (y / 256) * (self.width / 256);
+        let entry = (y % 256) / 8 * 32 + (x % 256) / 8;
+        self.screen_base + block * SCREEN_BLOCK_SIZE + entry * 2
+    }
+}
+
+///Draws a line of a text background
+/// * **line**: screen line, the current one unless mosaic is on
+pub fn render(video: &VideoMemory, bg: usize, line: u16, out: &mut Line) {
+    let control = TextControl::from_bgcnt(video.bg_control(bg));
+    let hofs = (video.io_16(BG0HOFS + 4 * bg as u32) & 0x1FF) as usize;
+    let vofs = (video.io_16(BG0HOFS + 4 * bg as u32 + 2) & 0x1FF) as usize;
+    let y = (line as usize + vofs) % control.height;
+    for (x, pixel) in out.iter_mut().enumerate() {
+        let x = (x + hofs) % control.width;
+        *pixel = pixel_at(video, &control, x, y);
+    }
+}
+
+///Color of a map pixel, or TRANSPARENT
+pub(crate) fn pixel_at(video: &VideoMemory, control: &TextControl, x: usize, y: usize) -> u16 {
+    // tile number, H-flip, V-flip and palette bank
+    let entry = video.vram_16(control.entry_offset(x, y)) as usize;
+    let tile = entry & 0x3FF;
+    let tx = if entry & 0x400 != 0 { 7 - x % 8 } else { x % 8 };
+    let ty = if entry & 0x800 != 0 { 7 - y % 8 } else { y % 8 };
+    if control.bpp8 {
+        let offset = control.char_base + tile * 64 + ty * 8 + tx;
+        match tile_byte(video, offset) {
+            0 => TRANSPARENT,
+            index => video.bg_color(index as usize),
+        }
+    } else {
+        let offset = control.char_base + tile * 32 + ty * 4 + tx / 2;
+        match (tile_byte(video, offset) >> ((tx & 1) * 4)) & 0xF {
+            0 => TRANSPARENT,
+            index => video.bg_color((entry >> 12) * 16 + index as usize),
+        }
+    }
+}
+
+///Reads tile data, which cannot reach the OBJ part of VRAM
+fn tile_byte(video: &VideoMemory, offset: usize) -> u8 {
+    if offset < BG_VRAM_SIZE {
+        video.vram_8(offset)
+    } else {
+        0
+    }
+}
@@ -0,0 +1,185 @@
+//Renders lines on a worker thread while the CPU keeps going.
+//The worker keeps its own copy of the video memory. At the end of each line it gets the I/O
+//registers and the blocks of palette, VRAM and OAM written since the previous line, then the
+//lines are collected at VBlank. They are drawn in order from the same data as on the main thread,
+//so the picture is identical.
+use crate::debug::VideoSwitches;
+use crate::ppu::render::{self, Line, VideoMemory};
+use crate::ppu::{AffineRef, SCREEN_WIDTH};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+///Granularity of the written memory tracking, in bytes
+pub const BLOCK_SIZE: usize = 256;
+const PALETTE_BLOCKS: usize = 1024 / BLOCK_SIZE;
+const VRAM_BLOCKS: usize = 96 * 1024 / BLOCK_SIZE;
+const OAM_BLOCKS: usize = 1024 / BLOCK_SIZE;
+const BLOCKS: usize = PALETTE_BLOCKS + VRAM_BLOCKS + OAM_BLOCKS;
+
+///Returns the tracking block of a palette, VRAM or OAM address.<br>
+///Blocks are numbered across the 3 regions: palette, then VRAM, then OAM
+pub fn block_of(address: u32) -> Option<usize> {
+    let block = match address {
+        0x0500_0000..=0x0500_03FF => (address - 0x0500_0000) as usize / BLOCK_SIZE,
+        0x0600_0000..=0x0601_7FFF => PALETTE_BLOCKS + (address - 0x0600_0000) as usize / BLOCK_SIZE,
+        0x0700_0000..=0x0700_03FF => {
+            PALETTE_BLOCKS + VRAM_BLOCKS + (address - 0x0700_0000) as usize / BLOCK_SIZE
+        }
+        _ => return None,
+    };
+    Some(block)
+}
+
+///Copy of the video memory owned by the worker
+struct VideoCopy {
+    io: Box<[u8; 1024]>,
+    palette: Box<[u8; 1024]>,
+    vram: Box<[u8; 96 * 1024]>,
+    oam: Box<[u8; 1024]>,
+    switches: VideoSwitches,
+}
+
+impl VideoCopy {
+    fn view(&self) -> VideoMemory<'_> {
+        VideoMemory {
+            io: &self.io,
+            palette: &self.palette,
+            vram: &self.vram,
+            oam: &self.oam,
+            switches: self.switches,
+        }
+    }
+
+    fn block_mut(&mut self, block: usize) -> &mut [u8] {
+        let (region, block): (&mut [u8], usize) = match block {
+            _ if block < PALETTE_BLOCKS => (&mut self.palette[..], block),
+            _ if block < PALETTE_BLOCKS + VRAM_BLOCKS => {
+                (&mut self.vram[..], block - PALETTE_BLOCKS)
+            }
+            _ => (&mut self.oam[..], block - PALETTE_BLOCKS - VRAM_BLOCKS),
+        };
+        &mut region[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE]
+    }
+}
+
+///Contents of a tracking block in the main thread's memory
+fn block(video: &VideoMemory, block: usize) -> [u8; BLOCK_SIZE] {
+    let (region, block): (&[u8], usize) = match block {
+        _ if block < PALETTE_BLOCKS => (&video.palette[..], block),
+        _ if block < PALETTE_BLOCKS + VRAM_BLOCKS => (&video.vram[..], block - PALETTE_BLOCKS),
+        _ => (&video.oam[..], block - PALETTE_BLOCKS - VRAM_BLOCKS),
+    };
+    region[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE]
+        .try_into()
+        .unwrap()
+}
+
+enum Job {
+    Line {
+        line: u16,
+        affine: [AffineRef; 2],
+        io: Box<[u8; 1024]>,
+        switches: VideoSwitches,
+        blocks: Vec<(usize, [u8; BLOCK_SIZE])>,
+    },
+    Stop,
+}
+
+///Handle on the render thread
+#[derive(Debug)]
+pub struct RenderWorker {
+    jobs: Sender<Job>,
+    lines: Receiver<(u16, Line)>,
+    handle: Option<JoinHandle<()>>,
+    ///Blocks written since the last line was sent
+    dirty: Vec<bool>,
+    ///Lines sent and not collected yet
+    pending: usize,
+}
+
+impl RenderWorker {
+    ///Starts the thread, with a copy of the current video memory
+    pub fn spawn(video: &VideoMemory) -> Self {
+        let mut copy = VideoCopy {
+            io: Box::new(*video.io),
+            palette: Box::new(*video.palette),
+            vram: vec![0; 96 * 1024].into_boxed_slice().try_into().unwrap(),
+            oam: Box::new(*video.oam),
+            switches: video.switches,
+        };
+        copy.vram.copy_from_slice(video.vram);
+        let (jobs, job_receiver) = channel();
+        let (line_sender, lines) = channel();
+        let handle = thread::spawn(move || {
+            while let Ok(Job::Line {
+                line,
+                affine,
+                io,
+                switches,
+                blocks,
+            }) = job_receiver.recv()
+            {
+                copy.io = io;
+                copy.switches = switches;
+                for (index, data) in blocks {
+                    copy.block_mut(index).copy_from_slice(&data);
+                }
+                let mut out = [0; SCREEN_WIDTH];
+                render::render_line(&copy.view(), &affine, &mut out);
+                if line_sender.send((line, out)).is_err() {
+                    break;
+                }
+            }
+        });
+        RenderWorker {
+            jobs,
+            lines,
+            handle: Some(handle),
+            dirty: vec![false; BLOCKS],
+            pending: 0,
+        }
+    }
+
+    ///Records a write to palette, VRAM or OAM
+    pub fn mark(&mut self, address: u32) {
+        if let Some(block) = block_of(address) {
+            self.dirty[block] = true;
+        }
+    }
+
+    ///Sends the current line to the thread, with everything it needs to draw it
+    pub fn submit(&mut self, video: &VideoMemory, affine: [AffineRef; 2]) {
+        let blocks = (0..BLOCKS)
+            .filter(|index| core::mem::take(&mut self.dirty[*index]))
+            .map(|index| (index, block(video, index)))
+            .collect();
+        let job = Job::Line {
+            line: video.vcount(),
+            affine,
+            io: Box::new(*video.io),
+            switches: video.switches,
+            blocks,
+        };
+        self.jobs.send(job).expect("render thread stopped");
+        self.pending += 1;
+    }
+
+    ///Waits for the lines sent so far and puts them in the screen buffer
+    pub fn collect(&mut self, screen: &mut [u16]) {
+        while self.pending > 0 {
+            let (line, pixels) = self.lines.recv().expect("render thread stopped");
+            let start = line as usize * SCREEN_WIDTH;
+            screen[start..start + SCREEN_WIDTH].copy_from_slice(&pixels);
+            self.pending -= 1;
+        }
+    }
+}
+
+impl Drop for RenderWorker {
+    fn drop(&mut self) {
+        let _ = self.jobs.send(Job::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
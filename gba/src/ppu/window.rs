@@ -0,0 +1,62 @@
+//Windows: WIN0 and WIN1 rectangles, plus the OBJ window drawn by sprites, select which layers
+//and whether color effects show on each dot.
+//Source: https://problemkaputt.de/gbatek.htm#lcdiowindowfeature
+use crate::ppu::obj::ObjLine;
+use crate::ppu::render::VideoMemory;
+use crate::ppu::{SCREEN_WIDTH, WIN0H, WIN0V, WININ, WINOUT};
+
+///Window mask bit of the color effects, bits 0-4 are the layers(BG0-BG3, OBJ)
+pub const EFFECTS: u8 = 1 << 5;
+///Everything shows, for dots of a screen without windows
+pub const ALL: u8 = 0x3F;
+
+///Whether a coordinate is inside a window range.<br>
+///The range goes from the first value up to, but excluding, the second one. When the first value
+///is greater, the range wraps around: it covers both edges of the screen
+fn inside(range: u16, position: u16, size: u16) -> bool {
+    let start = range >> 8;
+    let end = range & 0xFF;
+    if start <= end {
+        // garbage ends past the screen edge stop at it
+        position >= start && position < end.min(size)
+    } else {
+        position >= start || position < end
+    }
+}
+
+///Computes the window mask of every dot of the current line.<br>
+///WIN0 has precedence over WIN1, which has precedence over the OBJ window, then comes the outside.
+pub fn masks(video: &VideoMemory, sprites: &ObjLine) -> [u8; SCREEN_WIDTH] {
+    let dispcnt = video.dispcnt();
+    if dispcnt & 0xE000 == 0 || !video.switches.windows {
+        return [ALL; SCREEN_WIDTH];
+    }
+    let line = video.vcount();
+    let winin = video.io_16(WININ);
+    let winout = video.io_16(WINOUT);
+    // WIN0 and WIN1 covering the line, with their masks
+    let windows: Vec<(u16, u8)> = (0..2)
+        .filter(|win| dispcnt & (0x2000 << win) != 0)
+        .filter(|win| inside(video.io_16(WIN0V + 2 * win), line, 228))
+        .map(|win| {
+            (
+                video.io_16(WIN0H + 2 * win),
+                (winin >> (8 * win)) as u8 & ALL,
+            )
+        })
+        .collect();
+    let obj_window = dispcnt & 0x8000 != 0;
+
+    let mut masks = [(winout as u8) & ALL; SCREEN_WIDTH];
+    for (x, mask) in masks.iter_mut().enumerate() {
+        if let Some((_, inner)) = windows
+            .iter()
+            .find(|(range, _)| inside(*range, x as u16, SCREEN_WIDTH as u16))
+        {
+            *mask = *inner;
+        } else if obj_window && sprites.window[x] {
+            *mask = (winout >> 8) as u8 & ALL;
+        }
+    }
+    masks
+}
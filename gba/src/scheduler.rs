@@ -0,0 +1,122 @@
+//Event scheduler: keeps the time of the whole system and the list of upcoming hardware events.
+//Peripherals are not stepped every cycle, they schedule their next event instead; the CPU runs
+//until the first event is due, the event is handled, and so on.
+use crate::memory::Memory;
+use crate::power::PowerState;
+use arm7tdmi::cpu::{MemoryInterface, CPU};
+
+///Something the hardware has to do at a given time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    ///The timer counter wraps from 0xFFFF
+    TimerOverflow(usize),
+    ///The visible part of the line ends
+    HBlank,
+    ///The next line starts
+    HDraw,
+    ///The 512 Hz PSG frame sequencer steps
+    FrameSequencer,
+}
+
+///Time ordered queue of events, keyed on the master cycle counter
+#[derive(Clone, Debug, Default)]
+pub struct Scheduler {
+    ///Cycles elapsed since power on
+    pub now: u64,
+    ///Events sorted by time, events due at the same time keep the order they were scheduled in
+    events: Vec<(u64, Event)>,
+}
+
+impl Scheduler {
+    ///Schedules an event at an absolute time
+    pub fn schedule(&mut self, event: Event, time: u64) {
+        let index = self.events.partition_point(|(t, _)| *t <= time);
+        self.events.insert(index, (time, event));
+    }
+
+    ///Schedules an event `cycles` cycles from now
+    pub fn schedule_in(&mut self, event: Event, cycles: u64) {
+        self.schedule(event, self.now + cycles);
+    }
+
+    ///Removes every pending occurrence of an event
+    pub fn cancel(&mut self, event: Event) {
+        self.events.retain(|(_, e)| *e != event);
+    }
+
+    ///Moves an event to a new time, scheduling it if it was not pending
+    pub fn reschedule(&mut self, event: Event, time: u64) {
+        self.cancel(event);
+        self.schedule(event, time);
+    }
+
+    ///Returns the time of the first event, if any
+    pub fn next_event(&self) -> Option<u64> {
+        self.events.first().map(|(time, _)| *time)
+    }
+
+    ///Returns the time an event is scheduled at, if pending
+    pub fn time_of(&self, event: Event) -> Option<u64> {
+        self.events
+            .iter()
+            .find(|(_, e)| *e == event)
+            .map(|(time, _)| *time)
+    }
+
+    ///Removes and returns the first event if it is due at the given time
+    pub fn pop_due(&mut self, now: u64) -> Option<(u64, Event)> {
+        match self.events.first() {
+            Some((time, _)) if *time <= now => Some(self.events.remove(0)),
+            _ => None,
+        }
+    }
+}
+
+/**********
+ * EVENTS *
+ **********/
+impl Memory {
+    ///Handles, in order, every event due by now
+    pub fn run_events(&mut self) {
+        while let Some((time, event)) = self.scheduler.pop_due(self.now()) {
+            match event {
+                Event::TimerOverflow(id) => self.timer_overflow_event(id, time),
+                Event::HBlank => self.lcd_hblank_event(time),
+                Event::HDraw => self.lcd_hdraw_event(time),
+                Event::FrameSequencer => self.psg_sequencer_event(time),
+            }
+        }
+    }
+}
+
+///Runs the system until the given time.<br>
+///The CPU runs in a burst until the first event is due: the bus only compares the time with the
+///head of the queue after each instruction, and handles the events once they are due.<br>
+///While the CPU is halted nothing happens between events, so time jumps straight to the next one.
+///In Stop mode the whole system is frozen and this returns at once: only the keypad can wake it.
+pub fn run_until(cpu: &mut CPU<Memory>, time: u64) {
+    while cpu.memory.now() < time {
+        if cpu.memory.power_wake() {
+            cpu.step();
+            continue;
+        }
+        if cpu.memory.power == PowerState::Stop {
+            return;
+        }
+        let next = cpu
+            .memory
+            .scheduler
+            .next_event()
+            .map_or(time, |t| t.min(time));
+        let idle = (next - cpu.memory.now()).min(u32::MAX as u64);
+        cpu.memory.stall(idle as u32);
+        cpu.cycles += cpu.memory.take_cycles() as u64;
+    }
+}
+
+///Runs the system until the next VBlank starts, that is until the frame being drawn is complete.<br>
+///Returns at once while the system is in Stop mode.
+pub fn run_frame(cpu: &mut CPU<Memory>) {
+    let vblank = cpu.memory.lcd_next_vblank();
+    run_until(cpu, vblank);
+}
@@ -0,0 +1,181 @@
+//Hardware timers TM0 to TM3(0x0400_0100 to 0x0400_010F).
+//Timers are not ticked every cycle: each one remembers its counter value at a given time, the
+//live value is computed from the elapsed cycles when needed and overflows are scheduled events.
+//Source: https://problemkaputt.de/gbatek.htm#gbatimers
+use crate::interrupt::Interrupt;
+use crate::io;
+use crate::memory::Memory;
+use crate::scheduler::Event;
+
+///log2 of the prescaler values selectable in TMxCNT_H: 1, 64, 256 and 1024 cycles
+const PRESCALER_SHIFT: [u32; 4] = [0, 6, 8, 10];
+
+///A single timer
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Timer {
+    pub id: usize,
+    ///Value loaded in the counter on start and on overflow, written through TMxCNT_L
+    pub reload: u16,
+    ///TMxCNT_H
+    pub control: u16,
+    ///Counter value at `since`
+    counter: u16,
+    ///Time the counter was last brought up to date, always a multiple of the prescaler from the start
+    since: u64,
+}
+
+impl Timer {
+    pub fn enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+    pub fn irq(&self) -> bool {
+        self.control & 0x40 != 0
+    }
+    ///Count-up timing: the timer is clocked by the overflows of the previous one(not for TM0)
+    pub fn cascade(&self) -> bool {
+        self.id > 0 && self.control & 0x04 != 0
+    }
+    ///Cycles per tick
+    pub fn prescaler(&self) -> u32 {
+        1 << PRESCALER_SHIFT[(self.control & 3) as usize]
+    }
+    ///Whether the timer counts cycles on its own
+    fn running(&self) -> bool {
+        self.enabled() && !self.cascade()
+    }
+
+    ///Returns the counter value at the given time
+    pub fn counter_at(&self, now: u64) -> u16 {
+        if !self.running() {
+            return self.counter;
+        }
+        let ticks = (now - self.since) >> PRESCALER_SHIFT[(self.control & 3) as usize];
+        let total = self.counter as u64 + ticks;
+        if total < 0x1_0000 {
+            return total as u16;
+        }
+        // after the first overflow the counter keeps cycling from the reload value
+        let period = 0x1_0000 - self.reload as u64;
+        (self.reload as u64 + (total - 0x1_0000) % period) as u16
+    }
+
+    ///Returns the time of the next overflow, if the timer counts on its own
+    pub fn next_overflow(&self) -> Option<u64> {
+        if !self.running() {
+            return None;
+        }
+        let ticks = 0x1_0000 - self.counter as u64;
+        Some(self.since + (ticks << PRESCALER_SHIFT[(self.control & 3) as usize]))
+    }
+
+    ///Brings the counter up to date, keeping the partial tick
+    fn sync(&mut self, now: u64) {
+        if !self.running() {
+            return;
+        }
+        let shift = PRESCALER_SHIFT[(self.control & 3) as usize];
+        let ticks = (now - self.since) >> shift;
+        self.counter = self.counter_at(now);
+        self.since += ticks << shift;
+    }
+}
+
+///The 4 timers
+pub struct Timers {
+    pub timers: [Timer; 4],
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        let mut timers = [Timer::default(); 4];
+        for (id, timer) in timers.iter_mut().enumerate() {
+            timer.id = id;
+        }
+        Timers { timers }
+    }
+}
+
+///Offset(from IO_BASE) of TMxCNT_L
+pub fn register_base(id: usize) -> u32 {
+    0x100 + 4 * id as u32
+}
+
+/****************
+ * TIMER EVENTS *
+ ****************/
+impl Memory {
+    ///Live value of TMxCNT_L
+    pub(crate) fn timer_read_counter(&self, id: usize) -> u16 {
+        self.timers.timers[id].counter_at(self.now())
+    }
+
+    ///Called after a write to TMxCNT_L or TMxCNT_H
+    pub(crate) fn timer_write(&mut self, offset: u32) {
+        let id = ((offset - register_base(0)) / 4) as usize;
+        let value = self.io_stored(io::register_at(offset).unwrap()) as u16;
+        if offset == register_base(id) {
+            // writes only set the reload value, the counter picks it up on start or overflow
+            self.timers.timers[id].reload = value;
+            return;
+        }
+        // settle what happened so far with the old settings
+        self.run_events();
+        let now = self.now();
+        let timer = &mut self.timers.timers[id];
+        timer.sync(now);
+        let was_enabled = timer.enabled();
+        let was_running = timer.running();
+        timer.control = value;
+        // start edge: the counter is loaded with the reload value
+        if !was_enabled && timer.enabled() {
+            timer.counter = timer.reload;
+        }
+        if !was_running {
+            timer.since = now;
+        }
+        self.timer_schedule(id);
+    }
+
+    ///Moves the overflow event of a timer after its settings changed
+    fn timer_schedule(&mut self, id: usize) {
+        let event = Event::TimerOverflow(id);
+        match self.timers.timers[id].next_overflow() {
+            Some(time) => self.scheduler.reschedule(event, time),
+            None => self.scheduler.cancel(event),
+        }
+    }
+
+    ///Called by the scheduler when a timer counting on its own overflows
+    pub(crate) fn timer_overflow_event(&mut self, id: usize, time: u64) {
+        self.timers.timers[id].since = time;
+        self.timer_overflow(id);
+        self.timer_schedule(id);
+    }
+
+    fn timer_overflow(&mut self, id: usize) {
+        let timer = &mut self.timers.timers[id];
+        timer.counter = timer.reload;
+        if timer.irq() {
+            let interrupt = [
+                Interrupt::Timer0,
+                Interrupt::Timer1,
+                Interrupt::Timer2,
+                Interrupt::Timer3,
+            ][id];
+            self.interrupt.request(interrupt);
+        }
+        if id < 2 {
+            self.sound_timer_overflow(id);
+        }
+        // count-up timers are clocked by this overflow
+        if id < 3 {
+            let next = &mut self.timers.timers[id + 1];
+            if next.enabled() && next.cascade() {
+                next.counter = next.counter.wrapping_add(1);
+                if next.counter == 0 {
+                    self.timer_overflow(id + 1);
+                }
+            }
+        }
+    }
+}
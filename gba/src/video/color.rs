@@ -0,0 +1,129 @@
+//LCD simulation: color correction curves, and ghosting from the slow response of the screen.
+//The GBA's reflective LCD is dark and washed out: colors meant for it look far too saturated
+//on a modern monitor.
+//Source: https://near.sh/articles/video/color-emulation (GBA curve)
+use crate::ppu::frame::FrameBuffer;
+use crate::video::{Image, Rgb};
+
+///How 15 bit colors are turned into 24 bit ones
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ColorCorrection {
+    ///Each component scaled to 8 bits, as stored
+    #[default]
+    Raw,
+    ///Original GBA: the LCD's gamma and the bleeding between components
+    Gba,
+    ///Backlit GBA SP(AGS-101): close to raw, slightly less saturated
+    GbaSp,
+}
+
+impl ColorCorrection {
+    pub const ALL: [ColorCorrection; 3] = [
+        ColorCorrection::Raw,
+        ColorCorrection::Gba,
+        ColorCorrection::GbaSp,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorCorrection::Raw => "raw",
+            ColorCorrection::Gba => "gba",
+            ColorCorrection::GbaSp => "gba-sp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|curve| curve.name() == name)
+    }
+
+    ///Converts a BGR555 color
+    pub fn apply(&self, color: u16) -> Rgb {
+        let component = |shift: u16| ((color >> shift) & 0x1F) as f32 / 31.0;
+        let (r, g, b) = (component(0), component(5), component(10));
+        let [r, g, b] = match self {
+            ColorCorrection::Raw => return crate::ppu::frame::rgb888(color),
+            ColorCorrection::Gba => {
+                let (r, g, b) = (r.powf(4.0), g.powf(4.0), b.powf(4.0));
+                let scale = 255.0 / 280.0;
+                [
+                    ((255.0 * r + 50.0 * g) / 255.0).powf(1.0 / 2.2) * scale,
+                    ((10.0 * r + 230.0 * g + 30.0 * b) / 255.0).powf(1.0 / 2.2) * scale,
+                    ((50.0 * r + 10.0 * g + 220.0 * b) / 255.0).powf(1.0 / 2.2) * scale,
+                ]
+            }
+            ColorCorrection::GbaSp => {
+                let (r, g, b) = (r.powf(2.2), g.powf(2.2), b.powf(2.2));
+                [
+                    (0.86 * r + 0.10 * g + 0.04 * b).powf(1.0 / 2.2),
+                    (0.03 * r + 0.88 * g + 0.09 * b).powf(1.0 / 2.2),
+                    (0.02 * r + 0.08 * g + 0.90 * b).powf(1.0 / 2.2),
+                ]
+            }
+        };
+        let byte = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+        [byte(r), byte(g), byte(b)]
+    }
+
+    ///Conversion of every 15 bit color
+    pub fn table(&self) -> Vec<Rgb> {
+        (0..0x8000).map(|color| self.apply(color)).collect()
+    }
+}
+
+///Post-processing of the frames before they are shown
+#[derive(Clone, Debug, Default)]
+pub struct LcdFilter {
+    pub correction: ColorCorrection,
+    ///Share of the previous frame mixed in, in percent. 0 turns ghosting off, 50 blends two frames
+    ///evenly, which is what games flickering sprites for transparency expect
+    pub ghosting: u8,
+    ///Conversion table of the current curve, built on first use
+    table: Vec<Rgb>,
+    table_curve: ColorCorrection,
+    ///Previous frame, after color correction
+    previous: Option<Vec<Rgb>>,
+}
+
+impl LcdFilter {
+    pub fn new(correction: ColorCorrection, ghosting: u8) -> Self {
+        LcdFilter {
+            correction,
+            ghosting: ghosting.min(100),
+            ..Default::default()
+        }
+    }
+
+    ///Corrects the colors of a frame, then mixes the previous one in
+    pub fn process(&mut self, frame: &FrameBuffer) -> Image {
+        if self.table.is_empty() || self.table_curve != self.correction {
+            self.table = self.correction.table();
+            self.table_curve = self.correction;
+        }
+        let mut image = Image::new(FrameBuffer::WIDTH, FrameBuffer::HEIGHT);
+        for (pixel, color) in image.pixels.iter_mut().zip(frame.pixels()) {
+            *pixel = self.table[(*color & 0x7FFF) as usize];
+        }
+        let current = image.pixels.clone();
+        let weight = self.ghosting.min(100) as u32;
+        if let Some(previous) = self.previous.as_ref().filter(|_| weight > 0) {
+            for (pixel, old) in image.pixels.iter_mut().zip(previous) {
+                *pixel = mix(*pixel, *old, weight);
+            }
+        }
+        self.previous = Some(current);
+        image
+    }
+
+    ///Forgets the previous frame, e.g. after loading a save state
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+///Mixes two colors, with the given percentage of the second one
+pub fn mix(first: Rgb, second: Rgb, percent: u32) -> Rgb {
+    let component = |i: usize| {
+        ((first[i] as u32 * (100 - percent) + second[i] as u32 * percent + 50) / 100) as u8
+    };
+    [component(0), component(1), component(2)]
+}
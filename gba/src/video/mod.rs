@@ -0,0 +1,51 @@
+//Frame output for frontends: everything done to a picture once the PPU is done with it.
+//This runs outside of the emulation, on 8 bit per component images.
+pub mod color;
+pub mod png;
+pub mod scale;
+pub mod screenshot;
+pub mod viewer;
+
+use crate::ppu::frame::{self, FrameBuffer};
+
+///8 bit red, green and blue
+pub type Rgb = [u8; 3];
+
+///A picture in 24 bit colors, row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![[0; 3]; width * height],
+        }
+    }
+
+    ///Pixel at (x, y), coordinates out of the picture are clamped to its edges
+    pub fn at(&self, x: isize, y: isize) -> Rgb {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl From<&FrameBuffer> for Image {
+    fn from(frame: &FrameBuffer) -> Self {
+        Image {
+            width: FrameBuffer::WIDTH,
+            height: FrameBuffer::HEIGHT,
+            pixels: frame
+                .pixels()
+                .iter()
+                .map(|color| frame::rgb888(*color))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//Small PNG encoder: 8 bit RGB, a single IDAT chunk, rows without filtering.
+//The zlib stream uses stored blocks or the fixed Huffman codes of deflate, with a simple LZ77
+//search. Pixel art has long runs, so this gets most of what a full encoder would.
+//Source: https://www.w3.org/TR/png/
+//https://www.rfc-editor.org/rfc/rfc1950 (zlib), https://www.rfc-editor.org/rfc/rfc1951 (deflate)
+use crate::video::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+///Deflate block types
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Compression {
+    ///Uncompressed blocks of up to 65535 bytes
+    Stored,
+    ///Matches and literals with the fixed Huffman codes
+    #[default]
+    Fixed,
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+///CRC-32 of the chunks
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+///Checksum at the end of a zlib stream
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+///Deflate bit stream: values are packed from the least significant bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    ///Huffman codes are stored from their most significant bit
+    fn write_code(&mut self, code: u32, count: u32) {
+        let reversed = code.reverse_bits() >> (32 - count);
+        self.write(reversed, count);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+///Candidates tried for each match, a longer search compresses a bit better and much slower
+const MAX_CHAIN: usize = 64;
+
+///Writes a literal or length symbol with its fixed code
+fn write_symbol(out: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => out.write_code(0x30 + symbol, 8),
+        144..=255 => out.write_code(0x190 + symbol - 144, 9),
+        256..=279 => out.write_code(symbol - 256, 7),
+        _ => out.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap();
+    write_symbol(out, 257 + code as u32);
+    out.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+    let code = DISTANCE_BASE
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap();
+    out.write_code(code as u32, 5);
+    out.write(
+        (distance - DISTANCE_BASE[code] as usize) as u32,
+        DISTANCE_EXTRA[code] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    ((data[0] as usize) << 10 ^ (data[1] as usize) << 5 ^ data[2] as usize) & 0x7FFF
+}
+
+///Raw deflate stream of the data
+pub fn deflate(data: &[u8], compression: Compression) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    if compression == Compression::Stored {
+        let mut blocks = data.chunks(0xFFFF).peekable();
+        if blocks.peek().is_none() {
+            out.write(1, 3);
+            out.align();
+            out.bytes.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF]);
+        }
+        while let Some(block) = blocks.next() {
+            out.write(blocks.peek().is_none() as u32, 3);
+            out.align();
+            let len = block.len() as u16;
+            out.bytes.extend_from_slice(&len.to_le_bytes());
+            out.bytes.extend_from_slice(&(!len).to_le_bytes());
+            out.bytes.extend_from_slice(block);
+        }
+        return out.bytes;
+    }
+    // a single final block with the fixed codes
+    out.write(0b011, 3);
+    // most recent position of each hash, and the previous one with the same hash
+    let mut head = vec![usize::MAX; 0x8000];
+    let mut previous = vec![usize::MAX; data.len()];
+    let mut position = 0;
+    while position < data.len() {
+        let mut best = (0, 0);
+        if position + 3 <= data.len() {
+            let mut candidate = head[hash(&data[position..])];
+            let limit = (data.len() - position).min(MAX_MATCH);
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || position - candidate > WINDOW {
+                    break;
+                }
+                let length = (0..limit)
+                    .take_while(|i| data[candidate + i] == data[position + i])
+                    .count();
+                if length > best.0 {
+                    best = (length, position - candidate);
+                }
+                candidate = previous[candidate];
+            }
+        }
+        let step = if best.0 >= 3 {
+            write_match(&mut out, best.0, best.1);
+            best.0
+        } else {
+            write_symbol(&mut out, data[position] as u32);
+            1
+        };
+        for i in (position..position + step).filter(|i| i + 3 <= data.len()) {
+            let h = hash(&data[i..]);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+        position += step;
+    }
+    write_symbol(&mut out, 256);
+    out.align();
+    out.bytes
+}
+
+///zlib stream: header, deflate data, then the Adler-32 of the data
+pub fn zlib(data: &[u8], compression: Compression) -> Vec<u8> {
+    // deflate with a 32KB window, no dictionary, and check bits making the header a multiple of 31
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data, compression));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+///Encodes an image
+/// # Arguments
+/// * **image**: the picture, stored as 8 bit RGB
+/// * **text**: keyword and text pairs. Keywords are 1 to 79 Latin-1 characters.
+///   Texts go in tEXt chunks, in Latin-1, or in iTXt chunks(UTF-8) when they need more
+/// * **compression**: deflate blocks used for the pixels
+pub fn encode(image: &Image, text: &[(&str, String)], compression: Compression) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8 bits per component, RGB, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+    for (keyword, value) in text {
+        let mut data: Vec<u8> = keyword.chars().map(|c| c as u8).collect();
+        data.push(0);
+        if value.chars().all(|c| (c as u32) < 0x100) {
+            data.extend(value.chars().map(|c| c as u8));
+            chunk(&mut out, b"tEXt", &data);
+        } else {
+            // not compressed, no language tag nor translated keyword
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data.extend_from_slice(value.as_bytes());
+            chunk(&mut out, b"iTXt", &data);
+        }
+    }
+    // each row starts with its filter type, 0 is none
+    let mut raw = Vec::with_capacity((image.width * 3 + 1) * image.height);
+    for row in image.pixels.chunks(image.width) {
+        raw.push(0);
+        raw.extend(row.iter().flatten());
+    }
+    chunk(&mut out, b"IDAT", &zlib(&raw, compression));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
@@ -0,0 +1,215 @@
+//Pixel art upscalers, for streaming and screenshots without a GPU.
+//All of them look at the 3x3 neighbourhood of each pixel(5x5 for xBR) and write a block of
+//factor x factor pixels. Corners are handled once, the other 3 are mirrors of it.
+//Source: https://www.scale2x.it/algorithm
+//https://forums.libretro.com/t/xbr-algorithm-tutorial/123 (xBR)
+use crate::video::color::mix;
+use crate::video::{Image, Rgb};
+use std::fmt;
+
+///Upscaling filters, selectable by name
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scaler {
+    ///Each pixel repeated n times in both directions
+    Nearest(usize),
+    ///EPX: corners take the color of their 2 neighbours when they match
+    Scale2x,
+    Scale3x,
+    ///Like hq2x: corners on an edge are blended with their neighbours, by similarity in YUV
+    Hq2x,
+    ///Level 2 xBR: corners are cut along the edge with the lowest weighted difference
+    Xbr2x,
+}
+
+impl fmt::Display for Scaler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scaler::Nearest(factor) => write!(f, "nearest{}x", factor),
+            Scaler::Scale2x => write!(f, "scale2x"),
+            Scaler::Scale3x => write!(f, "scale3x"),
+            Scaler::Hq2x => write!(f, "hq2x"),
+            Scaler::Xbr2x => write!(f, "xbr2x"),
+        }
+    }
+}
+
+impl Scaler {
+    ///Names accepted by `from_name`, for help messages
+    pub const NAMES: &'static str = "nearest1x..nearest8x, scale2x, scale3x, hq2x, xbr2x";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let scaler = match name {
+            "scale2x" => Scaler::Scale2x,
+            "scale3x" => Scaler::Scale3x,
+            "hq2x" => Scaler::Hq2x,
+            "xbr2x" => Scaler::Xbr2x,
+            _ => {
+                let factor = name.strip_prefix("nearest")?.strip_suffix('x')?;
+                match factor.parse() {
+                    Ok(factor @ 1..=8) => Scaler::Nearest(factor),
+                    _ => return None,
+                }
+            }
+        };
+        Some(scaler)
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest(factor) => *factor,
+            Scaler::Scale3x => 3,
+            _ => 2,
+        }
+    }
+
+    pub fn scale(&self, image: &Image) -> Image {
+        let factor = self.factor();
+        let mut out = Image::new(image.width * factor, image.height * factor);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let block = match self {
+                    Scaler::Nearest(_) => vec![image.pixels[y * image.width + x]; factor * factor],
+                    Scaler::Scale2x => corners(image, x, y, scale2x_corner),
+                    Scaler::Scale3x => scale3x(image, x, y),
+                    Scaler::Hq2x => corners(image, x, y, hq2x_corner),
+                    Scaler::Xbr2x => corners(image, x, y, xbr_corner),
+                };
+                for (i, pixel) in block.into_iter().enumerate() {
+                    let (out_x, out_y) = (x * factor + i % factor, y * factor + i / factor);
+                    out.pixels[out_y * out.width + out_x] = pixel;
+                }
+            }
+        }
+        out
+    }
+}
+
+///Neighbourhood of a pixel seen from one of its corners: `at(u, v)` is u pixels towards the
+///corner horizontally and v vertically, so the same code handles the 4 corners
+struct Corner<'a> {
+    image: &'a Image,
+    x: isize,
+    y: isize,
+    dx: isize,
+    dy: isize,
+}
+
+impl Corner<'_> {
+    fn at(&self, u: isize, v: isize) -> Rgb {
+        self.image.at(self.x + u * self.dx, self.y + v * self.dy)
+    }
+}
+
+///2x2 block: top left, top right, bottom left, bottom right
+fn corners(image: &Image, x: usize, y: usize, corner: fn(&Corner) -> Rgb) -> Vec<Rgb> {
+    [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .iter()
+        .map(|(dx, dy)| {
+            corner(&Corner {
+                image,
+                x: x as isize,
+                y: y as isize,
+                dx: *dx,
+                dy: *dy,
+            })
+        })
+        .collect()
+}
+
+//In the corner functions, with the corner towards the bottom right:
+//A B C
+//D E F
+//G H I
+fn scale2x_corner(p: &Corner) -> Rgb {
+    let (b, d, e, f, h) = (p.at(0, -1), p.at(-1, 0), p.at(0, 0), p.at(1, 0), p.at(0, 1));
+    if b != h && d != f && f == h {
+        f
+    } else {
+        e
+    }
+}
+
+fn scale3x(image: &Image, x: usize, y: usize) -> Vec<Rgb> {
+    let (x, y) = (x as isize, y as isize);
+    let [a, b, c, d, e, f, g, h, i] = [-1, 0, 1]
+        .map(|v| [-1, 0, 1].map(|u| image.at(x + u, y + v)))
+        .concat()[..]
+    else {
+        unreachable!()
+    };
+    if b == h || d == f {
+        return vec![e; 9];
+    }
+    let pick = |when: bool, color: Rgb| if when { color } else { e };
+    vec![
+        pick(d == b, d),
+        pick((d == b && e != c) || (b == f && e != a), b),
+        pick(b == f, f),
+        pick((d == b && e != g) || (d == h && e != a), d),
+        e,
+        pick((b == f && e != i) || (h == f && e != c), f),
+        pick(d == h, d),
+        pick((d == h && e != i) || (h == f && e != g), h),
+        pick(h == f, f),
+    ]
+}
+
+///Color in Y, U and V, scaled to 0..=255
+fn yuv(color: Rgb) -> [i32; 3] {
+    let [r, g, b] = color.map(|c| c as i32);
+    [
+        (299 * r + 587 * g + 114 * b) / 1000,
+        (-169 * r - 331 * g + 500 * b) / 1000 + 128,
+        (500 * r - 419 * g - 81 * b) / 1000 + 128,
+    ]
+}
+
+///hq2x's test: colors are different past a threshold on any of Y, U or V
+fn different(first: Rgb, second: Rgb) -> bool {
+    let ([y1, u1, v1], [y2, u2, v2]) = (yuv(first), yuv(second));
+    (y1 - y2).abs() > 48 || (u1 - u2).abs() > 7 || (v1 - v2).abs() > 6
+}
+
+///Weighted YUV distance used by xBR
+fn distance(first: Rgb, second: Rgb) -> i32 {
+    let ([y1, u1, v1], [y2, u2, v2]) = (yuv(first), yuv(second));
+    48 * (y1 - y2).abs() + 7 * (u1 - u2).abs() + 6 * (v1 - v2).abs()
+}
+
+fn hq2x_corner(p: &Corner) -> Rgb {
+    let (e, f, h) = (p.at(0, 0), p.at(1, 0), p.at(0, 1));
+    if !different(f, h) && different(e, f) {
+        // on an edge between E and its 2 neighbours: half E, a quarter each
+        mix(e, mix(f, h, 50), 50)
+    } else {
+        e
+    }
+}
+
+fn xbr_corner(p: &Corner) -> Rgb {
+    let (c, e, f, g, h, i) = (
+        p.at(1, -1),
+        p.at(0, 0),
+        p.at(1, 0),
+        p.at(-1, 1),
+        p.at(0, 1),
+        p.at(1, 1),
+    );
+    let (b, d) = (p.at(0, -1), p.at(-1, 0));
+    let (f4, h5, i4, i5) = (p.at(2, 0), p.at(0, 2), p.at(2, 1), p.at(1, 2));
+    // weights of an edge along H-F, and along E-I
+    let along_hf =
+        distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4 * distance(h, f);
+    let along_ei =
+        distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+    if along_hf < along_ei && e != f && e != h {
+        let closer = if distance(e, f) <= distance(e, h) {
+            f
+        } else {
+            h
+        };
+        mix(e, closer, 50)
+    } else {
+        e
+    }
+}
@@ -0,0 +1,119 @@
+//Captures of the presented frame, as PNG or plain PPM.
+//Both carry the ROM title, the frame number and the state hash, so a picture attached to a bug
+//report says where it comes from: PNG in tEXt chunks, PPM in header comments.
+//Source: https://netpbm.sourceforge.net/doc/ppm.html
+use crate::memory::Memory;
+use crate::video::png::{self, Compression};
+use crate::video::scale::Scaler;
+use crate::video::Image;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    ///Picks the format from the file extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+///Context embedded in screenshots
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    ///Title from the cartridge header
+    pub title: String,
+    pub frame: u64,
+    ///`Memory::state_hash` when the screenshot was taken
+    pub state_hash: u64,
+    ///Upscaler applied to the picture
+    pub scaler: Option<Scaler>,
+}
+
+impl Metadata {
+    ///Keyword and text pairs, in the order they are written
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("Software", "rusty_gba".to_string()),
+            ("Title", self.title.clone()),
+            ("Frame", self.frame.to_string()),
+            ("State hash", format!("{:016X}", self.state_hash)),
+        ];
+        if let Some(scaler) = self.scaler {
+            fields.push(("Scaler", scaler.to_string()));
+        }
+        fields
+    }
+}
+
+///Binary PPM(P6), the metadata goes in comments after the magic number
+pub fn ppm(image: &Image, metadata: &Metadata) -> Vec<u8> {
+    let mut out = b"P6\n".to_vec();
+    for (keyword, value) in metadata.fields() {
+        // a comment ends at the end of the line
+        let value = value.replace(['\n', '\r'], " ");
+        out.extend_from_slice(format!("# {}: {}\n", keyword, value).as_bytes());
+    }
+    out.extend_from_slice(format!("{} {}\n255\n", image.width, image.height).as_bytes());
+    out.extend(image.pixels.iter().flatten());
+    out
+}
+
+pub fn png(image: &Image, metadata: &Metadata) -> Vec<u8> {
+    png::encode(image, &metadata.fields(), Compression::Fixed)
+}
+
+///Writes an image, in the format given by the extension of the path
+pub fn save<P: AsRef<Path>>(path: P, image: &Image, metadata: &Metadata) -> io::Result<()> {
+    let data = match ImageFormat::from_path(&path) {
+        Some(ImageFormat::Png) => png(image, metadata),
+        Some(ImageFormat::Ppm) => ppm(image, metadata),
+        None => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "screenshots are saved as .png or .ppm",
+            ))
+        }
+    };
+    fs::write(path, data)
+}
+
+/***************
+ * SCREENSHOTS *
+ ***************/
+impl Memory {
+    ///Context of the presented frame
+    pub fn screenshot_metadata(&self) -> Metadata {
+        Metadata {
+            title: self.cartridge_header().title,
+            frame: self.ppu.frame_buffer.frame(),
+            state_hash: self.state_hash(),
+            scaler: None,
+        }
+    }
+
+    ///Saves the presented frame to a .png or .ppm file
+    pub fn screenshot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.screenshot_scaled(path, Scaler::Nearest(1))
+    }
+
+    ///Saves the presented frame to a .png or .ppm file, upscaled
+    pub fn screenshot_scaled<P: AsRef<Path>>(&self, path: P, scaler: Scaler) -> io::Result<()> {
+        let image = scaler.scale(&Image::from(&self.ppu.frame_buffer));
+        let metadata = Metadata {
+            scaler: Some(scaler),
+            ..self.screenshot_metadata()
+        };
+        save(path, &image, &metadata)
+    }
+}
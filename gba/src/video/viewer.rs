@@ -0,0 +1,222 @@
+//VRAM viewers: what is in palette RAM, VRAM and OAM, as pictures, to debug games that render wrong.
+//They decode memory like the renderer does, without the screen around it: maps at their full
+//size without scrolling, sprites alone without transformation.
+//Transparent dots show the backdrop color(palette entry 0).
+use crate::memory::Memory;
+use crate::ppu::frame::rgb888;
+use crate::ppu::obj::{self, ObjMode, Sprite, OBJ_VRAM};
+use crate::ppu::render::{VideoMemory, TRANSPARENT};
+use crate::ppu::text::{self, TextControl};
+use crate::ppu::{affine, bitmap};
+use crate::video::screenshot;
+use crate::video::Image;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///Tiles per row of a tile sheet
+pub const SHEET_COLUMNS: usize = 32;
+
+///Turns BGR555 colors in an image, TRANSPARENT becoming the backdrop
+fn image(
+    video: &VideoMemory,
+    width: usize,
+    height: usize,
+    color: impl Fn(usize, usize) -> u16,
+) -> Image {
+    let mut out = Image::new(width, height);
+    for (i, pixel) in out.pixels.iter_mut().enumerate() {
+        let color = match color(i % width, i / width) {
+            TRANSPARENT => video.bg_color(0),
+            color => color,
+        };
+        *pixel = rgb888(color);
+    }
+    out
+}
+
+///All of VRAM as tiles, 32 per row
+/// * **bpp8**: 256 colors tiles(64 bytes), otherwise 16 colors(32 bytes)
+/// * **palette**: 16 colors bank, 0-15 for BG palettes, 16-31 for OBJ ones.
+///   256 colors tiles use the BG palette below 16, the OBJ one above
+pub fn tile_sheet(video: &VideoMemory, bpp8: bool, palette: usize) -> Image {
+    let tile_size = if bpp8 { 64 } else { 32 };
+    let rows = video.vram.len() / tile_size / SHEET_COLUMNS;
+    image(video, SHEET_COLUMNS * 8, rows * 8, |x, y| {
+        let tile = (y / 8) * SHEET_COLUMNS + x / 8;
+        let (tx, ty) = (x % 8, y % 8);
+        let (bank, index) = if bpp8 {
+            (
+                palette / 16 * 256,
+                video.vram_8(tile * 64 + ty * 8 + tx) as usize,
+            )
+        } else {
+            let byte = video.vram_8(tile * 32 + ty * 4 + tx / 2);
+            (
+                (palette % 32) * 16,
+                ((byte >> ((tx & 1) * 4)) & 0xF) as usize,
+            )
+        };
+        // color 0 of any palette is transparent, like on screen
+        if index == 0 {
+            TRANSPARENT
+        } else {
+            video.bg_color(bank + index)
+        }
+    })
+}
+
+///The 512 colors as 8x8 squares, 16 per row: BG palette on top, OBJ palette below
+pub fn palette_swatch(video: &VideoMemory) -> Image {
+    let mut out = Image::new(16 * 8, 32 * 8);
+    for (i, pixel) in out.pixels.iter_mut().enumerate() {
+        let (x, y) = (i % (16 * 8), i / (16 * 8));
+        *pixel = rgb888(video.bg_color((y / 8) * 16 + x / 8));
+    }
+    out
+}
+
+///A background at its native size, as configured in the current mode.<br>
+///Text and affine maps are drawn whole, a bitmap shows the page selected in DISPCNT.
+///Returns None if the mode has no such background
+pub fn tilemap(video: &VideoMemory, bg: usize) -> Option<Image> {
+    let control = video.bg_control(bg);
+    let map = match (video.mode(), bg) {
+        (0, _) | (1, 0..=1) => {
+            let control = TextControl::from_bgcnt(control);
+            image(video, control.width, control.height, |x, y| {
+                text::pixel_at(video, &control, x, y)
+            })
+        }
+        (1, 2) | (2, 2..=3) => {
+            let size = affine::map_size(control);
+            image(video, size, size, |x, y| {
+                affine::pixel_at(video, control, x, y)
+            })
+        }
+        (3..=5, 2) => {
+            let (width, height) = bitmap::dimensions(video);
+            let page = bitmap::page(video);
+            image(video, width, height, |x, y| {
+                bitmap::pixel_at(video, page, x, y)
+            })
+        }
+        _ => return None,
+    };
+    Some(map)
+}
+
+///A sprite at its size, with its flips but without affine transformation
+pub fn sprite_image(video: &VideoMemory, sprite: &Sprite) -> Image {
+    let (width, height) = (sprite.width as usize, sprite.height as usize);
+    image(video, width, height, |x, y| {
+        let tx = if sprite.hflip { width - 1 - x } else { x };
+        let ty = if sprite.vflip { height - 1 - y } else { y };
+        obj::texel(video, sprite, tx, ty).unwrap_or(TRANSPARENT)
+    })
+}
+
+///One line per sprite with its decoded attributes
+pub fn oam_listing(video: &VideoMemory) -> String {
+    let mut out = String::new();
+    for sprite in obj::sprites(video) {
+        let mode = match sprite.mode {
+            ObjMode::Normal => "normal",
+            ObjMode::SemiTransparent => "semi-transparent",
+            ObjMode::Window => "window",
+            ObjMode::Prohibited => "prohibited",
+        };
+        let _ = write!(
+            out,
+            "{:3}: x {:4} y {:3} size {}x{} tile {:3} (VRAM {:#07X}) {} palette {:2} priority {} {}",
+            sprite.index,
+            sprite.x,
+            sprite.y,
+            sprite.width,
+            sprite.height,
+            sprite.tile,
+            OBJ_VRAM + sprite.tile * 32,
+            if sprite.bpp8 { "256 colors" } else { "16 colors" },
+            sprite.palette,
+            sprite.priority,
+            mode,
+        );
+        if sprite.affine {
+            let [pa, pb, pc, pd] = obj::affine_parameters(video, sprite.affine_group);
+            let _ = write!(
+                out,
+                " affine {} [{:#06X} {:#06X} {:#06X} {:#06X}]",
+                sprite.affine_group, pa as u16, pb as u16, pc as u16, pd as u16
+            );
+        }
+        for (set, flag) in [
+            (sprite.double_size, " double-size"),
+            (sprite.disabled, " disabled"),
+            (sprite.mosaic, " mosaic"),
+            (sprite.hflip, " h-flip"),
+            (sprite.vflip, " v-flip"),
+        ] {
+            if set {
+                out.push_str(flag);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/****************
+ * VRAM VIEWERS *
+ ****************/
+impl Memory {
+    ///Writes every viewer in a directory, as PNG pictures and a text listing:
+    /// * tiles_4bpp.png with the given palette bank, tiles_8bpp.png with its BG or OBJ palette
+    /// * bg0.png to bg3.png, for the backgrounds of the current mode
+    /// * palette.png
+    /// * obj_000.png to obj_127.png, and oam.txt for their attributes
+    ///
+    ///Returns the files written
+    pub fn dump_vram<P: AsRef<Path>>(
+        &self,
+        directory: P,
+        palette: usize,
+    ) -> io::Result<Vec<PathBuf>> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        let video = self.video_memory();
+        let metadata = self.screenshot_metadata();
+        let mut images = vec![
+            (
+                "tiles_4bpp.png".to_string(),
+                tile_sheet(&video, false, palette),
+            ),
+            (
+                "tiles_8bpp.png".to_string(),
+                tile_sheet(&video, true, palette),
+            ),
+            ("palette.png".to_string(), palette_swatch(&video)),
+        ];
+        for bg in 0..4 {
+            if let Some(map) = tilemap(&video, bg) {
+                images.push((format!("bg{}.png", bg), map));
+            }
+        }
+        for sprite in obj::sprites(&video) {
+            images.push((
+                format!("obj_{:03}.png", sprite.index),
+                sprite_image(&video, &sprite),
+            ));
+        }
+        let mut written = Vec::new();
+        for (name, image) in images {
+            let path = directory.join(name);
+            screenshot::save(&path, &image, &metadata)?;
+            written.push(path);
+        }
+        let path = directory.join("oam.txt");
+        fs::write(&path, oam_listing(&video))?;
+        written.push(path);
+        Ok(written)
+    }
+}
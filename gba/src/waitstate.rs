@@ -0,0 +1,250 @@
+//GamePak wait state control(WAITCNT, 0x0400_0204) and the GamePak prefetch buffer.
+//Everything here only computes how many cycles a memory access takes, the data itself is handled by `Memory`.
+//Source: https://problemkaputt.de/gbatek.htm#gbasystemcontrol
+
+///Width of a memory access
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccessWidth {
+    Byte = 1,
+    Half = 2,
+    Word = 4,
+}
+
+///Wait states for the first(non sequential) access, indexed by the 2 WAITCNT bits
+const FIRST_ACCESS: [u32; 4] = [4, 3, 2, 8];
+///Wait states for the second(sequential) access of each ROM window, indexed by the WAITCNT bit
+const SECOND_ACCESS: [[u32; 2]; 3] = [[2, 1], [4, 1], [8, 1]];
+///Size of the prefetch buffer, in halfwords
+pub const PREFETCH_CAPACITY: u32 = 8;
+
+///Decoded content of WAITCNT
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WaitControl {
+    ///SRAM wait states
+    pub sram: u32,
+    ///Non sequential wait states for WS0, WS1 and WS2
+    pub first: [u32; 3],
+    ///Sequential wait states for WS0, WS1 and WS2
+    pub second: [u32; 3],
+    pub prefetch: bool,
+}
+
+impl WaitControl {
+    ///Decodes a WAITCNT value
+    pub fn from_waitcnt(value: u16) -> Self {
+        let bits = |start: u16, len: u16| ((value >> start) & ((1 << len) - 1)) as usize;
+        WaitControl {
+            sram: FIRST_ACCESS[bits(0, 2)],
+            first: [
+                FIRST_ACCESS[bits(2, 2)],
+                FIRST_ACCESS[bits(5, 2)],
+                FIRST_ACCESS[bits(8, 2)],
+            ],
+            second: [
+                SECOND_ACCESS[0][bits(4, 1)],
+                SECOND_ACCESS[1][bits(7, 1)],
+                SECOND_ACCESS[2][bits(10, 1)],
+            ],
+            prefetch: bits(14, 1) == 1,
+        }
+    }
+
+    ///Cycles taken by a 16 bit access to the given ROM window(0 to 2)
+    pub fn rom_cycles(&self, window: usize, sequential: bool) -> u32 {
+        if sequential {
+            1 + self.second[window]
+        } else {
+            1 + self.first[window]
+        }
+    }
+}
+
+impl Default for WaitControl {
+    fn default() -> Self {
+        WaitControl::from_waitcnt(0)
+    }
+}
+
+///The GamePak prefetch buffer.<br>
+///While the CPU is not using the GamePak bus, it keeps reading the next sequential halfwords
+///of ROM after the last opcode fetch, up to 8 of them.<br>
+///Quirks modelled:
+/// * it only fills while the GamePak bus is free(accesses to other regions, or its own hits)
+/// * an opcode fetch hitting a buffered halfword takes 1 cycle, no matter the wait states
+/// * an opcode fetch hitting the halfword being fetched only waits for the remaining cycles
+/// * any ROM data access, or an opcode fetch elsewhere, throws the buffer away
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Prefetch {
+    ///Address of the first halfword held(or being fetched) by the buffer
+    pub head: u32,
+    ///Halfwords ready in the buffer
+    pub count: u32,
+    ///Cycles already spent on the halfword being fetched
+    pub progress: u32,
+    ///Whether the buffer is following an opcode stream
+    pub active: bool,
+}
+
+impl Prefetch {
+    ///Throws away the buffer content
+    pub fn flush(&mut self) {
+        self.count = 0;
+        self.progress = 0;
+        self.active = false;
+    }
+
+    ///Restarts the buffer right after an opcode fetch at the given address
+    pub fn restart(&mut self, next: u32) {
+        self.head = next;
+        self.count = 0;
+        self.progress = 0;
+        self.active = true;
+    }
+
+    ///Lets the buffer work in background for the given amount of cycles
+    /// * **halfword_cycles**: cycles taken by a sequential halfword read from the current window
+    pub fn run(&mut self, cycles: u32, halfword_cycles: u32) {
+        if !self.active || self.count == PREFETCH_CAPACITY {
+            return;
+        }
+        self.progress += cycles;
+        while self.progress >= halfword_cycles && self.count < PREFETCH_CAPACITY {
+            self.progress -= halfword_cycles;
+            self.count += 1;
+        }
+        if self.count == PREFETCH_CAPACITY {
+            self.progress = 0;
+        }
+    }
+
+    ///Tries to serve an opcode fetch of `halfwords` halfwords from the buffer.<br>
+    ///Returns the cycles taken if the fetch hits the buffer, None otherwise.
+    pub fn take(&mut self, address: u32, halfwords: u32, halfword_cycles: u32) -> Option<u32> {
+        if !self.active || address != self.head {
+            return None;
+        }
+        let mut cycles = 0;
+        for _ in 0..halfwords {
+            if self.count == 0 {
+                // wait for the halfword currently on its way, the next one starts right after
+                let wait = halfword_cycles - self.progress;
+                self.run(wait, halfword_cycles);
+                cycles += wait;
+            }
+            self.count -= 1;
+            self.head += 2;
+        }
+        if cycles == 0 {
+            // served straight from the buffer, which keeps reading during that cycle
+            cycles = 1;
+            self.run(cycles, halfword_cycles);
+        }
+        Some(cycles)
+    }
+}
+
+///Timing state of the whole memory bus
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct WaitState {
+    pub control: WaitControl,
+    pub prefetch: Prefetch,
+    ///Address following the last access, used to detect sequential accesses
+    next_address: u32,
+    ///Cycles accumulated since the CPU last asked for them
+    pub cycles: u32,
+}
+
+impl WaitState {
+    ///Updates the configuration after a write to WAITCNT
+    pub fn write_waitcnt(&mut self, value: u16) {
+        self.control = WaitControl::from_waitcnt(value);
+        if !self.control.prefetch {
+            self.prefetch.flush();
+        }
+    }
+
+    ///Computes the cost of an access, adds it to the pending cycles and returns it
+    /// # Arguments
+    /// * **address**: address being accessed
+    /// * **width**: access width
+    /// * **opcode**: whether the CPU is fetching an instruction
+    pub fn access(&mut self, address: u32, width: AccessWidth, opcode: bool) -> u32 {
+        // ROM is divided in 128KBytes pages, crossing one always restarts a non sequential access
+        let sequential = address == self.next_address && address & 0x1_FFFF != 0;
+        self.next_address = address.wrapping_add(width as u32);
+
+        let cycles = match address >> 24 {
+            0x08..=0x0D => self.rom_access(address, width, opcode, sequential),
+            _ => {
+                let cycles = self.access_cycles(address, width, sequential);
+                // GamePak bus is free, the prefetcher can work meanwhile
+                let halfword_cycles = self.prefetch_halfword_cycles();
+                self.prefetch.run(cycles, halfword_cycles);
+                cycles
+            }
+        };
+        self.cycles += cycles;
+        cycles
+    }
+
+    ///Cost of a single access, without any prefetch effect.<br>
+    ///Also used by DMA, which keeps separate sequential streams for source and destination.
+    pub fn access_cycles(&self, address: u32, width: AccessWidth, sequential: bool) -> u32 {
+        match address >> 24 {
+            0x08..=0x0D => {
+                let window = ((address >> 25) - 4) as usize;
+                // a 32 bit access is split in two 16 bit accesses, the second one always sequential
+                let mut cycles = self.control.rom_cycles(window, sequential);
+                if width == AccessWidth::Word {
+                    cycles += self.control.rom_cycles(window, true);
+                }
+                cycles
+            }
+            0x02 if width == AccessWidth::Word => 6,
+            0x02 => 3,
+            0x05 | 0x06 if width == AccessWidth::Word => 2,
+            0x0E | 0x0F => 1 + self.control.sram,
+            _ => 1,
+        }
+    }
+
+    ///Returns the cycles accumulated since the last call and resets them
+    pub fn take_cycles(&mut self) -> u32 {
+        core::mem::take(&mut self.cycles)
+    }
+
+    fn rom_access(
+        &mut self,
+        address: u32,
+        width: AccessWidth,
+        opcode: bool,
+        sequential: bool,
+    ) -> u32 {
+        let window = ((address >> 25) - 4) as usize;
+        let halfwords = if width == AccessWidth::Word { 2 } else { 1 };
+        let halfword_cycles = self.control.rom_cycles(window, true);
+
+        if opcode && self.control.prefetch {
+            if let Some(cycles) = self.prefetch.take(address, halfwords, halfword_cycles) {
+                return cycles;
+            }
+        }
+        // the CPU takes the GamePak bus: whatever was prefetched is lost
+        self.prefetch.flush();
+        let cycles = self.access_cycles(address, width, sequential);
+        if opcode && self.control.prefetch {
+            self.prefetch.restart(address + width as u32);
+        }
+        cycles
+    }
+
+    ///Cycles needed by the prefetcher to read a halfword from the window it is following
+    fn prefetch_halfword_cycles(&self) -> u32 {
+        match self.prefetch.head >> 24 {
+            0x08..=0x0D => self
+                .control
+                .rom_cycles(((self.prefetch.head >> 25) - 4) as usize, true),
+            _ => 1,
+        }
+    }
+}
@@ -1,13 +1,82 @@
 pub use arm7tdmi::cpu::MemoryInterface;
 pub use arm7tdmi::cpu::CPU;
+pub use gba::cartridge::Cartridge;
+use gba::debug::SWITCH_NAMES;
 pub use gba::memory::Memory;
+use gba::video::scale::Scaler;
 // use std::fmt::Display;
 // use std::fmt::Formatter;
 use std::io::Write;
 use std::iter;
 use std::{fs, io::ErrorKind};
 
+///Command line: `rusty_gba [rom] [--scale <filter>] [--screenshot <file.png|file.ppm>]`<br>
+///`[--disable <switch,...>]` hides layers or mutes channels, for debugging<br>
+///or `rusty_gba dump-vram <directory> [rom] [--palette <bank>]` to export the VRAM viewers
+#[derive(Debug, Default)]
+struct Options {
+    rom: Option<String>,
+    ///Upscaler applied to frames written out
+    scale: Option<Scaler>,
+    ///Picture saved once the first frame is presented
+    screenshot: Option<String>,
+    ///Directory the VRAM viewers are written to
+    dump_vram: Option<String>,
+    ///Palette bank of the tile sheets, 0-15 for BG, 16-31 for OBJ
+    palette: usize,
+    ///Debug switches turned off
+    disabled: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.peekable();
+        if args.peek().map(String::as_str) == Some("dump-vram") {
+            args.next();
+            let directory = args.next().ok_or("dump-vram needs a directory")?;
+            options.dump_vram = Some(directory);
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scale" => {
+                    let name = args.next().ok_or("--scale needs a filter name")?;
+                    let scaler = Scaler::from_name(&name).ok_or_else(|| {
+                        format!("unknown filter {}, expected one of {}", name, Scaler::NAMES)
+                    })?;
+                    options.scale = Some(scaler);
+                }
+                "--screenshot" => {
+                    let path = args.next().ok_or("--screenshot needs a file name")?;
+                    options.screenshot = Some(path);
+                }
+                "--disable" => {
+                    let names = args.next().ok_or("--disable needs switch names")?;
+                    options
+                        .disabled
+                        .extend(names.split(',').map(str::to_string));
+                }
+                "--palette" => {
+                    let bank = args.next().and_then(|bank| bank.parse().ok());
+                    options.palette = bank
+                        .filter(|bank| *bank < 32)
+                        .ok_or("--palette needs a bank number from 0 to 31")?;
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => options.rom = Some(arg),
+            }
+        }
+        // frames are only written out by screenshots for now
+        if options.scale.is_some() && options.screenshot.is_none() {
+            return Err("--scale needs --screenshot, nothing else is scaled".to_string());
+        }
+        Ok(options)
+    }
+}
+
 pub fn main() {
+    let options =
+        Options::parse(std::env::args().skip(1)).unwrap_or_else(|error| panic!("{}", error));
     let _bios = fs::read("gba_bios.bin").unwrap_or_else(|error| {
         if error.kind() == ErrorKind::NotFound {
             panic!("GBA Bios Not found");
@@ -16,6 +85,46 @@ pub fn main() {
         }
     });
 
+    //create a new cpu
+    let mut cpu: CPU<Memory> = CPU::new();
+    cpu.memory.init_bios(_bios);
+    for name in &options.disabled {
+        if !cpu.memory.debug_switch(name, false) {
+            panic!("unknown switch {}, expected one of {}", name, SWITCH_NAMES);
+        }
+    }
+    // the game ROM is optional
+    if let Some(path) = &options.rom {
+        let cartridge = Cartridge::load(path).unwrap_or_else(|error| panic!("{}", error));
+        for problem in cartridge.diagnostics() {
+            println!("Warning: {}", problem);
+        }
+        println!(
+            "Loaded {} ({}) v{}",
+            cartridge.header.title, cartridge.header.game_code, cartridge.header.version
+        );
+        cpu.memory.load_cartridge(&cartridge);
+    }
+    if let Some(directory) = &options.dump_vram {
+        match cpu.memory.dump_vram(directory, options.palette) {
+            Ok(files) => println!("Wrote {} files in {}", files.len(), directory),
+            Err(error) => println!("Error: can't write in {}: {}", directory, error),
+        }
+        return;
+    }
+    if let Some(path) = &options.screenshot {
+        // the CPU can't run games yet: let the LCD draw a frame of the current memory
+        let vblank = cpu.memory.lcd_next_vblank();
+        let cycles = (vblank - cpu.memory.now()) as u32;
+        cpu.memory.stall(cycles);
+        cpu.memory.take_cycles();
+        let scaler = options.scale.unwrap_or(Scaler::Nearest(1));
+        match cpu.memory.screenshot_scaled(path, scaler) {
+            Ok(()) => println!("Saved {} ({})", path, scaler),
+            Err(error) => println!("Error: can't save {}: {}", path, error),
+        }
+        return;
+    }
     //create a file to write in append using fs
     let mut file = fs::OpenOptions::new()
         .create_new(true)
@@ -23,9 +132,6 @@ pub fn main() {
         .append(true)
         .open("dump_txt/log_arm2.txt")
         .unwrap();
-    //create a new cpu
-    let mut cpu: CPU<Memory> = CPU::new();
-    cpu.memory.init_bios(_bios);
     for (i, instr) in cpu.memory.bios.clone().chunks(4).into_iter().enumerate() {
         let instr_as_u32 = u32::from_le_bytes([instr[0], instr[1], instr[2], instr[3]]);
         let instr_fmt = format!("{}:{}\n", i * 4, cpu.decode(instr_as_u32));
@@ -0,0 +1,166 @@
+use super::{elapse_to, line};
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::affine::Transform;
+use gba::ppu::render::{self};
+use gba::ppu::{AffineRef, LINE_CYCLES, SCREEN_WIDTH};
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG2CNT: u32 = 0x0400_000C;
+const BG2PA: u32 = 0x0400_0020;
+const BG2X: u32 = 0x0400_0028;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const BACKDROP: u16 = 0x1234;
+
+///Mode 2 with BG2 on: 128x128 map at block 1, tiles at 0. Tile n is filled with color n
+fn setup() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BACKDROP);
+    mem.write_16(DISPCNT, 0x0402);
+    mem.write_16(BG2CNT, 0x0100);
+    for tile in 1..4u32 {
+        mem.write_16(PALETTE + tile * 2, tile as u16);
+        for i in 0..32 {
+            mem.write_16(VRAM + tile * 64 + i * 2, (tile * 0x0101) as u16);
+        }
+    }
+    mem
+}
+
+///Puts a tile in the 128x128 map, entries are single bytes
+fn map(mem: &mut Memory, x: u32, y: u32, tile: u8) {
+    let address = VRAM + 0x800 + y * 16 + x;
+    let pair = mem.read_16(address & !1).to_le_bytes();
+    let value = if address & 1 == 0 {
+        [tile, pair[1]]
+    } else {
+        [pair[0], tile]
+    };
+    mem.write_16(address & !1, u16::from_le_bytes(value));
+}
+
+#[cfg(test)]
+#[test]
+fn identity_map() {
+    let mut mem = setup();
+    map(&mut mem, 1, 0, 1);
+    map(&mut mem, 2, 0, 2);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[7], BACKDROP);
+    assert_eq!(line[8..16], [1; 8]);
+    assert_eq!(line[16..24], [2; 8]);
+}
+
+#[test]
+fn overflow_is_transparent_or_wraps() {
+    let mut mem = setup();
+    map(&mut mem, 0, 0, 3);
+    assert_eq!(line(&mut mem, 0)[128], BACKDROP);
+    mem.write_16(BG2CNT, 0x2100);
+    let wrapped = line(&mut mem, 0);
+    assert_eq!(wrapped[0], 3);
+    assert_eq!(wrapped[128..136], [3; 8]);
+    //negative coordinates wrap too
+    mem.write_32(BG2X, (-8i32 << 8) as u32 & 0x0FFF_FFFF);
+    assert_eq!(line(&mut mem, 0)[8], 3);
+}
+
+#[test]
+fn scaling() {
+    let mut mem = setup();
+    map(&mut mem, 1, 0, 1);
+    //zoom in by 2: a tile is 16 dots wide
+    mem.write_16(BG2PA, 0x0080);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[15], BACKDROP);
+    assert_eq!(line[16..32], [1; 16]);
+}
+
+#[test]
+fn rotation_by_90_degrees() {
+    let mut mem = setup();
+    map(&mut mem, 0, 1, 2);
+    //the line walks down the first column of the map
+    mem.write_16(BG2PA, 0);
+    mem.write_16(BG2PA + 4, 0x0100);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[0], BACKDROP);
+    assert_eq!(line[8..16], [2; 8]);
+}
+
+#[test]
+fn fractions_accumulate() {
+    let transform = Transform {
+        pa: 0x10,
+        pc: 0,
+        origin: AffineRef { x: 0x80, y: 0 },
+    };
+    //1/16 of a texel per dot, starting from half a texel
+    assert_eq!(transform.texel(0), (0, 0));
+    assert_eq!(transform.texel(7), (0, 0));
+    assert_eq!(transform.texel(8), (1, 0));
+    assert_eq!(transform.texel(120), (8, 0));
+    let negative = Transform {
+        pa: -0x100,
+        pc: 0,
+        origin: AffineRef { x: 0, y: 0 },
+    };
+    assert_eq!(negative.texel(1), (-1, 0));
+}
+
+#[test]
+fn mode1_and_mode2_layers() {
+    let mut mem = setup();
+    mem.write_16(DISPCNT, 0x0F01);
+    let layers = render::render_backgrounds(&mem.video_memory(), &mem.ppu.affine);
+    assert_eq!(layers.iter().map(|l| l.bg).collect::<Vec<_>>(), [0, 1, 2]);
+    mem.write_16(DISPCNT, 0x0F02);
+    let layers = render::render_backgrounds(&mem.video_memory(), &mem.ppu.affine);
+    assert_eq!(layers.iter().map(|l| l.bg).collect::<Vec<_>>(), [2, 3]);
+}
+
+#[test]
+fn bg3_has_its_own_parameters() {
+    let mut mem = setup();
+    mem.write_16(DISPCNT, 0x0802);
+    mem.write_16(BG2CNT + 2, 0x0100);
+    map(&mut mem, 1, 0, 1);
+    mem.write_32(BG2X + 0x10, 8 << 8);
+    assert_eq!(line(&mut mem, 0)[0], 1);
+    assert_eq!(mem.ppu.affine[0].x, 0);
+}
+
+#[test]
+fn reference_point_moves_every_line() {
+    let mut mem = setup();
+    map(&mut mem, 1, 0, 1);
+    //shear: each line starts one texel further right, while staying on map row 0
+    mem.write_16(BG2PA + 2, 0x0100);
+    mem.write_16(BG2PA + 6, 0);
+    elapse_to(&mut mem, LINE_CYCLES * 160);
+    assert_eq!(mem.ppu.screen[8], 1);
+    assert_eq!(mem.ppu.screen[SCREEN_WIDTH + 7], 1);
+    assert_eq!(mem.ppu.screen[4 * SCREEN_WIDTH + 4], 1);
+    assert_eq!(mem.ppu.screen[4 * SCREEN_WIDTH + 12], BACKDROP);
+}
+
+#[test]
+fn write_mid_frame_reloads_the_reference() {
+    let mut mem = setup();
+    for x in 0..16 {
+        for y in 0..16 {
+            map(&mut mem, x, y, 1);
+        }
+    }
+    map(&mut mem, 0, 0, 2);
+    //frozen on row 0 by PD = 0
+    mem.write_16(BG2PA + 6, 0);
+    elapse_to(&mut mem, LINE_CYCLES * 50);
+    mem.write_32(BG2X, 8 << 8);
+    elapse_to(&mut mem, LINE_CYCLES * 160);
+    assert_eq!(mem.ppu.screen[49 * SCREEN_WIDTH], 2);
+    assert_eq!(mem.ppu.screen[50 * SCREEN_WIDTH], 1);
+    //BG2X keeps its value and is latched again at VBlank
+    assert_eq!(mem.ppu.affine[0].x, 8 << 8);
+}
@@ -0,0 +1,128 @@
+use super::{elapse_to, line};
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::render::WHITE;
+use gba::ppu::{FRAME_CYCLES, LINE_CYCLES, SCREEN_WIDTH};
+
+const DISPCNT: u32 = 0x0400_0000;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const BACKDROP: u16 = 0x1234;
+
+///Memory with a recognizable backdrop color
+fn setup(dispcnt: u16) -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BACKDROP);
+    mem.write_16(DISPCNT, dispcnt);
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn mode3_direct_color() {
+    let mut mem = setup(0x0403);
+    mem.write_16(VRAM + 10 * 2, 0x001F);
+    mem.write_16(VRAM + 239 * 2, 0x7C00);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[10], 0x001F);
+    assert_eq!(line[239], 0x7C00);
+    //a black dot is still a dot
+    assert_eq!(line[0], 0);
+}
+
+#[test]
+fn disabled_bg2_shows_backdrop() {
+    let mut mem = setup(0x0003);
+    mem.write_16(VRAM, 0x001F);
+    assert!(line(&mut mem, 0).iter().all(|pixel| *pixel == BACKDROP));
+}
+
+#[test]
+fn forced_blank_is_white() {
+    let mut mem = setup(0x0483);
+    mem.write_16(VRAM, 0x001F);
+    assert!(line(&mut mem, 0).iter().all(|pixel| *pixel == WHITE));
+}
+
+#[test]
+fn mode4_palette_and_page_flip() {
+    let mut mem = setup(0x0404);
+    mem.write_16(PALETTE + 2 * 5, 0x03E0);
+    mem.write_16(PALETTE + 2 * 6, 0x7FFF);
+    //pixels 0 and 1 share a halfword
+    mem.write_16(VRAM, 0x0500);
+    mem.write_16(VRAM + 0xA000, 0x0006);
+    let front = line(&mut mem, 0);
+    assert_eq!(front[0], BACKDROP);
+    assert_eq!(front[1], 0x03E0);
+    mem.write_16(DISPCNT, 0x0414);
+    let back = line(&mut mem, 0);
+    assert_eq!(back[0], 0x7FFF);
+    assert_eq!(back[1], BACKDROP);
+}
+
+#[test]
+fn mode5_small_frame() {
+    let mut mem = setup(0x0405);
+    mem.write_16(VRAM + 159 * 2, 0x0011);
+    mem.write_16(VRAM + 0xA000, 0x0022);
+    let front = line(&mut mem, 0);
+    assert_eq!(front[159], 0x0011);
+    //only 160 dots wide
+    assert_eq!(front[160], BACKDROP);
+    mem.write_16(DISPCNT, 0x0415);
+    assert_eq!(line(&mut mem, 0)[0], 0x0022);
+}
+
+#[test]
+fn affine_scaling_and_reference_point() {
+    let mut mem = setup(0x0403);
+    for x in 0..240 {
+        mem.write_16(VRAM + x * 2, x as u16);
+    }
+    //zoom out by 2
+    mem.write_16(0x0400_0020, 0x0200);
+    assert_eq!(line(&mut mem, 0)[10], 20);
+    assert_eq!(line(&mut mem, 0)[120], BACKDROP);
+    //writing BG2X moves the reference point at once
+    mem.write_16(0x0400_0020, 0x0100);
+    mem.write_32(0x0400_0028, 30 << 8);
+    assert_eq!(mem.ppu.affine[0].x, 30 << 8);
+    assert_eq!(line(&mut mem, 0)[0], 30);
+    //negative values are sign extended from 28 bits
+    mem.write_32(0x0400_0028, 0x0FFF_FF00);
+    assert_eq!(mem.ppu.affine[0].x, -0x100);
+    assert_eq!(line(&mut mem, 0)[0], BACKDROP);
+    assert_eq!(line(&mut mem, 0)[1], 0);
+}
+
+#[test]
+fn frame_is_drawn_line_by_line() {
+    let mut mem = setup(0x0403);
+    for y in 0..160 {
+        mem.write_16(VRAM + y * 240 * 2, y as u16);
+    }
+    elapse_to(&mut mem, LINE_CYCLES * 160);
+    for y in 0..160 {
+        assert_eq!(mem.ppu.screen[y * SCREEN_WIDTH], y as u16);
+    }
+}
+
+#[test]
+fn reference_point_steps_by_pd_and_reloads_at_vblank() {
+    let mut mem = setup(0x0403);
+    for y in 0..160 {
+        mem.write_16(VRAM + y * 240 * 2, y as u16 + 1);
+    }
+    //every line shows line 3
+    mem.write_16(0x0400_0026, 0);
+    mem.write_32(0x0400_002C, 3 << 8);
+    elapse_to(&mut mem, LINE_CYCLES * 160);
+    assert_eq!(mem.ppu.screen[0], 4);
+    assert_eq!(mem.ppu.screen[100 * SCREEN_WIDTH], 4);
+    //back to the identity: PD is used from the next line, BG2Y is reloaded at VBlank
+    mem.write_16(0x0400_0026, 0x100);
+    elapse_to(&mut mem, FRAME_CYCLES + LINE_CYCLES * 160);
+    assert_eq!(mem.ppu.screen[0], 4);
+    assert_eq!(mem.ppu.screen[100 * SCREEN_WIDTH], 104);
+}
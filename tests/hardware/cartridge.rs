@@ -0,0 +1,105 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::cartridge::*;
+use gba::memory::Memory;
+
+///Builds a small homebrew-like ROM: entry branch, title and codes but no logo nor checksum
+fn homebrew_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x200];
+    //b 0x080000C0
+    rom[0..4].copy_from_slice(&0xEA00_002E_u32.to_le_bytes());
+    rom[0xA0..0xA8].copy_from_slice(b"RUSTYGBA");
+    rom[0xAC..0xB0].copy_from_slice(b"ARGE");
+    rom[0xB0..0xB2].copy_from_slice(b"01");
+    rom[0xBC] = 2;
+    rom
+}
+
+#[cfg(test)]
+#[test]
+fn parse_header() {
+    let cart = Cartridge::from_bytes(homebrew_rom()).unwrap();
+    assert_eq!(cart.header.title, "RUSTYGBA");
+    assert_eq!(cart.header.game_code, "ARGE");
+    assert_eq!(cart.header.maker_code, "01");
+    assert_eq!(cart.header.version, 2);
+    assert_eq!(cart.header.unit_code, 0);
+    assert_eq!(cart.header.entry_point(), 0x0800_00C0);
+}
+
+#[test]
+fn entry_point_backwards() {
+    let mut rom = homebrew_rom();
+    //b -8(branch to itself)
+    rom[0..4].copy_from_slice(&0xEAFF_FFFE_u32.to_le_bytes());
+    let cart = Cartridge::from_bytes(rom).unwrap();
+    assert_eq!(cart.header.entry_point(), 0x0800_0000);
+}
+
+#[test]
+fn broken_header_diagnostics() {
+    let cart = Cartridge::from_bytes(homebrew_rom()).unwrap();
+    let problems = cart.diagnostics();
+    assert_eq!(problems.len(), 3);
+    assert_eq!(problems[0], Diagnostic::LogoMismatch);
+    assert_eq!(problems[1], Diagnostic::FixedValueMismatch(0));
+    assert!(matches!(
+        problems[2],
+        Diagnostic::ChecksumMismatch { found: 0, .. }
+    ));
+}
+
+#[test]
+fn fix_header_like_gbafix() {
+    let mut cart = Cartridge::from_bytes(homebrew_rom()).unwrap();
+    cart.fix_header();
+    assert!(cart.diagnostics().is_empty());
+    assert_eq!(cart.header.logo, NINTENDO_LOGO);
+    assert_eq!(cart.rom[0xB2], 0x96);
+    //all bytes from 0xA0 to 0xBD, plus 0x19, must sum to 0
+    let sum = cart.rom[0xA0..=0xBD]
+        .iter()
+        .fold(0x19u8, |acc, b| acc.wrapping_add(*b));
+    assert_eq!(sum, 0);
+    //the rest of the ROM is untouched
+    assert_eq!(cart.header.title, "RUSTYGBA");
+}
+
+#[test]
+fn rom_size_limits() {
+    assert!(matches!(
+        Cartridge::from_bytes(vec![0; 0x10]),
+        Err(CartridgeError::TooSmall(0x10))
+    ));
+    assert!(matches!(
+        Cartridge::load("does/not/exist.gba"),
+        Err(CartridgeError::Io(_))
+    ));
+}
+
+#[test]
+fn load_into_gamepak_windows() {
+    let cart = Cartridge::from_bytes(homebrew_rom()).unwrap();
+    let mut mem = Memory::default();
+    mem.load_cartridge(&cart);
+    assert_eq!(mem.read_32(0x0800_0000), 0xEA00_002E);
+    assert_eq!(mem.read_8(0x0A00_00A0), b'R');
+    assert_eq!(mem.read_8(0x0C00_00AC), b'A');
+}
+
+#[test]
+fn smaller_rom_replaces_larger() {
+    let mut large = homebrew_rom();
+    large.resize(0x1000, 0xAA);
+    let mut mem = Memory::default();
+    mem.load_cartridge(&Cartridge::from_bytes(large).unwrap());
+    assert_eq!(mem.read_8(0x0800_0800), 0xAA);
+    let mut small = homebrew_rom();
+    small[0xA0..0xA8].copy_from_slice(b"SMALLROM");
+    mem.load_cartridge(&Cartridge::from_bytes(small).unwrap());
+    // nothing of the first ROM is left past the end of the second
+    for window in [0x0800_0000, 0x0A00_0000, 0x0C00_0000] {
+        assert_eq!(mem.read_8(window + 0x800), 0);
+        assert_eq!(mem.read_8(window + 0xFFF), 0);
+    }
+    assert_eq!(mem.cartridge_header().title, "SMALLROM");
+}
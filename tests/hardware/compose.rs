@@ -0,0 +1,229 @@
+use super::line;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::effects;
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG1CNT: u32 = 0x0400_000A;
+const WIN0H: u32 = 0x0400_0040;
+const WIN1H: u32 = 0x0400_0042;
+const WIN0V: u32 = 0x0400_0044;
+const WIN1V: u32 = 0x0400_0046;
+const WININ: u32 = 0x0400_0048;
+const WINOUT: u32 = 0x0400_004A;
+const BLDCNT: u32 = 0x0400_0050;
+const BLDALPHA: u32 = 0x0400_0052;
+const BLDY: u32 = 0x0400_0054;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OAM: u32 = 0x0700_0000;
+const RED: u16 = 0x001F;
+const GREEN: u16 = 0x03E0;
+const BLUE: u16 = 0x7C00;
+
+///Mode 0: BG0(priority 0) is all red, BG1(priority 1) all green, the backdrop is blue.
+///Sprites are disabled, OBJ tile 1 is filled with sprite color 1, white
+fn setup() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BLUE);
+    mem.write_16(PALETTE + 2, RED);
+    mem.write_16(PALETTE + 4, GREEN);
+    mem.write_16(PALETTE + 0x202, 0x7FFF);
+    for i in 0..16 {
+        mem.write_16(VRAM + 32 + i * 2, 0x1111);
+        mem.write_16(VRAM + 64 + i * 2, 0x2222);
+        mem.write_16(VRAM + 0x1_0020 + i * 2, 0x1111);
+    }
+    for i in 0..1024 {
+        mem.write_16(VRAM + 0x4000 + i * 2, 1);
+        mem.write_16(VRAM + 0x4800 + i * 2, 2);
+    }
+    mem.write_16(BG0CNT, 0x0800);
+    mem.write_16(BG1CNT, 0x0901);
+    mem.write_16(DISPCNT, 0x1340);
+    for n in 0..128 {
+        mem.write_16(OAM + n * 8, 0x0200);
+    }
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn win0_rectangle() {
+    let mut mem = setup();
+    mem.write_16(DISPCNT, 0x3340);
+    // x 10 to 19, lines 5 to 14
+    mem.write_16(WIN0H, 0x0A14);
+    mem.write_16(WIN0V, 0x050F);
+    // inside only BG1, outside both
+    mem.write_16(WININ, 0x0002);
+    mem.write_16(WINOUT, 0x0003);
+    let row = line(&mut mem, 5);
+    assert_eq!(
+        (row[9], row[10], row[19], row[20]),
+        (RED, GREEN, GREEN, RED)
+    );
+    assert_eq!(line(&mut mem, 4)[10], RED);
+    assert_eq!(line(&mut mem, 14)[10], GREEN);
+    assert_eq!(line(&mut mem, 15)[10], RED);
+    // nothing enabled outside: the backdrop
+    mem.write_16(WINOUT, 0);
+    assert_eq!(line(&mut mem, 5)[0], BLUE);
+}
+
+#[test]
+fn window_wraparound() {
+    let mut mem = setup();
+    mem.write_16(DISPCNT, 0x3340);
+    mem.write_16(WININ, 0x0002);
+    mem.write_16(WINOUT, 0x0001);
+    // x 200 to 239 then 0 to 19, lines 150 to 159 then 0 to 9
+    mem.write_16(WIN0H, 0xC814);
+    mem.write_16(WIN0V, 0x960A);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[19], row[20]), (GREEN, GREEN, RED));
+    assert_eq!((row[199], row[200], row[239]), (RED, GREEN, GREEN));
+    assert_eq!(line(&mut mem, 155)[0], GREEN);
+    assert_eq!(line(&mut mem, 10)[0], RED);
+    // a right edge past the screen stops at it
+    mem.write_16(WIN0H, 0xE6FA);
+    let row = line(&mut mem, 0);
+    assert_eq!(
+        (row[0], row[229], row[230], row[239]),
+        (RED, RED, GREEN, GREEN)
+    );
+}
+
+#[test]
+fn window_precedence() {
+    let mut mem = setup();
+    // WIN0 and WIN1 overlap on 20 to 29
+    mem.write_16(DISPCNT, 0x7340);
+    mem.write_16(WIN0H, 0x141E);
+    mem.write_16(WIN1H, 0x0A28);
+    mem.write_16(WIN0V, 0x00A0);
+    mem.write_16(WIN1V, 0x00A0);
+    mem.write_16(WININ, 0x0200);
+    mem.write_16(WINOUT, 0x0001);
+    let row = line(&mut mem, 0);
+    assert_eq!(
+        (row[5], row[10], row[20], row[30]),
+        (RED, GREEN, BLUE, GREEN)
+    );
+    // WIN1 alone
+    mem.write_16(DISPCNT, 0x5340);
+    assert_eq!(line(&mut mem, 0)[20], GREEN);
+}
+
+#[test]
+fn obj_window() {
+    let mut mem = setup();
+    // 8x8 OBJ window sprite at (16, 0), and a regular one at (20, 0)
+    mem.write_16(OAM, 0x0800);
+    mem.write_16(OAM + 2, 16);
+    mem.write_16(OAM + 4, 1);
+    mem.write_16(OAM + 8, 0x0000);
+    mem.write_16(OAM + 10, 20);
+    mem.write_16(OAM + 12, 1);
+    mem.write_16(DISPCNT, 0x9340);
+    // outside BG0 and OBJ, OBJ window only BG1
+    mem.write_16(WINOUT, 0x0211);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[15], row[16], row[19]), (RED, GREEN, GREEN));
+    // the regular sprite has priority 0, but OBJ is off inside the OBJ window
+    assert_eq!(
+        (row[23], row[24], row[27], row[28]),
+        (GREEN, 0x7FFF, 0x7FFF, RED)
+    );
+
+    // WIN0 has precedence over the OBJ window
+    mem.write_16(DISPCNT, 0xB340);
+    mem.write_16(WIN0H, 0x0014);
+    mem.write_16(WIN0V, 0x00A0);
+    mem.write_16(WININ, 0x0001);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[16], row[20]), (RED, GREEN));
+}
+
+#[test]
+fn alpha_blending() {
+    let mut mem = setup();
+    // BG0 over BG1, half and half
+    mem.write_16(BLDCNT, 0x0241);
+    mem.write_16(BLDALPHA, 0x0808);
+    assert_eq!(line(&mut mem, 0)[0], 0x01EF);
+    // weights saturate at 16, components at 31
+    mem.write_16(BLDALPHA, 0x1F1F);
+    assert_eq!(line(&mut mem, 0)[0], RED | GREEN);
+    // the backdrop is not a second target: no blending
+    mem.write_16(DISPCNT, 0x1140);
+    assert_eq!(line(&mut mem, 0)[0], RED);
+    mem.write_16(BLDCNT, 0x2041);
+    mem.write_16(BLDALPHA, 0x0808);
+    assert_eq!(line(&mut mem, 0)[0], 0x3C0F);
+}
+
+#[test]
+fn brightness() {
+    let mut mem = setup();
+    // brighten BG0 by 1/2: 31 stays, 0 goes to 15
+    mem.write_16(BLDCNT, 0x0081);
+    mem.write_16(BLDY, 8);
+    assert_eq!(line(&mut mem, 0)[0], 0x3DFF);
+    // darken all the way, BLDY saturates at 16
+    mem.write_16(BLDCNT, 0x00C1);
+    mem.write_16(BLDY, 31);
+    assert_eq!(line(&mut mem, 0)[0], 0);
+    // BG0 not a target
+    mem.write_16(BLDCNT, 0x00C2);
+    assert_eq!(line(&mut mem, 0)[0], RED);
+    // backdrop as a target
+    mem.write_16(DISPCNT, 0x1040);
+    mem.write_16(BLDCNT, 0x00A0);
+    mem.write_16(BLDY, 4);
+    assert_eq!(line(&mut mem, 0)[0], 0x7CE7);
+}
+
+#[test]
+fn window_effects_bit() {
+    let mut mem = setup();
+    mem.write_16(BLDCNT, 0x00C1);
+    mem.write_16(BLDY, 16);
+    mem.write_16(DISPCNT, 0x3340);
+    mem.write_16(WIN0H, 0x000A);
+    mem.write_16(WIN0V, 0x00A0);
+    // effects inside WIN0 only
+    mem.write_16(WININ, 0x0021);
+    mem.write_16(WINOUT, 0x0001);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[9], row[10]), (0, RED));
+}
+
+#[test]
+fn semi_transparent_sprites() {
+    let mut mem = setup();
+    // semi-transparent white sprite over BG0, priority 0
+    mem.write_16(OAM, 0x0400);
+    mem.write_16(OAM + 2, 0);
+    mem.write_16(OAM + 4, 1);
+    mem.write_16(BLDALPHA, 0x0808);
+    // BG0 not a second target: drawn as is
+    assert_eq!(line(&mut mem, 0)[0], 0x7FFF);
+    // blended even though no effect is selected and OBJ is not a first target
+    mem.write_16(BLDCNT, 0x0100);
+    assert_eq!(line(&mut mem, 0)[0], 0x3DFF);
+    // without a second target below, the regular effect applies
+    mem.write_16(BLDCNT, 0x0090);
+    mem.write_16(BLDY, 16);
+    assert_eq!(line(&mut mem, 0)[0], 0x7FFF);
+    mem.write_16(BLDCNT, 0x00D0);
+    assert_eq!(line(&mut mem, 0)[0], 0);
+}
+
+#[test]
+fn blend_formula() {
+    assert_eq!(effects::blend(0x7FFF, 0x7FFF, 16, 16), 0x7FFF);
+    assert_eq!(effects::blend(0x7FFF, 0, 4, 16), 0x1CE7);
+    assert_eq!(effects::blend(0x0010, 0x0010, 12, 4), 0x0010);
+}
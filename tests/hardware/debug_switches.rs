@@ -0,0 +1,175 @@
+use super::{elapse_to, line};
+use arm7tdmi::cpu::MemoryInterface;
+use gba::debug::{AudioSwitches, VideoSwitches};
+use gba::memory::Memory;
+use gba::ppu::{LINE_CYCLES, SCREEN_WIDTH};
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG1CNT: u32 = 0x0400_000A;
+const WIN0H: u32 = 0x0400_0040;
+const WIN0V: u32 = 0x0400_0044;
+const WININ: u32 = 0x0400_0048;
+const WINOUT: u32 = 0x0400_004A;
+const BLDCNT: u32 = 0x0400_0050;
+const BLDY: u32 = 0x0400_0054;
+const SOUNDCNT_H: u32 = 0x0400_0082;
+const SOUNDCNT_X: u32 = 0x0400_0084;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OAM: u32 = 0x0700_0000;
+const RED: u16 = 0x001F;
+const GREEN: u16 = 0x03E0;
+const BLUE: u16 = 0x7C00;
+const WHITE: u16 = 0x7FFF;
+
+///Mode 0: BG0(priority 0) all red over BG1 all green, backdrop blue.
+///A white 8x8 sprite at (0, 0), above both
+fn setup() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BLUE);
+    mem.write_16(PALETTE + 2, RED);
+    mem.write_16(PALETTE + 4, GREEN);
+    mem.write_16(PALETTE + 0x202, WHITE);
+    for i in 0..16 {
+        mem.write_16(VRAM + 32 + i * 2, 0x1111);
+        mem.write_16(VRAM + 64 + i * 2, 0x2222);
+        mem.write_16(VRAM + 0x1_0020 + i * 2, 0x1111);
+    }
+    for i in 0..1024 {
+        mem.write_16(VRAM + 0x4000 + i * 2, 1);
+        mem.write_16(VRAM + 0x4800 + i * 2, 2);
+    }
+    mem.write_16(BG0CNT, 0x0800);
+    mem.write_16(BG1CNT, 0x0901);
+    for n in 1..128 {
+        mem.write_16(OAM + n * 8, 0x0200);
+    }
+    mem.write_16(OAM + 4, 0x0001);
+    mem.write_16(DISPCNT, 0x1340);
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn hidden_layers() {
+    let mut mem = setup();
+    assert_eq!(mem.ppu.switches, VideoSwitches::default());
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[7], row[8]), (WHITE, WHITE, RED));
+    assert!(mem.debug_switch("obj", false));
+    assert_eq!(line(&mut mem, 0)[0], RED);
+    assert!(mem.debug_switch("bg0", false));
+    assert_eq!(line(&mut mem, 0)[0], GREEN);
+    mem.ppu.switches.bg[1] = false;
+    assert_eq!(line(&mut mem, 0)[0], BLUE);
+    // the game still sees its own settings
+    assert_eq!(mem.read_16(DISPCNT), 0x1340);
+    assert!(mem.debug_switch("bg0", true));
+    assert_eq!(line(&mut mem, 0)[0], RED);
+}
+
+#[test]
+fn windows_and_blending() {
+    let mut mem = setup();
+    // WIN0 over x 0-15, lines 0-15, shows only BG1 inside
+    mem.write_16(WIN0H, 0x0010);
+    mem.write_16(WIN0V, 0x0010);
+    mem.write_16(WININ, 0x0002);
+    mem.write_16(DISPCNT, 0x3340);
+    assert_eq!(line(&mut mem, 0)[0], GREEN);
+    assert!(mem.debug_switch("windows", false));
+    assert_eq!(line(&mut mem, 0)[0], WHITE);
+    assert_eq!(line(&mut mem, 0)[8], RED);
+
+    // BG0 darkened by half
+    mem.write_16(BLDCNT, 0x00C1);
+    mem.write_16(BLDY, 8);
+    assert_eq!(line(&mut mem, 0)[8], 0x0010);
+    assert!(mem.debug_switch("blending", false));
+    assert_eq!(line(&mut mem, 0)[8], RED);
+    assert_eq!(mem.read_16(BLDCNT), 0x00C1);
+}
+
+#[test]
+fn hidden_sprites_keep_the_obj_window() {
+    let mut mem = setup();
+    // sprite 0 makes the OBJ window(BG0 only), sprite 1 at x 16 is outside(BG1 and OBJ)
+    mem.write_16(OAM, 0x0800);
+    mem.write_16(OAM + 8, 0x0000);
+    mem.write_16(OAM + 10, 16);
+    mem.write_16(OAM + 12, 0x0001);
+    mem.write_16(WINOUT, 0x0112);
+    mem.write_16(DISPCNT, 0x9340);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8], row[16]), (RED, GREEN, WHITE));
+    assert!(mem.debug_switch("obj", false));
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8], row[16]), (RED, GREEN, GREEN));
+}
+
+#[test]
+fn render_thread_follows_switches() {
+    let mut mem = setup();
+    mem.ppu_set_threaded(true);
+    mem.debug_switch("bg0", false);
+    // lines 0-9 are drawn without BG0
+    elapse_to(&mut mem, 10 * LINE_CYCLES);
+    mem.debug_switch("bg0", true);
+    let vblank = mem.lcd_next_vblank();
+    elapse_to(&mut mem, vblank);
+    assert_eq!(mem.ppu.screen[9 * SCREEN_WIDTH + 8], GREEN);
+    assert_eq!(mem.ppu.screen[10 * SCREEN_WIDTH + 8], RED);
+}
+
+#[test]
+fn debugger_commands() {
+    let mut mem = Memory::default();
+    assert_eq!(
+        mem.debugger_command("switch bg2 off"),
+        Ok("bg2 off\n".to_string())
+    );
+    assert_eq!(
+        mem.debugger_command("  switch psg3   off "),
+        Ok("psg3 off\n".to_string())
+    );
+    assert!(!mem.ppu.switches.bg[2]);
+    assert!(!mem.apu.switches.psg[2]);
+    let listing = mem.debugger_command("switches").unwrap();
+    assert_eq!(listing.lines().count(), 13);
+    assert!(listing.starts_with("bg0 on\nbg1 on\nbg2 off\nbg3 on\nobj on\n"));
+    assert!(listing.contains("psg3 off\npsg4 on\nfifo-a on\n"));
+    assert!(mem.debugger_command("switch bg9 off").is_err());
+    assert!(mem.debugger_command("switch bg0 maybe").is_err());
+    assert!(mem.debugger_command("frobnicate").is_err());
+    assert_eq!(
+        mem.debugger_command("switch bg2 on"),
+        Ok("bg2 on\n".to_string())
+    );
+    assert_eq!(mem.ppu.switches, VideoSwitches::default());
+}
+
+#[test]
+fn muted_fifos() {
+    let mut mem = Memory::default();
+    assert_eq!(mem.apu.switches, AudioSwitches::default());
+    // A at 100% on both sides, B at 50% on the left
+    mem.write_16(SOUNDCNT_X, 0x0080);
+    mem.write_16(SOUNDCNT_H, 0x2304);
+    mem.apu.fifo[0].push(10);
+    mem.apu.fifo[1].push(-50);
+    mem.apu.fifo[0].step();
+    mem.apu.fifo[1].step();
+    assert_eq!(mem.sound_output(), [40 - 100, 40]);
+    assert!(mem.debug_switch("fifo-b", false));
+    assert_eq!(mem.sound_output(), [40, 40]);
+    assert!(mem.debug_switch("fifo-a", false));
+    assert_eq!(mem.sound_output(), [0, 0]);
+    assert_eq!(mem.read_16(SOUNDCNT_H) & 0x3304, 0x2304);
+    for name in ["psg1", "psg4", "fifo-a"] {
+        assert!(mem.debug_switch(name, true));
+    }
+    assert!(!mem.apu.switches.fifo[1]);
+    assert!(!mem.debug_switch("bg4", false));
+    assert!(!mem.debug_switch("", false));
+}
@@ -0,0 +1,201 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::dma::{DmaTiming, FIFO_A};
+use gba::interrupt::Interrupt;
+use gba::memory::Memory;
+
+///Programs a channel through its I/O registers, exactly like a game would
+fn setup(mem: &mut Memory, id: u32, source: u32, dest: u32, count: u16, control: u16) {
+    let base = 0x0400_00B0 + 12 * id;
+    mem.write_32(base, source);
+    mem.write_32(base + 4, dest);
+    mem.write_16(base + 8, count);
+    mem.write_16(base + 10, control);
+}
+
+#[cfg(test)]
+#[test]
+fn immediate_halfword_copy() {
+    let mut mem = Memory::default();
+    for i in 0..4 {
+        mem.write_16(0x0300_0000 + i * 2, 0x1111 * (i as u16 + 1));
+    }
+    mem.take_cycles();
+    setup(&mut mem, 3, 0x0300_0000, 0x0200_0000, 4, 0x8000);
+    assert_eq!(mem.read_16(0x0200_0000), 0x1111);
+    assert_eq!(mem.read_16(0x0200_0006), 0x4444);
+    assert_eq!(mem.read_16(0x0200_0008), 0);
+    //the enable bit clears itself once done
+    assert_eq!(mem.read_16(0x0400_00DE), 0);
+    assert!(!mem.dma.channels[3].enabled());
+}
+
+#[test]
+fn dma_stalls_the_cpu() {
+    let mut mem = Memory::default();
+    setup(&mut mem, 3, 0x0300_0000, 0x0300_1000, 4, 0x0000);
+    mem.take_cycles();
+    mem.write_16(0x0400_00DE, 0x8000);
+    //1 for the register write, 2 internal, 4 units of IWRAM read + IWRAM write
+    assert_eq!(mem.take_cycles(), 1 + 2 + 4 * 2);
+}
+
+#[test]
+fn word_copy_with_decrement_and_fixed() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0300_0000, 0xAAAA_AAAA);
+    mem.write_32(0x0300_0004, 0xBBBB_BBBB);
+    //source decrements, destination increments
+    setup(
+        &mut mem,
+        0,
+        0x0300_0004,
+        0x0300_0100,
+        2,
+        0x8000 | 0x0400 | 0x0080,
+    );
+    assert_eq!(mem.read_32(0x0300_0100), 0xBBBB_BBBB);
+    assert_eq!(mem.read_32(0x0300_0104), 0xAAAA_AAAA);
+    //fixed destination: only the last unit survives
+    setup(
+        &mut mem,
+        1,
+        0x0300_0000,
+        0x0300_0200,
+        2,
+        0x8000 | 0x0400 | 0x0040,
+    );
+    assert_eq!(mem.read_32(0x0300_0200), 0xBBBB_BBBB);
+    assert_eq!(mem.read_32(0x0300_0204), 0);
+}
+
+#[test]
+fn irq_on_completion() {
+    let mut mem = Memory::default();
+    setup(&mut mem, 2, 0x0300_0000, 0x0300_0100, 1, 0x8000 | 0x4000);
+    assert_eq!(mem.interrupt.requested, Interrupt::Dma2.mask());
+}
+
+#[test]
+fn vblank_repeat_reloads_destination() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0300_0000, 0x1234);
+    mem.write_16(0x0300_0002, 0x5678);
+    //repeat, destination increment/reload, VBlank timing
+    setup(
+        &mut mem,
+        0,
+        0x0300_0000,
+        0x0300_0100,
+        1,
+        0x8000 | 0x1000 | 0x0200 | 0x0060,
+    );
+    assert_eq!(mem.read_16(0x0300_0100), 0);
+    mem.dma_trigger(DmaTiming::HBlank);
+    assert_eq!(mem.read_16(0x0300_0100), 0);
+
+    mem.dma_trigger(DmaTiming::VBlank);
+    assert_eq!(mem.read_16(0x0300_0100), 0x1234);
+    //the source keeps going, the destination starts over
+    mem.dma_trigger(DmaTiming::VBlank);
+    assert_eq!(mem.read_16(0x0300_0100), 0x5678);
+    assert_eq!(mem.read_16(0x0300_0102), 0);
+    assert!(mem.dma.channels[0].enabled());
+}
+
+#[test]
+fn count_zero_means_maximum() {
+    let mut mem = Memory::default();
+    setup(&mut mem, 0, 0x0300_0000, 0x0300_0100, 0, 0x8000 | 0x1000);
+    assert_eq!(mem.dma.channels[0].count, 0x4000);
+    setup(&mut mem, 3, 0x0300_0000, 0x0300_0100, 0, 0x8000 | 0x1000);
+    assert_eq!(mem.dma.channels[3].count, 0x1_0000);
+}
+
+#[test]
+fn channel_address_masks() {
+    let mut mem = Memory::default();
+    //DMA0 cannot see the GamePak: the source wraps into internal memory
+    setup(&mut mem, 0, 0x0B00_0000, 0x0300_0100, 1, 0x8000 | 0x1000);
+    assert_eq!(mem.dma.channels[0].source, 0x0300_0000);
+    setup(&mut mem, 1, 0x0B00_0000, 0x0B00_0100, 1, 0x8000 | 0x1000);
+    assert_eq!(mem.dma.channels[1].source, 0x0B00_0000);
+    assert_eq!(mem.dma.channels[1].dest, 0x0300_0100);
+    setup(&mut mem, 3, 0x0300_0000, 0x0D00_0000, 1, 0x8000 | 0x1000);
+    assert_eq!(mem.dma.channels[3].dest, 0x0D00_0000);
+}
+
+#[test]
+fn gamepak_and_unmapped_areas() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0300_0000, 0x1234_5678);
+    //DMA3 to EEPROM: the writes are lost, the transfer completes
+    setup(&mut mem, 3, 0x0300_0000, 0x0D00_0000, 1, 0x8000 | 0x0400);
+    assert!(!mem.dma.channels[3].enabled());
+    assert_eq!(mem.dma.channels[3].dest, 0x0D00_0004);
+    //nothing answers at 0x0100_0000: the last value read comes back
+    setup(&mut mem, 3, 0x0100_0000, 0x0200_0000, 2, 0x8000 | 0x0400);
+    assert_eq!(mem.read_32(0x0200_0000), 0x1234_5678);
+    assert_eq!(mem.read_32(0x0200_0004), 0x1234_5678);
+    assert_eq!(mem.read_32(0x0D00_0000), 0);
+}
+
+#[test]
+fn mirrored_destinations() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0300_0000, 0x7FFF);
+    //VRAM above 96KBytes lands on the OBJ tiles, palette and OAM repeat every KByte
+    for (dest, seen) in [
+        (0x0601_8000, 0x0601_0000),
+        (0x0603_0002, 0x0601_0002),
+        (0x0500_0402, 0x0500_0002),
+        (0x07FF_FC04, 0x0700_0004),
+        (0x0400_0800, 0x0400_0800),
+    ] {
+        setup(&mut mem, 3, 0x0300_0000, dest, 1, 0x8000);
+        if seen != dest {
+            assert_eq!(mem.read_16(seen), 0x7FFF, "{:#X}", dest);
+        }
+    }
+}
+
+#[test]
+fn sound_fifo_transfer() {
+    let mut mem = Memory::default();
+    for i in 0..8 {
+        mem.write_32(0x0300_0000 + i * 4, i);
+    }
+    //special timing, repeat, 16 bit and count 1: all ignored for sound DMA
+    setup(
+        &mut mem,
+        1,
+        0x0300_0000,
+        FIFO_A,
+        1,
+        0x8000 | 0x3000 | 0x0200,
+    );
+    mem.dma_sound_fifo(FIFO_A);
+    assert_eq!(mem.dma.channels[1].source, 0x0300_0010);
+    assert_eq!(mem.dma.channels[1].dest, FIFO_A);
+    mem.dma_sound_fifo(FIFO_A);
+    assert_eq!(mem.dma.channels[1].source, 0x0300_0020);
+    assert!(mem.dma.channels[1].enabled());
+}
+
+#[test]
+fn video_capture_stops_at_line_162() {
+    let mut mem = Memory::default();
+    setup(
+        &mut mem,
+        3,
+        0x0300_0000,
+        0x0300_1000,
+        4,
+        0x8000 | 0x3000 | 0x0200,
+    );
+    mem.dma_video_capture(1);
+    assert_eq!(mem.dma.channels[3].source, 0x0300_0000);
+    mem.dma_video_capture(2);
+    assert_eq!(mem.dma.channels[3].source, 0x0300_0008);
+    mem.dma_video_capture(162);
+    assert!(!mem.dma.channels[3].enabled());
+}
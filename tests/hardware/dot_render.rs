@@ -0,0 +1,137 @@
+use super::elapse_to;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::{RenderMode, HDRAW_CYCLES, LINE_CYCLES, SCREEN_WIDTH};
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG0HOFS: u32 = 0x0400_0010;
+const BLDCNT: u32 = 0x0400_0050;
+const BLDY: u32 = 0x0400_0054;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OAM: u32 = 0x0700_0000;
+
+///Scrolled BG0 with 4 different tiles, a rotated sprite, and a fade on BG0
+fn scene(mode: RenderMode) -> Memory {
+    let mut mem = Memory::default();
+    mem.ppu.render_mode = mode;
+    for n in 0..512u32 {
+        mem.write_16(PALETTE + n * 2, (n * 0x47) as u16 & 0x7FFF);
+    }
+    for i in 0..64u32 {
+        mem.write_16(VRAM + 32 + i * 2, (i * 0x1357) as u16);
+        mem.write_16(VRAM + 0x1_0020 + i * 2, (i * 0x2468) as u16);
+    }
+    for i in 0..1024u32 {
+        mem.write_16(
+            VRAM + 0x800 + i * 2,
+            (1 + i % 4) as u16 | ((i % 3) << 12) as u16,
+        );
+    }
+    mem.write_16(BG0CNT, 0x0100);
+    mem.write_16(BG0HOFS, 13);
+    // 16x16 affine sprite at (50, 40), rotated by 45 degrees
+    mem.write_16(OAM, 0x4128);
+    mem.write_16(OAM + 2, 0x4032);
+    mem.write_16(OAM + 4, 1);
+    for (n, param) in [0xB5u16, 0xFF4B, 0xB5, 0xB5].iter().enumerate() {
+        mem.write_16(OAM + n as u32 * 8 + 6, *param);
+    }
+    mem.write_16(BLDCNT, 0x0081);
+    mem.write_16(BLDY, 5);
+    mem.write_16(DISPCNT, 0x1140);
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn static_scene_matches_scanline() {
+    let mut scanline = scene(RenderMode::Scanline);
+    let mut dot = scene(RenderMode::Dot);
+    // the scene is set up while line 0 is drawn: compare the second frame
+    for mem in [&mut scanline, &mut dot] {
+        for _ in 0..2 {
+            let vblank = mem.lcd_next_vblank();
+            elapse_to(mem, vblank);
+        }
+    }
+    assert!(scanline
+        .ppu
+        .screen
+        .iter()
+        .any(|color| *color != scanline.ppu.screen[0]));
+    assert!(scanline.ppu.screen == dot.ppu.screen);
+}
+
+#[test]
+fn mid_line_palette_write() {
+    for mode in [RenderMode::Scanline, RenderMode::Dot] {
+        let mut mem = Memory::default();
+        mem.ppu.render_mode = mode;
+        mem.write_16(PALETTE, 0x001F);
+        // dot 100 of line 10
+        elapse_to(&mut mem, 10 * LINE_CYCLES + 400);
+        mem.write_16(PALETTE, 0x03E0);
+        elapse_to(&mut mem, 11 * LINE_CYCLES);
+
+        let line = &mem.ppu.screen[10 * SCREEN_WIDTH..11 * SCREEN_WIDTH];
+        assert_eq!(line[239], 0x03E0);
+        if mode == RenderMode::Dot {
+            assert_eq!(line[..100], [0x001F; 100]);
+            assert_eq!(line[100], 0x03E0);
+        } else {
+            assert_eq!(line[0], 0x03E0);
+        }
+        // the lines before were not touched
+        assert_eq!(mem.ppu.screen[9 * SCREEN_WIDTH + 239], 0x001F);
+    }
+}
+
+#[test]
+fn mid_line_scroll_write() {
+    let mut mem = scene(RenderMode::Dot);
+    let mut reference = scene(RenderMode::Dot);
+    mem.write_16(DISPCNT, 0x0100);
+    reference.write_16(DISPCNT, 0x0100);
+    reference.write_16(BG0HOFS, 100);
+    elapse_to(&mut mem, 20 * LINE_CYCLES + 480);
+    mem.write_16(BG0HOFS, 100);
+    for mem in [&mut mem, &mut reference] {
+        elapse_to(mem, 21 * LINE_CYCLES);
+    }
+    let line = 20 * SCREEN_WIDTH;
+    // the right half is drawn with the new scroll value
+    assert_eq!(
+        mem.ppu.screen[line + 120..line + 240],
+        reference.ppu.screen[line + 120..line + 240]
+    );
+    assert_ne!(
+        mem.ppu.screen[line..line + 119],
+        reference.ppu.screen[line..line + 119]
+    );
+}
+
+#[test]
+fn video_memory_contention() {
+    for mode in [RenderMode::Scanline, RenderMode::Dot] {
+        let mut mem = Memory::default();
+        mem.ppu.render_mode = mode;
+        let contention = mode == RenderMode::Dot;
+        elapse_to(&mut mem, 100);
+        let start = mem.now();
+        mem.write_16(VRAM, 1);
+        assert_eq!(mem.now() - start, 1 + contention as u64);
+        // free during HBlank
+        elapse_to(&mut mem, HDRAW_CYCLES + 10);
+        let start = mem.now();
+        mem.write_16(VRAM, 1);
+        mem.write_16(OAM, 1);
+        assert_eq!(mem.now() - start, 2);
+        // and during VBlank
+        elapse_to(&mut mem, 160 * LINE_CYCLES + 10);
+        let start = mem.now();
+        mem.read_16(PALETTE);
+        assert_eq!(mem.now() - start, 1);
+    }
+}
@@ -0,0 +1,114 @@
+use super::elapse_to;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::frame::{self, FrameBuffer, FrameBufferError, PixelFormat};
+use gba::ppu::LINE_CYCLES;
+
+const DISPCNT: u32 = 0x0400_0000;
+const VRAM: u32 = 0x0600_0000;
+
+///Mode 3, first dots: pure red, pure green, pure blue, then 0x5A3C(r 28, g 17, b 22)
+fn presented() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(DISPCNT, 0x0403);
+    for (dot, color) in [0x001Fu16, 0x03E0, 0x7C00, 0x5A3C].iter().enumerate() {
+        mem.write_16(VRAM + dot as u32 * 2, *color);
+    }
+    let vblank = mem.lcd_next_vblank();
+    elapse_to(&mut mem, vblank);
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn component_expansion() {
+    assert_eq!(frame::rgb888(0x7FFF), [255, 255, 255]);
+    assert_eq!(frame::rgb888(0x0000), [0, 0, 0]);
+    // r 28, g 17, b 22
+    assert_eq!(frame::rgb888(0x5A3C), [231, 140, 181]);
+}
+
+#[test]
+fn pixel_formats() {
+    let mem = presented();
+    let frame = &mem.ppu.frame_buffer;
+    assert_eq!(&frame.pixels()[..4], &[0x001F, 0x03E0, 0x7C00, 0x5A3C]);
+
+    let rgba = frame.to_bytes(PixelFormat::Rgba8888);
+    assert_eq!(rgba.len(), 240 * 160 * 4);
+    assert_eq!(rgba[..8], [255, 0, 0, 255, 0, 255, 0, 255]);
+    assert_eq!(rgba[12..16], [231, 140, 181, 255]);
+    let bgra = frame.to_bytes(PixelFormat::Bgra8888);
+    assert_eq!(bgra[..8], [0, 0, 255, 255, 0, 255, 0, 255]);
+    assert_eq!(bgra[12..16], [181, 140, 231, 255]);
+    let argb = frame.to_bytes(PixelFormat::Argb8888);
+    assert_eq!(argb[8..16], [255, 0, 0, 255, 255, 231, 140, 181]);
+    let rgb565 = frame.to_bytes(PixelFormat::Rgb565);
+    assert_eq!(rgb565.len(), 240 * 160 * 2);
+    let rgb565: Vec<u16> = rgb565[..8]
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    // green gets 6 bits: 17 becomes 35
+    assert_eq!(
+        rgb565,
+        [0xF800, 0x07E0, 0x001F, (28 << 11) | (35 << 5) | 22]
+    );
+}
+
+#[test]
+fn stride_and_padding() {
+    let mem = presented();
+    let frame = &mem.ppu.frame_buffer;
+    // 2 bytes per pixel, rows of 480 bytes padded to 512
+    let mut out = vec![0xAA; 512 * 159 + 480];
+    frame.convert(PixelFormat::Rgb565, &mut out, 512).unwrap();
+    assert_eq!(out[..2], [0x00, 0xF8]);
+    assert_eq!(out[480..512], [0xAA; 32]);
+    assert_eq!(out[512..514], [0, 0]);
+
+    assert_eq!(
+        frame.convert(PixelFormat::Rgba8888, &mut out, 512),
+        Err(FrameBufferError::StrideTooSmall {
+            stride: 512,
+            needed: 960
+        })
+    );
+    assert_eq!(
+        frame.convert(PixelFormat::Rgb565, &mut out[1..], 512),
+        Err(FrameBufferError::BufferTooSmall {
+            len: 512 * 159 + 479,
+            needed: 512 * 159 + 480
+        })
+    );
+}
+
+#[test]
+fn presented_at_vblank() {
+    let mut mem = Memory::default();
+    mem.write_16(DISPCNT, 0x0403);
+    mem.write_16(VRAM, 0x1111);
+    assert!(!mem.ppu.frame_buffer.take_ready());
+    let vblank = mem.lcd_next_vblank();
+    elapse_to(&mut mem, vblank - 1);
+    assert!(!mem.ppu.frame_buffer.take_ready());
+    elapse_to(&mut mem, vblank);
+    assert!(mem.ppu.frame_buffer.take_ready());
+    assert!(!mem.ppu.frame_buffer.take_ready());
+    assert_eq!(mem.ppu.frame_buffer.frame(), 1);
+    assert_eq!(mem.ppu.frame_buffer.pixels()[0], 0x1111);
+
+    // while the next frame is drawn, the presented one does not change
+    mem.write_16(VRAM, 0x2222);
+    elapse_to(&mut mem, vblank + 228 * LINE_CYCLES - 20 * LINE_CYCLES);
+    assert_eq!(mem.ppu.screen[0], 0x2222);
+    assert_eq!(mem.ppu.frame_buffer.pixels()[0], 0x1111);
+    elapse_to(&mut mem, vblank + 228 * LINE_CYCLES);
+    assert!(mem.ppu.frame_buffer.take_ready());
+    assert_eq!(mem.ppu.frame_buffer.frame(), 2);
+    assert_eq!(mem.ppu.frame_buffer.pixels()[0], 0x2222);
+    assert_eq!(
+        mem.ppu.frame_buffer.pixels().len(),
+        FrameBuffer::WIDTH * FrameBuffer::HEIGHT
+    );
+}
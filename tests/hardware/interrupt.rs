@@ -0,0 +1,96 @@
+use arm7tdmi::cpu::{MemoryInterface, OperatingMode, CPU};
+use gba::interrupt::{Interrupt, InterruptController, BIOS_IF};
+use gba::memory::Memory;
+
+#[cfg(test)]
+#[test]
+fn irq_line_needs_ime_ie_and_if() {
+    let mut ic = InterruptController::default();
+    ic.request(Interrupt::VBlank);
+    assert!(!ic.irq_line());
+    ic.enabled = Interrupt::VBlank.mask();
+    assert!(ic.waiting());
+    assert!(!ic.irq_line());
+    ic.master = true;
+    assert!(ic.irq_line());
+    ic.acknowledge(Interrupt::VBlank.mask());
+    assert!(!ic.irq_line());
+}
+
+#[test]
+fn interrupt_sources_bits() {
+    assert_eq!(Interrupt::VBlank.mask(), 0x0001);
+    assert_eq!(Interrupt::Timer3.mask(), 0x0040);
+    assert_eq!(Interrupt::Dma0.mask(), 0x0100);
+    assert_eq!(Interrupt::GamePak.mask(), 0x2000);
+}
+
+#[test]
+fn registers_reach_controller() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0200, 0xFFFF); //IE
+    mem.write_16(0x0400_0208, 0x0001); //IME
+    assert_eq!(mem.interrupt.enabled, 0x3FFF);
+    assert!(mem.interrupt.master);
+
+    mem.interrupt.request(Interrupt::HBlank);
+    mem.interrupt.request(Interrupt::Keypad);
+    assert_eq!(mem.read_16(0x0400_0202), 0x1002);
+    assert!(mem.irq_line());
+
+    //the CPU cannot raise interrupts by writing IF, only acknowledge them
+    mem.write_16(
+        0x0400_0202,
+        Interrupt::Keypad.mask() | Interrupt::VCount.mask(),
+    );
+    assert_eq!(mem.read_16(0x0400_0202), 0x0002);
+    mem.write_8(0x0400_0202, 0x02);
+    assert!(!mem.irq_line());
+}
+
+#[test]
+fn cpu_takes_irq() {
+    let mut cpu: CPU<Memory> = CPU::new();
+    cpu.psr[0].register = 0x6000_001F; //System, Z and C set
+    cpu.update_operating_mode(false);
+    cpu.registers[15] = 0x0800_0100;
+    cpu.memory.interrupt.enabled = Interrupt::Timer0.mask();
+    cpu.memory.interrupt.master = true;
+
+    assert!(!cpu.check_irq());
+    cpu.memory.interrupt.request(Interrupt::Timer0);
+    assert!(cpu.check_irq());
+
+    assert_eq!(cpu.operating_mode, OperatingMode::IRQ);
+    assert_eq!(cpu.get_register(15), 0x18);
+    assert_eq!(cpu.get_register(14), 0x0800_0104);
+    assert_eq!(cpu.psr[OperatingMode::IRQ].register, 0x6000_001F);
+    assert!(cpu.psr[0].get_i());
+    assert!(!cpu.psr[0].get_t());
+    //the interrupted mode keeps its own LR
+    assert_eq!(cpu.registers[14], 0);
+}
+
+#[test]
+fn cpsr_i_masks_irq() {
+    let mut cpu: CPU<Memory> = CPU::new();
+    cpu.psr[0].register = 0x0000_009F; //System, I set
+    cpu.update_operating_mode(false);
+    cpu.memory.interrupt.enabled = Interrupt::Serial.mask();
+    cpu.memory.interrupt.master = true;
+    cpu.memory.interrupt.request(Interrupt::Serial);
+    assert!(!cpu.check_irq());
+    assert_eq!(cpu.operating_mode, OperatingMode::System);
+}
+
+#[test]
+fn bios_acknowledge_word() {
+    let mut mem = Memory::default();
+    mem.acknowledge_bios_if(Interrupt::VBlank.mask());
+    mem.acknowledge_bios_if(Interrupt::Dma3.mask());
+    assert_eq!(mem.read_16(BIOS_IF), 0x0801);
+    //the BIOS reaches the same word through the IWRAM mirror
+    assert_eq!(mem.read_16(0x03FF_FFF8), 0x0801);
+    mem.write_32(0x03FF_FFFC, 0x0300_1234);
+    assert_eq!(mem.read_32(0x0300_7FFC), 0x0300_1234);
+}
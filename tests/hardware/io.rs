@@ -0,0 +1,74 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::io;
+use gba::memory::Memory;
+
+#[cfg(test)]
+#[test]
+fn lookup_register() {
+    let reg = io::register_at(0x0B2).unwrap();
+    assert_eq!(reg.name, "DMA0SAD");
+    assert_eq!(reg.address(), 0x0400_00B0);
+    assert!(io::register_at(0x05E).is_none());
+    assert_eq!(io::register_by_name("IF").unwrap().offset, io::IF);
+}
+
+#[test]
+fn read_only_register_ignores_writes() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0006, 0x1234); //VCOUNT
+    assert_eq!(mem.read_16(0x0400_0006), 0);
+}
+
+#[test]
+fn write_only_register_reads_zero() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0010, 0xFFFF); //BG0HOFS
+    assert_eq!(mem.read_16(0x0400_0010), 0);
+    assert_eq!(
+        mem.io_stored(io::register_by_name("BG0HOFS").unwrap()),
+        0x01FF
+    );
+}
+
+#[test]
+fn unused_bits_are_masked() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0008, 0xFFFF); //BG0CNT, bit 13 is BG2/BG3 only
+    assert_eq!(mem.read_16(0x0400_0008), 0xDFFF);
+    mem.write_16(0x0400_0048, 0xFFFF); //WININ
+    assert_eq!(mem.read_16(0x0400_0048), 0x3F3F);
+}
+
+#[test]
+fn word_write_spans_two_registers() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0400_0050, 0xFFFF_FFFF); //BLDCNT + BLDALPHA
+    assert_eq!(mem.read_16(0x0400_0050), 0x3FFF);
+    assert_eq!(mem.read_16(0x0400_0052), 0x1F1F);
+}
+
+#[test]
+fn if_write_one_to_clear() {
+    let mut mem = Memory::default();
+    mem.interrupt.requested = 0b0000_0000_0000_0111;
+    //writing 0 does nothing
+    mem.write_16(0x0400_0202, 0);
+    assert_eq!(mem.read_16(0x0400_0202), 0b111);
+    //writing 1 acknowledges only that interrupt
+    mem.write_16(0x0400_0202, 0b010);
+    assert_eq!(mem.read_16(0x0400_0202), 0b101);
+    //byte writes only touch their own byte
+    mem.write_8(0x0400_0203, 0xFF);
+    assert_eq!(mem.read_16(0x0400_0202), 0b101);
+    mem.write_8(0x0400_0202, 0xFF);
+    assert_eq!(mem.read_16(0x0400_0202), 0);
+}
+
+#[test]
+fn unmapped_io_reads_open_bus() {
+    let mut mem = Memory::default();
+    mem.bios[0..4].copy_from_slice(&[0x78, 0x56, 0x34, 0x12]);
+    mem.read_32(0);
+    assert_eq!(mem.read_32(0x0400_005C), 0x1234_5678);
+    assert_eq!(mem.read_8(0x0400_005D), 0x56);
+}
@@ -0,0 +1,121 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::interrupt::Interrupt;
+use gba::keypad::{InputSource, Key, Keypad};
+use gba::memory::Memory;
+
+///Input source holding a fixed set of keys
+#[derive(Default)]
+struct Held {
+    keys: u16,
+    turbo: u16,
+    allow_opposite: bool,
+}
+
+impl InputSource for Held {
+    fn pressed(&mut self) -> u16 {
+        self.keys
+    }
+    fn turbo(&self) -> u16 {
+        self.turbo
+    }
+    fn allow_opposite_directions(&self) -> bool {
+        self.allow_opposite
+    }
+}
+
+fn held(keys: &[Key]) -> Held {
+    Held {
+        keys: keys.iter().fold(0, |mask, key| mask | key.mask()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn keyinput_is_active_low() {
+    let mut mem = Memory::default();
+    assert_eq!(mem.read_16(0x0400_0130), 0x03FF);
+    mem.keypad_poll(&mut held(&[Key::A, Key::Start]));
+    assert_eq!(mem.read_16(0x0400_0130), 0x03FF & !0b1001);
+    mem.keypad_poll(&mut held(&[]));
+    assert_eq!(mem.read_16(0x0400_0130), 0x03FF);
+}
+
+#[test]
+fn keyinput_ignores_writes() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0130, 0);
+    assert_eq!(mem.read_16(0x0400_0130), 0x03FF);
+}
+
+#[test]
+fn or_condition_irq() {
+    let mut mem = Memory::default();
+    //IRQ on A or B
+    mem.write_16(0x0400_0132, 0x4003);
+    mem.keypad_poll(&mut held(&[Key::L]));
+    assert_eq!(mem.interrupt.requested, 0);
+    mem.keypad_poll(&mut held(&[Key::B]));
+    assert_eq!(mem.interrupt.requested, Interrupt::Keypad.mask());
+}
+
+#[test]
+fn and_condition_irq() {
+    let mut mem = Memory::default();
+    //IRQ on A+B+Select+Start, the classic soft reset combo
+    mem.write_16(0x0400_0132, 0xC00F);
+    mem.keypad_poll(&mut held(&[Key::A, Key::B, Key::Start]));
+    assert_eq!(mem.interrupt.requested, 0);
+    mem.keypad_poll(&mut held(&[Key::A, Key::B, Key::Start, Key::Select]));
+    assert_eq!(mem.interrupt.requested, Interrupt::Keypad.mask());
+}
+
+#[test]
+fn irq_disabled_in_keycnt() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0132, 0x0003);
+    mem.keypad_poll(&mut held(&[Key::A]));
+    assert_eq!(mem.interrupt.requested, 0);
+}
+
+#[test]
+fn keycnt_write_checks_held_keys() {
+    let mut mem = Memory::default();
+    mem.keypad_poll(&mut held(&[Key::R]));
+    mem.write_16(0x0400_0132, 0x4000 | Key::R.mask());
+    assert_eq!(mem.interrupt.requested, Interrupt::Keypad.mask());
+    assert_eq!(mem.read_16(0x0400_0132), 0x4100);
+}
+
+#[test]
+fn opposite_directions_are_rejected() {
+    let mut keypad = Keypad::default();
+    keypad.poll(&mut held(&[Key::Left, Key::Right, Key::Up, Key::A]));
+    assert_eq!(keypad.pressed, Key::Up.mask() | Key::A.mask());
+    keypad.poll(&mut held(&[Key::Up, Key::Down]));
+    assert_eq!(keypad.pressed, 0);
+}
+
+#[test]
+fn opposite_directions_can_be_allowed() {
+    let mut keypad = Keypad::default();
+    let mut source = held(&[Key::Left, Key::Right]);
+    source.allow_opposite = true;
+    keypad.poll(&mut source);
+    assert_eq!(keypad.pressed, Key::Left.mask() | Key::Right.mask());
+}
+
+#[test]
+fn turbo_toggles_every_period() {
+    let mut keypad = Keypad::default();
+    let mut source = held(&[Key::A, Key::B]);
+    source.turbo = Key::A.mask();
+    let mut a = Vec::new();
+    for _ in 0..8 {
+        keypad.poll(&mut source);
+        a.push(keypad.pressed & Key::A.mask() != 0);
+        //B is held normally
+        assert!(keypad.pressed & Key::B.mask() != 0);
+    }
+    assert_eq!(a, [true, true, false, false, true, true, false, false]);
+}
@@ -0,0 +1,173 @@
+use super::elapse_to;
+use arm7tdmi::cpu::{MemoryInterface, CPU};
+use gba::interrupt::Interrupt;
+use gba::memory::Memory;
+use gba::ppu::{FRAME_CYCLES, HDRAW_CYCLES, LINE_CYCLES};
+use gba::scheduler;
+
+const DISPSTAT: u32 = 0x0400_0004;
+const VCOUNT: u32 = 0x0400_0006;
+
+///DISPSTAT flags, read without spending cycles
+fn flags(mem: &Memory) -> u16 {
+    mem.io_stored(gba::io::register_by_name("DISPSTAT").unwrap()) as u16 & 7
+}
+
+#[cfg(test)]
+#[test]
+fn vcount_follows_the_lines() {
+    let mut mem = Memory::default();
+    assert_eq!(mem.ppu.vcount, 0);
+    elapse_to(&mut mem, LINE_CYCLES - 1);
+    assert_eq!(mem.ppu.vcount, 0);
+    elapse_to(&mut mem, LINE_CYCLES);
+    assert_eq!(mem.read_16(VCOUNT), 1);
+    elapse_to(&mut mem, LINE_CYCLES * 227);
+    assert_eq!(mem.read_16(VCOUNT), 227);
+    elapse_to(&mut mem, FRAME_CYCLES);
+    assert_eq!(mem.read_16(VCOUNT), 0);
+}
+
+#[test]
+fn hblank_flag_inside_the_line() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, HDRAW_CYCLES - 1);
+    assert_eq!(flags(&mem) & 3, 0);
+    elapse_to(&mut mem, HDRAW_CYCLES);
+    assert_eq!(flags(&mem) & 3, 0b10);
+    elapse_to(&mut mem, LINE_CYCLES);
+    assert_eq!(flags(&mem) & 3, 0);
+}
+
+#[test]
+fn dispstat_write_keeps_hblank() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, HDRAW_CYCLES + 10);
+    assert_eq!(flags(&mem) & 2, 2);
+    //e.g. from the HBlank handler: enable the VCount match on line 0
+    mem.write_16(DISPSTAT, 0x0020);
+    assert_eq!(flags(&mem), 0b110);
+    mem.write_16(DISPSTAT, 0x0520);
+    assert_eq!(flags(&mem), 0b010);
+    elapse_to(&mut mem, LINE_CYCLES);
+    assert_eq!(flags(&mem), 0);
+}
+
+#[test]
+fn vblank_flag_on_lines_160_to_226() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, LINE_CYCLES * 159);
+    assert_eq!(flags(&mem) & 1, 0);
+    elapse_to(&mut mem, LINE_CYCLES * 160);
+    assert_eq!(flags(&mem) & 1, 1);
+    assert_eq!(mem.ppu.frame, 1);
+    elapse_to(&mut mem, LINE_CYCLES * 226);
+    assert_eq!(flags(&mem) & 1, 1);
+    elapse_to(&mut mem, LINE_CYCLES * 227);
+    assert_eq!(flags(&mem) & 1, 0);
+}
+
+#[test]
+fn status_flags_are_read_only() {
+    let mut mem = Memory::default();
+    mem.write_16(DISPSTAT, 0xFFFF);
+    assert_eq!(mem.read_16(DISPSTAT), 0xFF38);
+}
+
+#[test]
+fn interrupts_need_dispstat_enable() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, FRAME_CYCLES);
+    assert_eq!(mem.interrupt.requested, 0);
+    mem.write_16(DISPSTAT, 0x0018);
+    elapse_to(&mut mem, FRAME_CYCLES + HDRAW_CYCLES);
+    assert_eq!(mem.interrupt.requested, Interrupt::HBlank.mask());
+    elapse_to(&mut mem, FRAME_CYCLES + LINE_CYCLES * 160);
+    assert_eq!(
+        mem.interrupt.requested,
+        Interrupt::HBlank.mask() | Interrupt::VBlank.mask()
+    );
+}
+
+#[test]
+fn hblank_irq_also_during_vblank() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, LINE_CYCLES * 200);
+    mem.write_16(DISPSTAT, 0x0010);
+    elapse_to(&mut mem, LINE_CYCLES * 200 + HDRAW_CYCLES);
+    assert_eq!(mem.interrupt.requested, Interrupt::HBlank.mask());
+}
+
+#[test]
+fn vcount_match() {
+    let mut mem = Memory::default();
+    //LYC = 100, IRQ enabled
+    mem.write_16(DISPSTAT, 0x6420);
+    elapse_to(&mut mem, LINE_CYCLES * 99);
+    assert_eq!(flags(&mem) & 4, 0);
+    assert_eq!(mem.interrupt.requested, 0);
+    elapse_to(&mut mem, LINE_CYCLES * 100);
+    assert_eq!(flags(&mem) & 4, 4);
+    assert_eq!(mem.interrupt.requested, Interrupt::VCount.mask());
+    elapse_to(&mut mem, LINE_CYCLES * 101);
+    assert_eq!(flags(&mem) & 4, 0);
+}
+
+#[test]
+fn lyc_write_updates_the_flag() {
+    let mut mem = Memory::default();
+    elapse_to(&mut mem, LINE_CYCLES * 5);
+    mem.write_16(DISPSTAT, 0x0500);
+    assert_eq!(mem.read_16(DISPSTAT) & 4, 4);
+}
+
+#[test]
+fn hblank_dma_only_on_visible_lines() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0300_0000, 0x1234_5678);
+    //DMA0, IWRAM to IWRAM, 1 word, fixed addresses, repeat, HBlank
+    mem.write_32(0x0400_00B0, 0x0300_0000);
+    mem.write_32(0x0400_00B4, 0x0300_0100);
+    mem.write_16(0x0400_00B8, 1);
+    mem.write_16(0x0400_00BA, 0xA540);
+    mem.write_16(0x0400_0200, Interrupt::Dma0.mask());
+    elapse_to(&mut mem, LINE_CYCLES * 160 + HDRAW_CYCLES);
+    assert_eq!(mem.read_32(0x0300_0100), 0x1234_5678);
+    mem.interrupt.acknowledge(0xFFFF);
+    //no DMA while in VBlank
+    mem.write_32(0x0300_0100, 0);
+    mem.take_cycles();
+    elapse_to(&mut mem, LINE_CYCLES * 161 + HDRAW_CYCLES);
+    assert_eq!(mem.read_32(0x0300_0100), 0);
+}
+
+#[test]
+fn vblank_dma() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0300_0000, 0xCAFE_BABE);
+    mem.write_32(0x0400_00D4, 0x0300_0000);
+    mem.write_32(0x0400_00D8, 0x0300_0200);
+    mem.write_16(0x0400_00DC, 1);
+    mem.write_16(0x0400_00DE, 0x9400);
+    elapse_to(&mut mem, LINE_CYCLES * 159);
+    assert_eq!(mem.read_32(0x0300_0200), 0);
+    elapse_to(&mut mem, LINE_CYCLES * 161);
+    assert_eq!(mem.read_32(0x0300_0200), 0xCAFE_BABE);
+}
+
+#[test]
+fn run_frame_stops_at_vblank() {
+    let mut cpu: CPU<Memory> = CPU::new();
+    cpu.memory
+        .init_bios([0x00, 0x00, 0xA0, 0xE1].repeat(0x1000));
+    cpu.fill_pipeline();
+    //halted with nothing to wake it up, the frames go by without executing anything
+    cpu.memory.write_8(0x0400_0301, 0);
+    scheduler::run_frame(&mut cpu);
+    assert_eq!(cpu.memory.ppu.vcount, 160);
+    assert_eq!(cpu.memory.ppu.frame, 1);
+    let start = cpu.memory.ppu.line_start;
+    scheduler::run_frame(&mut cpu);
+    assert_eq!(cpu.memory.ppu.frame, 2);
+    assert_eq!(cpu.memory.ppu.line_start - start, FRAME_CYCLES);
+}
@@ -0,0 +1,45 @@
+pub mod affine_bg;
+pub mod bitmap;
+pub mod cartridge;
+pub mod compose;
+pub mod debug_switches;
+pub mod dma;
+pub mod dot_render;
+pub mod frame;
+pub mod interrupt;
+pub mod io;
+pub mod keypad;
+pub mod lcd;
+pub mod mosaic;
+pub mod obj;
+pub mod postprocess;
+pub mod power;
+pub mod psg;
+pub mod scheduler;
+pub mod screenshot;
+pub mod text_bg;
+pub mod threaded;
+pub mod timer;
+pub mod upscale;
+pub mod viewer;
+pub mod waitstate;
+
+use arm7tdmi::cpu::MemoryInterface;
+use gba::io::register_by_name;
+use gba::memory::Memory;
+use gba::ppu::render::{self, Line};
+use gba::ppu::SCREEN_WIDTH;
+
+///Moves the clock to the given time since power on, handling the events due on the way
+pub fn elapse_to(mem: &mut Memory, time: u64) {
+    mem.stall((time - mem.now()) as u32);
+    mem.take_cycles();
+}
+
+///Draws a screen line from the current registers and video memory, as the PPU would on line y
+pub fn line(mem: &mut Memory, y: u16) -> Line {
+    mem.io_store(register_by_name("VCOUNT").unwrap(), y as u32);
+    let mut line = [0; SCREEN_WIDTH];
+    render::render_line(&mem.video_memory(), &mem.ppu.affine, &mut line);
+    line
+}
@@ -0,0 +1,158 @@
+use super::line;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::mosaic::Mosaic;
+use gba::ppu::AffineRef;
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG2CNT: u32 = 0x0400_000C;
+const BG2PD: u32 = 0x0400_0026;
+const MOSAIC: u32 = 0x0400_004C;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OBJ_PALETTE: u32 = 0x0500_0200;
+const OAM: u32 = 0x0700_0000;
+
+///Mode 3 with BG2 on: dot (x, y) has color y * 240 + x, for the first 128 lines
+fn bitmap() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(DISPCNT, 0x0403);
+    for dot in 0..240 * 128u32 {
+        mem.write_16(VRAM + dot * 2, dot as u16);
+    }
+    mem
+}
+
+///Mode 0 with only OBJ on, 1D mapping, every sprite disabled but sprite 0: 8x8 at (3, 5), tile 1.
+///Entry n of the sprite palette is color 0x100 + n
+fn sprites() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(DISPCNT, 0x1040);
+    for n in 1..16u32 {
+        mem.write_16(OBJ_PALETTE + n * 2, 0x100 + n as u16);
+    }
+    for n in 1..128 {
+        mem.write_16(OAM + n * 8, 0x0200);
+    }
+    mem.write_16(OAM, 0x1005);
+    mem.write_16(OAM + 2, 3);
+    mem.write_16(OAM + 4, 1);
+    mem
+}
+
+#[cfg(test)]
+#[test]
+fn block_sizes() {
+    let mut mem = Memory::default();
+    mem.write_16(MOSAIC, 0xF320);
+    let mosaic = Mosaic::new(&mem.video_memory());
+    assert_eq!(
+        mosaic,
+        Mosaic {
+            bg_width: 1,
+            bg_height: 3,
+            obj_width: 4,
+            obj_height: 16
+        }
+    );
+    assert_eq!(mosaic.bg_line(8), 6);
+    assert_eq!(mosaic.obj_line(40), 32);
+}
+
+#[test]
+fn bitmap_background() {
+    let mut mem = bitmap();
+    mem.ppu.affine[0] = AffineRef { x: 0, y: 7 << 8 };
+    // 4x3 blocks
+    mem.write_16(MOSAIC, 0x0023);
+    let row = line(&mut mem, 7);
+    assert_eq!((row[5], row[239]), (7 * 240 + 5, 7 * 240 + 239));
+    // the reference point has moved on line 7: the block rewinds it to line 6
+    mem.write_16(BG2CNT, 0x0040);
+    let row = line(&mut mem, 7);
+    assert_eq!(
+        (row[3], row[4], row[7]),
+        (6 * 240, 6 * 240 + 4, 6 * 240 + 4)
+    );
+    assert_eq!(row[239], 6 * 240 + 236);
+}
+
+#[test]
+fn affine_vertical_blocks() {
+    let mut mem = bitmap();
+    // twice as tall: each line moves the reference point 2 rows down
+    mem.write_16(BG2PD, 0x0200);
+    mem.write_16(BG2CNT, 0x0040);
+    mem.write_16(MOSAIC, 0x0020);
+    mem.ppu.affine[0] = AffineRef { x: 0, y: 14 << 8 };
+    assert_eq!(line(&mut mem, 7)[0], 12 * 240);
+    mem.ppu.affine[0] = AffineRef { x: 0, y: 12 << 8 };
+    assert_eq!(line(&mut mem, 6)[0], 12 * 240);
+}
+
+#[test]
+fn text_background() {
+    let mut mem = Memory::default();
+    // map row 0 uses tile 1, row 1 tile 2, each filled with its own color
+    mem.write_16(PALETTE + 2, 0x0011);
+    mem.write_16(PALETTE + 4, 0x0022);
+    for i in 0..16 {
+        mem.write_16(VRAM + 32 + i * 2, 0x1111);
+        mem.write_16(VRAM + 64 + i * 2, 0x2222);
+    }
+    for i in 0..32 {
+        mem.write_16(VRAM + 0x800 + i * 2, 1);
+        mem.write_16(VRAM + 0x840 + i * 2, 2);
+    }
+    mem.write_16(BG0CNT, 0x0140);
+    mem.write_16(DISPCNT, 0x0100);
+    // 16 lines high blocks
+    mem.write_16(MOSAIC, 0x00F0);
+    assert_eq!(line(&mut mem, 9)[0], 0x0011);
+    assert_eq!(line(&mut mem, 15)[0], 0x0011);
+    mem.write_16(BG0CNT, 0x0100);
+    assert_eq!(line(&mut mem, 9)[0], 0x0022);
+}
+
+#[test]
+fn sprite_horizontal_blocks() {
+    let mut mem = sprites();
+    // dot x of every row has index x + 1
+    for row in 0..8 {
+        mem.write_16(VRAM + 0x1_0020 + row * 4, 0x4321);
+        mem.write_16(VRAM + 0x1_0020 + row * 4 + 2, 0x8765);
+    }
+    let row = line(&mut mem, 5);
+    assert_eq!((row[3], row[4], row[10]), (0x101, 0x102, 0x108));
+    // 4 dots wide blocks, starting on the screen's left edge: the first block starts before
+    // the sprite and shows its first dot
+    mem.write_16(MOSAIC, 0x0300);
+    let row = line(&mut mem, 5);
+    assert_eq!((row[3], row[4], row[7]), (0x101, 0x102, 0x102));
+    assert_eq!((row[8], row[10], row[11]), (0x106, 0x106, 0));
+    // sprites without the mosaic bit ignore it
+    mem.write_16(OAM, 0x0005);
+    assert_eq!(line(&mut mem, 5)[7], 0x105);
+}
+
+#[test]
+fn sprite_vertical_blocks() {
+    let mut mem = sprites();
+    // row r has index r + 1
+    for row in 0..8u32 {
+        mem.write_16(VRAM + 0x1_0020 + row * 4, (row as u16 + 1) * 0x1111);
+        mem.write_16(VRAM + 0x1_0020 + row * 4 + 2, (row as u16 + 1) * 0x1111);
+    }
+    // 4 lines high blocks: lines 4-7, 8-11, 12-15
+    mem.write_16(MOSAIC, 0x3000);
+    // the block of lines 5 to 7 starts above the sprite: its first row repeats
+    assert_eq!(line(&mut mem, 5)[3], 0x101);
+    assert_eq!(line(&mut mem, 7)[3], 0x101);
+    // line 8 is row 3
+    assert_eq!(line(&mut mem, 8)[3], 0x104);
+    assert_eq!(line(&mut mem, 11)[3], 0x104);
+    assert_eq!(line(&mut mem, 12)[3], 0x108);
+    // the sprite still ends on its last line
+    assert_eq!(line(&mut mem, 13)[3], 0);
+}
@@ -0,0 +1,266 @@
+use super::line;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::io;
+use gba::memory::Memory;
+use gba::ppu::obj::{self, ObjMode, Sprite};
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const VRAM: u32 = 0x0600_0000;
+const OBJ_VRAM: u32 = 0x0601_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OBJ_PALETTE: u32 = 0x0500_0200;
+const OAM: u32 = 0x0700_0000;
+const BACKDROP: u16 = 0x1234;
+
+///Mode 0 with only OBJ on, 1D mapping. Every sprite starts disabled.<br>
+///Entry n of the sprite palette is color 0x100 + n
+fn setup() -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BACKDROP);
+    mem.write_16(DISPCNT, 0x1040);
+    for n in 1..256u32 {
+        mem.write_16(OBJ_PALETTE + n * 2, 0x100 + n as u16);
+    }
+    for n in 0..128 {
+        sprite(&mut mem, n, 0x0200, 0, 0);
+    }
+    mem
+}
+
+fn sprite(mem: &mut Memory, n: u32, attr0: u16, attr1: u16, attr2: u16) {
+    mem.write_16(OAM + n * 8, attr0);
+    mem.write_16(OAM + n * 8 + 2, attr1);
+    mem.write_16(OAM + n * 8 + 4, attr2);
+}
+
+///Fills a 4bpp tile(32 bytes) with a single palette index
+fn fill_tile(mem: &mut Memory, tile: u32, index: u16) {
+    for i in 0..16 {
+        mem.write_16(OBJ_VRAM + tile * 32 + i * 2, index * 0x1111);
+    }
+}
+
+fn affine_group(mem: &mut Memory, group: u32, params: [i16; 4]) {
+    for (n, param) in params.iter().enumerate() {
+        mem.write_16(OAM + (group * 4 + n as u32) * 8 + 6, *param as u16);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn attributes() {
+    // vertical, size 2: 16x32, affine with double size, 256 colors, semi-transparent
+    let sprite = Sprite::parse(3, [0xA7F0, 0xBFF8, 0xB123]);
+    assert_eq!(sprite.y, 0xF0);
+    assert_eq!(sprite.x, -8);
+    assert!(sprite.affine && sprite.double_size && !sprite.disabled);
+    assert_eq!(sprite.mode, ObjMode::SemiTransparent);
+    assert!(sprite.bpp8);
+    assert_eq!((sprite.width, sprite.height), (16, 32));
+    assert_eq!(sprite.bounds(), (32, 64));
+    assert_eq!(sprite.affine_group, 31);
+    // no flips on affine sprites, those bits select the group
+    assert!(!sprite.hflip && !sprite.vflip);
+    assert_eq!(
+        (sprite.tile, sprite.priority, sprite.palette),
+        (0x123, 0, 0xB)
+    );
+    assert_eq!(sprite.cycles(), 10 + 2 * 32);
+}
+
+#[test]
+fn regular_sprite() {
+    let mut mem = setup();
+    fill_tile(&mut mem, 1, 3);
+    // 8x8 at (10, 5), tile 1, palette bank 2
+    sprite(&mut mem, 0, 5, 10, 0x2001);
+    assert_eq!(line(&mut mem, 4)[10], BACKDROP);
+    let row = line(&mut mem, 5);
+    assert_eq!(row[9], BACKDROP);
+    assert_eq!(row[10], 0x100 + 0x23);
+    assert_eq!(row[17], 0x100 + 0x23);
+    assert_eq!(row[18], BACKDROP);
+    assert_eq!(line(&mut mem, 12)[10], 0x100 + 0x23);
+    assert_eq!(line(&mut mem, 13)[10], BACKDROP);
+
+    // disabled
+    sprite(&mut mem, 0, 0x0205, 10, 0x2001);
+    assert_eq!(line(&mut mem, 5)[10], BACKDROP);
+}
+
+#[test]
+fn flips_and_mapping() {
+    let mut mem = setup();
+    // 16x16 sprite: tiles 4, 5 on top, then 6, 7 in 1D or 36, 37 in 2D
+    for (tile, index) in [(4, 1), (5, 2), (6, 3), (7, 4), (36, 5), (37, 6)] {
+        fill_tile(&mut mem, tile, index);
+    }
+    sprite(&mut mem, 0, 0, 0x4000, 4);
+    let row = line(&mut mem, 8);
+    assert_eq!((row[0], row[8]), (0x103, 0x104));
+    // horizontal flip
+    sprite(&mut mem, 0, 0, 0x5000, 4);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8]), (0x102, 0x101));
+    // vertical flip
+    sprite(&mut mem, 0, 0, 0x6000, 4);
+    assert_eq!(line(&mut mem, 0)[0], 0x103);
+
+    // 2D mapping
+    mem.write_16(DISPCNT, 0x1000);
+    sprite(&mut mem, 0, 0, 0x4000, 4);
+    let row = line(&mut mem, 8);
+    assert_eq!((row[0], row[8]), (0x105, 0x106));
+}
+
+#[test]
+fn colors_256() {
+    let mut mem = setup();
+    // tile 2 in 256 colors units: bytes 64 to 127, dot (x, y) has index 8 * y + x + 1
+    for i in 0..32u32 {
+        let a = (i * 2 + 1) as u16;
+        mem.write_16(OBJ_VRAM + 64 + i * 2, a | (a + 1) << 8);
+    }
+    sprite(&mut mem, 0, 0x2000, 0, 0xF002);
+    let row = line(&mut mem, 3);
+    // the palette bank is ignored
+    assert_eq!(row[0], 0x100 + 25);
+    assert_eq!(row[7], 0x100 + 32);
+}
+
+#[test]
+fn priorities() {
+    let mut mem = setup();
+    fill_tile(&mut mem, 1, 1);
+    fill_tile(&mut mem, 2, 2);
+    // sprite 1 has a lower priority value than sprite 0
+    sprite(&mut mem, 0, 0, 0, 0x0801);
+    sprite(&mut mem, 1, 0, 4, 0x0402);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[4], row[8]), (0x101, 0x102, 0x102));
+    // same priority: the lower OAM index wins
+    sprite(&mut mem, 1, 0, 4, 0x0802);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[4], row[8]), (0x101, 0x102));
+
+    // BG0 at priority 2 is all color 0x7C00: the sprites above, with priority 2 and 1, win
+    mem.write_16(PALETTE + 2, 0x7C00);
+    for i in 0..16 {
+        mem.write_16(VRAM + 32 + i * 2, 0x1111);
+    }
+    for i in 0..1024 {
+        mem.write_16(VRAM + 0x800 + i * 2, 1);
+    }
+    mem.write_16(BG0CNT, 0x0102);
+    mem.write_16(DISPCNT, 0x1140);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8], row[12]), (0x101, 0x102, 0x7C00));
+    // at priority 1, BG0 hides the priority 2 sprite
+    mem.write_16(BG0CNT, 0x0101);
+    sprite(&mut mem, 1, 0, 4, 0x0402);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8]), (0x7C00, 0x102));
+}
+
+#[test]
+fn vertical_wraparound() {
+    let mut mem = setup();
+    fill_tile(&mut mem, 1, 1);
+    fill_tile(&mut mem, 2, 2);
+    // 8x16 at y = 250: lines 250 to 255, then 0 to 9
+    sprite(&mut mem, 0, 0x80FA, 0, 1);
+    assert_eq!(line(&mut mem, 1)[0], 0x101);
+    assert_eq!(line(&mut mem, 2)[0], 0x102);
+    assert_eq!(line(&mut mem, 9)[0], 0x102);
+    assert_eq!(line(&mut mem, 10)[0], BACKDROP);
+}
+
+#[test]
+fn affine_sprites() {
+    let mut mem = setup();
+    // 8x8 sprite, left half index 1, right half index 2
+    for i in 0..8 {
+        mem.write_16(OBJ_VRAM + 32 + i * 4, 0x1111);
+        mem.write_16(OBJ_VRAM + 32 + i * 4 + 2, 0x2222);
+    }
+    affine_group(&mut mem, 5, [0x100, 0, 0, 0x100]);
+    sprite(&mut mem, 0, 0x0100, 5 << 9, 1);
+    let row = line(&mut mem, 0);
+    assert_eq!(
+        (row[0], row[3], row[4], row[8]),
+        (0x101, 0x101, 0x102, BACKDROP)
+    );
+
+    // double size: a 16x16 box with the sprite in its center
+    sprite(&mut mem, 0, 0x0300, 5 << 9, 1);
+    assert_eq!(line(&mut mem, 3)[4], BACKDROP);
+    let row = line(&mut mem, 4);
+    assert_eq!(
+        (row[3], row[4], row[8], row[11], row[12]),
+        (BACKDROP, 0x101, 0x102, 0x102, BACKDROP)
+    );
+
+    // horizontal mirror around the center of the box, dot 8: the sprite moves one dot right.
+    // Then a 2x zoom filling the whole box
+    affine_group(&mut mem, 5, [-0x100, 0, 0, 0x100]);
+    let row = line(&mut mem, 4);
+    assert_eq!((row[4], row[5], row[9]), (BACKDROP, 0x102, 0x101));
+    affine_group(&mut mem, 5, [0x80, 0, 0, 0x80]);
+    let row = line(&mut mem, 0);
+    assert_eq!(
+        (row[0], row[7], row[8], row[15]),
+        (0x101, 0x101, 0x102, 0x102)
+    );
+}
+
+#[test]
+fn line_cycle_budget() {
+    let mut mem = setup();
+    for tile in 1..9 {
+        fill_tile(&mut mem, tile, 1);
+    }
+    // 64x64 sprites, 8 dots apart: 64 cycles each
+    for n in 0..20 {
+        sprite(&mut mem, n, 0, 0xC000 | (n as u16 * 8), 1);
+    }
+    assert_eq!(obj::sprites(&mem.video_memory())[0].cycles(), 64);
+    // 1210 cycles: 18 sprites fit, the following ones are dropped
+    let row = line(&mut mem, 0);
+    assert_eq!(row[17 * 8 + 63], 0x101);
+    assert_eq!(row[18 * 8 + 63], BACKDROP);
+
+    // with a free HBlank: 954 cycles, 14 sprites
+    mem.write_16(DISPCNT, 0x1060);
+    let row = line(&mut mem, 0);
+    assert_eq!(row[13 * 8 + 63], 0x101);
+    assert_eq!(row[14 * 8 + 63], BACKDROP);
+
+    // sprites not on the line cost nothing
+    sprite(&mut mem, 0, 100, 0xC000, 1);
+    let row = line(&mut mem, 0);
+    assert_eq!(row[14 * 8 + 63], 0x101);
+    assert_eq!(row[15 * 8 + 63], BACKDROP);
+}
+
+#[test]
+fn bitmap_modes_and_window() {
+    let mut mem = setup();
+    fill_tile(&mut mem, 1, 1);
+    fill_tile(&mut mem, 512, 2);
+    sprite(&mut mem, 0, 0, 0, 1);
+    sprite(&mut mem, 1, 0, 8, 512);
+    // the lower tiles hold the frame buffer in mode 3
+    mem.write_16(DISPCNT, 0x1043);
+    let row = line(&mut mem, 0);
+    assert_eq!((row[0], row[8]), (BACKDROP, 0x102));
+
+    // OBJ window sprites are not drawn
+    mem.write_16(DISPCNT, 0x1040);
+    sprite(&mut mem, 0, 0x0800, 0, 1);
+    assert_eq!(line(&mut mem, 0)[0], BACKDROP);
+    mem.io_store(io::register_by_name("VCOUNT").unwrap(), 0);
+    let layer = obj::render(&mem.video_memory());
+    assert!(layer.window[0] && !layer.window[8]);
+    assert_eq!(layer.pixels[8], 0x102);
+}
@@ -0,0 +1,100 @@
+use super::elapse_to;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::frame;
+use gba::video::color::{self, ColorCorrection, LcdFilter};
+use gba::video::Image;
+
+const DISPCNT: u32 = 0x0400_0000;
+const VRAM: u32 = 0x0600_0000;
+
+///Mode 3 with the first dot set to color, presented at the next VBlank
+fn present(mem: &mut Memory, color: u16) {
+    mem.write_16(DISPCNT, 0x0403);
+    mem.write_16(VRAM, color);
+    let vblank = mem.lcd_next_vblank();
+    elapse_to(mem, vblank);
+}
+
+#[cfg(test)]
+#[test]
+fn raw_curve() {
+    for color in [0x0000, 0x7FFF, 0x5A3C, 0x001F] {
+        assert_eq!(ColorCorrection::Raw.apply(color), frame::rgb888(color));
+    }
+    for curve in ColorCorrection::ALL {
+        assert_eq!(ColorCorrection::from_name(curve.name()), Some(curve));
+    }
+    assert_eq!(ColorCorrection::from_name("crt"), None);
+    assert_eq!(ColorCorrection::default(), ColorCorrection::Raw);
+}
+
+#[test]
+fn gba_curve() {
+    let gba = ColorCorrection::Gba;
+    assert_eq!(gba.apply(0x0000), [0, 0, 0]);
+    let [r, g, b] = gba.apply(0x7FFF);
+    // white turns slightly warm and dim
+    assert!(r > 245 && g > 230 && b > 230 && g < 245);
+    // components bleed into each other
+    let [r, g, b] = gba.apply(0x001F);
+    assert!(r > 200 && g > 0 && b > 0);
+    // midtones are much darker than stored
+    let [r, _, _] = gba.apply(16);
+    assert!((r as u32) < frame::rgb888(16)[0] as u32 * 2 / 3);
+    assert_eq!(gba.table().len(), 0x8000);
+    assert_eq!(gba.table()[0x5A3C], gba.apply(0x5A3C));
+}
+
+#[test]
+fn gba_sp_curve() {
+    let sp = ColorCorrection::GbaSp;
+    assert_eq!(sp.apply(0x0000), [0, 0, 0]);
+    assert_eq!(sp.apply(0x7FFF), [255, 255, 255]);
+    // less saturated than raw, brighter than the original GBA
+    let [r, g, b] = sp.apply(0x001F);
+    assert!(r < 255 && g > 0 && b > 0);
+    assert!(r > ColorCorrection::Gba.apply(0x001F)[0]);
+    let grey = (16 << 10) | (16 << 5) | 16;
+    assert!(sp.apply(grey)[0] > ColorCorrection::Gba.apply(grey)[0]);
+}
+
+#[test]
+fn corrected_frame() {
+    let mut mem = Memory::default();
+    present(&mut mem, 0x5A3C);
+    let mut filter = LcdFilter::new(ColorCorrection::Gba, 0);
+    let image = filter.process(&mem.ppu.frame_buffer);
+    assert_eq!((image.width, image.height), (240, 160));
+    assert_eq!(image.pixels[0], ColorCorrection::Gba.apply(0x5A3C));
+
+    filter.correction = ColorCorrection::Raw;
+    let image = filter.process(&mem.ppu.frame_buffer);
+    assert_eq!(image, Image::from(&mem.ppu.frame_buffer));
+}
+
+#[test]
+fn ghosting() {
+    assert_eq!(color::mix([200, 100, 0], [0, 0, 100], 50), [100, 50, 50]);
+    assert_eq!(color::mix([200, 100, 0], [0, 0, 100], 0), [200, 100, 0]);
+    assert_eq!(color::mix([200, 100, 0], [0, 0, 100], 25), [150, 75, 25]);
+
+    let mut mem = Memory::default();
+    let mut filter = LcdFilter::new(ColorCorrection::Raw, 50);
+    present(&mut mem, 0x001F);
+    // nothing to mix with on the first frame
+    assert_eq!(filter.process(&mem.ppu.frame_buffer).pixels[0], [255, 0, 0]);
+    present(&mut mem, 0x7C00);
+    assert_eq!(
+        filter.process(&mem.ppu.frame_buffer).pixels[0],
+        [128, 0, 128]
+    );
+    // the previous frame is mixed in before ghosting
+    present(&mut mem, 0x7C00);
+    assert_eq!(filter.process(&mem.ppu.frame_buffer).pixels[0], [0, 0, 255]);
+
+    filter.reset();
+    present(&mut mem, 0x001F);
+    assert_eq!(filter.process(&mem.ppu.frame_buffer).pixels[0], [255, 0, 0]);
+    assert_eq!(LcdFilter::new(ColorCorrection::Raw, 150).ghosting, 100);
+}
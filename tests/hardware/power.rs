@@ -0,0 +1,180 @@
+use arm7tdmi::cpu::{MemoryInterface, OperatingMode, CPU};
+use gba::interrupt::{Interrupt, BIOS_IF};
+use gba::keypad::{InputSource, Key};
+use gba::memory::Memory;
+use gba::power::PowerState;
+use gba::scheduler;
+
+const IE: u32 = 0x0400_0200;
+const HALTCNT: u32 = 0x0400_0301;
+const IME: u32 = 0x0400_0208;
+
+struct PressA;
+
+impl InputSource for PressA {
+    fn pressed(&mut self) -> u16 {
+        Key::A.mask()
+    }
+}
+
+///CPU running `mov r0, r0` from the BIOS, with timer 0 overflowing 0x100 cycles after start
+fn idle_cpu() -> CPU<Memory> {
+    let mut cpu: CPU<Memory> = CPU::new();
+    cpu.memory
+        .init_bios([0x00, 0x00, 0xA0, 0xE1].repeat(0x1000));
+    cpu.memory.write_16(0x0400_0100, 0xFF00);
+    cpu.memory.write_16(0x0400_0102, 0x00C0);
+    cpu.fill_pipeline();
+    cpu
+}
+
+///Idle CPU starting on `swi function` instead of the first `mov r0, r0`
+fn swi_cpu(function: u32) -> CPU<Memory> {
+    let mut cpu = idle_cpu();
+    cpu.memory.write_32(0, 0xEF00_0000 | (function << 16));
+    cpu.fill_pipeline();
+    cpu
+}
+
+#[cfg(test)]
+#[test]
+fn haltcnt_selects_the_mode() {
+    let mut mem = Memory::default();
+    mem.write_8(HALTCNT, 0x00);
+    assert_eq!(mem.power, PowerState::Halt);
+    mem.write_8(HALTCNT, 0x80);
+    assert_eq!(mem.power, PowerState::Stop);
+}
+
+#[test]
+fn halt_wakes_on_enabled_interrupt_even_without_ime() {
+    let mut mem = Memory::default();
+    mem.write_8(HALTCNT, 0x00);
+    mem.interrupt.request(Interrupt::VBlank);
+    assert!(!mem.power_wake());
+    mem.write_16(IE, Interrupt::VBlank.mask());
+    assert!(mem.power_wake());
+    assert_eq!(mem.power, PowerState::Running);
+}
+
+#[test]
+fn stop_ignores_internal_interrupts() {
+    let mut mem = Memory::default();
+    mem.write_16(IE, 0x3FFF);
+    mem.write_8(HALTCNT, 0x80);
+    mem.interrupt.request(Interrupt::Timer0);
+    assert!(!mem.power_wake());
+    mem.interrupt.request(Interrupt::GamePak);
+    assert!(mem.power_wake());
+}
+
+#[test]
+fn keypad_wakes_from_stop() {
+    let mut mem = Memory::default();
+    mem.write_16(IE, Interrupt::Keypad.mask());
+    mem.write_16(0x0400_0132, 0x4000 | Key::A.mask());
+    mem.write_8(HALTCNT, 0x80);
+    assert!(!mem.power_wake());
+    mem.keypad_poll(&mut PressA);
+    assert!(mem.power_wake());
+}
+
+#[test]
+fn halted_cpu_skips_to_the_next_event() {
+    let mut cpu = idle_cpu();
+    cpu.memory.write_16(IE, Interrupt::Timer0.mask());
+    cpu.memory.write_8(HALTCNT, 0x00);
+    let pc = cpu.registers[15];
+    scheduler::run_until(&mut cpu, 0x80);
+    assert_eq!(cpu.registers[15], pc);
+    assert_eq!(cpu.memory.now(), 0x80);
+    assert_eq!(cpu.cycles, 0x80);
+    //the overflow wakes the CPU up, then it runs again
+    scheduler::run_until(&mut cpu, 0x200);
+    assert_eq!(cpu.memory.power, PowerState::Running);
+    assert!(cpu.registers[15] > pc);
+}
+
+#[test]
+fn halted_cpu_without_wake_source_sleeps_through() {
+    let mut cpu = idle_cpu();
+    cpu.memory.write_8(HALTCNT, 0x00);
+    let pc = cpu.registers[15];
+    scheduler::run_until(&mut cpu, 0x10000);
+    //the timer interrupt is requested but not enabled in IE
+    assert_eq!(cpu.memory.interrupt.requested, Interrupt::Timer0.mask());
+    assert_eq!(cpu.registers[15], pc);
+    assert!(cpu.memory.now() >= 0x10000);
+}
+
+#[test]
+fn stopped_system_does_not_advance() {
+    let mut cpu = idle_cpu();
+    cpu.memory.write_8(HALTCNT, 0x80);
+    let now = cpu.memory.now();
+    scheduler::run_until(&mut cpu, 0x1000);
+    assert_eq!(cpu.memory.now(), now);
+    assert_eq!(cpu.memory.interrupt.requested, 0);
+}
+
+#[test]
+fn halt_and_stop_swis() {
+    let mut cpu = swi_cpu(0x02);
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Halt);
+    assert_eq!(cpu.registers[15], 4);
+    cpu.memory.write_16(IE, Interrupt::Timer0.mask());
+    scheduler::run_until(&mut cpu, 0x200);
+    assert_eq!(cpu.memory.power, PowerState::Running);
+    assert!(cpu.registers[15] > 4);
+
+    let mut cpu = swi_cpu(0x03);
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Stop);
+    //other functions enter the BIOS through the SWI vector
+    let mut cpu = swi_cpu(0x06);
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Running);
+    assert_eq!(cpu.operating_mode, OperatingMode::Supervisor);
+    assert_eq!(cpu.registers[15], 0x08);
+    assert_eq!(cpu.get_register(14), 4);
+}
+
+#[test]
+fn intr_wait_swis() {
+    let mut cpu = swi_cpu(0x04);
+    cpu.psr[0].register = 0x0000_009F; //System, I set: the test plays the IRQ handler
+    cpu.update_operating_mode(false);
+    cpu.registers[0] = 1;
+    cpu.registers[1] = Interrupt::Timer0.mask() as u32;
+    cpu.memory.acknowledge_bios_if(Interrupt::Timer0.mask());
+    cpu.step();
+    //the old flag is discarded, IME is turned on and the SWI waits
+    assert_eq!(cpu.memory.power, PowerState::Halt);
+    assert_eq!(cpu.registers[15], 0);
+    assert_eq!(cpu.memory.read_16(BIOS_IF), 0);
+    assert_eq!(cpu.memory.read_16(IME), 1);
+    //woken up by an interrupt the handler did not report: back to sleep
+    cpu.memory.power = PowerState::Running;
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Halt);
+    assert_eq!(cpu.registers[15], 0);
+    //the handler reports it: the flag set while waiting is not discarded
+    cpu.memory
+        .acknowledge_bios_if(Interrupt::Timer0.mask() | Interrupt::VBlank.mask());
+    cpu.memory.power = PowerState::Running;
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Running);
+    assert_eq!(cpu.registers[15], 4);
+    assert_eq!(cpu.memory.read_16(BIOS_IF), Interrupt::VBlank.mask());
+
+    let mut cpu = swi_cpu(0x05);
+    cpu.memory.acknowledge_bios_if(Interrupt::VBlank.mask());
+    cpu.step();
+    assert_eq!(cpu.memory.power, PowerState::Halt);
+    cpu.memory.acknowledge_bios_if(Interrupt::VBlank.mask());
+    cpu.memory.power = PowerState::Running;
+    cpu.step();
+    assert_eq!(cpu.registers[15], 4);
+    assert_eq!(cpu.memory.read_16(BIOS_IF), 0);
+}
@@ -0,0 +1,204 @@
+use super::elapse_to;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::apu::psg::SEQUENCER_PERIOD;
+use gba::io;
+use gba::memory::Memory;
+
+const SOUND1CNT_L: u32 = 0x0400_0060;
+const SOUND1CNT_H: u32 = 0x0400_0062;
+const SOUND1CNT_X: u32 = 0x0400_0064;
+const SOUND2CNT_L: u32 = 0x0400_0068;
+const SOUND2CNT_H: u32 = 0x0400_006C;
+const SOUND3CNT_L: u32 = 0x0400_0070;
+const SOUND3CNT_H: u32 = 0x0400_0072;
+const SOUND3CNT_X: u32 = 0x0400_0074;
+const SOUND4CNT_L: u32 = 0x0400_0078;
+const SOUND4CNT_H: u32 = 0x0400_007C;
+const SOUNDCNT_L: u32 = 0x0400_0080;
+const SOUNDCNT_H: u32 = 0x0400_0082;
+const SOUNDCNT_X: u32 = 0x0400_0084;
+const WAVE_RAM0: u32 = 0x0400_0090;
+
+///Sound circuit on, PSG at 100%, the given channels and master volumes in SOUNDCNT_L
+fn powered(soundcnt_l: u16) -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(SOUNDCNT_X, 0x0080);
+    mem.write_16(SOUNDCNT_H, 0x0002);
+    mem.write_16(SOUNDCNT_L, soundcnt_l);
+    mem
+}
+
+///Runs until the given time, then reads the output so the channels catch up
+fn play_to(mem: &mut Memory, time: u64) {
+    elapse_to(mem, time);
+    mem.sound_output();
+}
+
+#[cfg(test)]
+#[test]
+fn square_duty() {
+    // channel 2 on both sides, volume 15, 50% duty, 128 cycles per step
+    let mut mem = powered(0x2200);
+    mem.write_16(SOUND2CNT_L, 0xF080);
+    mem.write_16(SOUND2CNT_H, 0x8000 | 2040);
+    let start = mem.now();
+    // the 50% waveform is high for steps 7, 0, 1 and 2
+    for (step, level) in [(0, 15), (2, 15), (3, -15), (6, -15), (7, 15), (8, 15)] {
+        play_to(&mut mem, start + step * 128 + 64);
+        assert_eq!(mem.sound_output(), [level, level], "step {}", step);
+    }
+}
+
+#[test]
+fn length_counter() {
+    let mut mem = powered(0);
+    // length 64 - 62 = 2, length enabled
+    mem.write_16(SOUND2CNT_L, 0xF03E);
+    mem.write_16(SOUND2CNT_H, 0xC000);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0x0082);
+    // lengths are clocked on even steps: 0 at the first period, 2 at the third
+    play_to(&mut mem, 2 * SEQUENCER_PERIOD + 1);
+    assert_eq!(mem.apu.psg.square[1].length, 1);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0x0082);
+    play_to(&mut mem, 3 * SEQUENCER_PERIOD + 1);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0x0080);
+    // without length enable, the channel plays on
+    mem.write_16(SOUND2CNT_H, 0x8000);
+    assert_eq!(mem.apu.psg.square[1].length, 64);
+    play_to(&mut mem, 200 * SEQUENCER_PERIOD);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0x0082);
+}
+
+#[test]
+fn envelope() {
+    let mut mem = powered(0);
+    // volume 15, decreasing every envelope tick
+    mem.write_16(SOUND1CNT_H, 0xF100);
+    mem.write_16(SOUND1CNT_X, 0x8000);
+    assert_eq!(mem.apu.psg.square[0].envelope.volume, 15);
+    // envelopes are clocked on step 7, at 64 Hz
+    play_to(&mut mem, 8 * SEQUENCER_PERIOD + 1);
+    assert_eq!(mem.apu.psg.square[0].envelope.volume, 14);
+    play_to(&mut mem, 16 * SEQUENCER_PERIOD + 1);
+    assert_eq!(mem.apu.psg.square[0].envelope.volume, 13);
+    // turning the DAC off stops the channel
+    mem.write_16(SOUND1CNT_H, 0x0000);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0x0080);
+}
+
+#[test]
+fn sweep() {
+    let mut mem = powered(0);
+    let sound1cnt_x = io::register_by_name("SOUND1CNT_X").unwrap();
+    // sweep every 128 Hz tick, up by frequency >> 1
+    mem.write_16(SOUND1CNT_L, 0x0011);
+    mem.write_16(SOUND1CNT_H, 0xF000);
+    mem.write_16(SOUND1CNT_X, 0x8000 | 1024);
+    // the sweep is clocked on steps 2 and 6
+    play_to(&mut mem, 3 * SEQUENCER_PERIOD + 1);
+    assert_eq!(mem.io_stored(sound1cnt_x) & 0x7FF, 1536);
+    // the next frequency would be 2304, the channel stops
+    assert!(!mem.apu.psg.square[0].enabled);
+    // overflowing at once on trigger
+    mem.write_16(SOUND1CNT_X, 0x8000 | 1400);
+    assert!(!mem.apu.psg.square[0].enabled);
+    mem.write_16(SOUND1CNT_X, 0x8000 | 1000);
+    assert!(mem.apu.psg.square[0].enabled);
+}
+
+#[test]
+fn wave_banks() {
+    // channel 3 on both sides
+    let mut mem = powered(0x4400);
+    // bank 1 plays, the CPU sees bank 0
+    mem.write_16(SOUND3CNT_L, 0x0040);
+    mem.write_32(WAVE_RAM0, 0x1234_5678);
+    assert_eq!(mem.apu.psg.wave_ram[0][..4], [0x78, 0x56, 0x34, 0x12]);
+    mem.write_16(SOUND3CNT_L, 0x0000);
+    assert_eq!(mem.read_32(WAVE_RAM0), 0);
+    mem.write_32(WAVE_RAM0, 0xFFFF_FFFF);
+    mem.write_16(SOUND3CNT_L, 0x0040);
+    assert_eq!(mem.read_32(WAVE_RAM0), 0x1234_5678);
+
+    // 64 samples from bank 0 then bank 1, 100% volume, 8 cycles per sample
+    mem.write_16(SOUND3CNT_L, 0x00A0);
+    mem.write_16(SOUND3CNT_H, 0x2000);
+    mem.write_16(SOUND3CNT_X, 0x8000 | 2047);
+    let start = mem.now();
+    // the first sample is the high nibble of the first byte: 7
+    assert_eq!(mem.sound_output(), [-1, -1]);
+    play_to(&mut mem, start + 32 * 8 + 4);
+    assert_eq!(mem.apu.psg.wave.position, 32);
+    assert_eq!(mem.sound_output(), [15, 15]);
+    // 25% volume
+    mem.write_16(SOUND3CNT_H, 0x6000);
+    assert_eq!(mem.sound_output(), [3, 3]);
+    play_to(&mut mem, start + 64 * 8 + 4);
+    assert_eq!(mem.apu.psg.wave.position, 0);
+}
+
+#[test]
+fn noise_lfsr() {
+    let mut mem = powered(0);
+    // 7 bit mode, a shift every 32 cycles
+    mem.write_16(SOUND4CNT_L, 0xF000);
+    mem.write_16(SOUND4CNT_H, 0x8008);
+    assert_eq!(mem.apu.psg.noise.lfsr, 0x7F);
+    let start = mem.now() + 16;
+    play_to(&mut mem, start);
+    let lfsr = mem.apu.psg.noise.lfsr;
+    assert!(lfsr < 0x80);
+    // the 7 bit sequence repeats every 127 shifts
+    play_to(&mut mem, start + 127 * 32);
+    assert_eq!(mem.apu.psg.noise.lfsr, lfsr);
+
+    // the 15 bit one every 32767
+    mem.write_16(SOUND4CNT_H, 0x8000);
+    assert_eq!(mem.apu.psg.noise.lfsr, 0x7FFF);
+    let start = mem.now() + 16;
+    play_to(&mut mem, start);
+    let lfsr = mem.apu.psg.noise.lfsr;
+    play_to(&mut mem, start + 127 * 32);
+    assert_ne!(mem.apu.psg.noise.lfsr, lfsr);
+    play_to(&mut mem, start + 32767 * 32);
+    assert_eq!(mem.apu.psg.noise.lfsr, lfsr);
+}
+
+#[test]
+fn mixing() {
+    // channel 1 right only, channel 2 on both sides, right volume 8, left volume 4
+    let mut mem = powered(0x2337);
+    mem.write_16(SOUND1CNT_H, 0xF080);
+    mem.write_16(SOUND1CNT_X, 0x8000 | 2040);
+    mem.write_16(SOUND2CNT_L, 0x7080);
+    mem.write_16(SOUND2CNT_H, 0x8000 | 2040);
+    assert_eq!(mem.sound_output(), [7 * 4, (15 + 7) * 8]);
+    // PSG at 25%
+    mem.write_16(SOUNDCNT_H, 0x0000);
+    assert_eq!(mem.sound_output(), [7, 44]);
+    mem.write_16(SOUNDCNT_H, 0x0002);
+    assert!(mem.debug_switch("psg2", false));
+    assert_eq!(mem.sound_output(), [0, 15 * 8]);
+}
+
+#[test]
+fn power_off() {
+    let mut mem = powered(0x2200);
+    mem.write_16(SOUND2CNT_L, 0xF080);
+    mem.write_16(SOUND2CNT_H, 0x8000);
+    mem.write_32(WAVE_RAM0, 0x1234_5678);
+    mem.write_16(SOUNDCNT_X, 0x0000);
+    assert_eq!(mem.read_16(SOUNDCNT_X), 0);
+    assert_eq!(mem.read_16(SOUNDCNT_L), 0);
+    assert_eq!(mem.read_16(SOUND2CNT_L), 0);
+    // registers can't be written while off, wave RAM can
+    mem.write_16(SOUND2CNT_L, 0xF080);
+    assert_eq!(mem.read_16(SOUND2CNT_L), 0);
+    assert_eq!(mem.read_32(WAVE_RAM0), 0x1234_5678);
+    // the frame sequencer stops
+    play_to(&mut mem, 10 * SEQUENCER_PERIOD);
+    assert_eq!(mem.apu.psg.step, 0);
+    mem.write_16(SOUNDCNT_X, 0x0080);
+    mem.write_16(SOUND2CNT_L, 0xF080);
+    assert_eq!(mem.read_16(SOUND2CNT_L), 0xF080);
+}
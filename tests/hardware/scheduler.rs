@@ -0,0 +1,87 @@
+use arm7tdmi::cpu::{MemoryInterface, CPU};
+use gba::interrupt::Interrupt;
+use gba::memory::Memory;
+use gba::scheduler::{self, Event, Scheduler};
+
+#[cfg(test)]
+#[test]
+fn events_come_out_in_time_order() {
+    let mut scheduler = Scheduler::default();
+    scheduler.schedule(Event::TimerOverflow(0), 300);
+    scheduler.schedule(Event::TimerOverflow(1), 100);
+    scheduler.schedule(Event::TimerOverflow(2), 200);
+    assert_eq!(scheduler.next_event(), Some(100));
+    assert_eq!(scheduler.pop_due(50), None);
+    assert_eq!(scheduler.pop_due(250), Some((100, Event::TimerOverflow(1))));
+    assert_eq!(scheduler.pop_due(250), Some((200, Event::TimerOverflow(2))));
+    assert_eq!(scheduler.pop_due(250), None);
+}
+
+#[test]
+fn same_time_keeps_schedule_order() {
+    let mut scheduler = Scheduler::default();
+    scheduler.schedule(Event::TimerOverflow(3), 10);
+    scheduler.schedule(Event::TimerOverflow(0), 10);
+    assert_eq!(scheduler.pop_due(10), Some((10, Event::TimerOverflow(3))));
+    assert_eq!(scheduler.pop_due(10), Some((10, Event::TimerOverflow(0))));
+}
+
+#[test]
+fn schedule_in_is_relative_to_now() {
+    let mut scheduler = Scheduler::default();
+    scheduler.now = 1000;
+    scheduler.schedule_in(Event::TimerOverflow(0), 24);
+    assert_eq!(scheduler.time_of(Event::TimerOverflow(0)), Some(1024));
+}
+
+#[test]
+fn cancel_and_reschedule() {
+    let mut scheduler = Scheduler::default();
+    scheduler.schedule(Event::TimerOverflow(0), 10);
+    scheduler.schedule(Event::TimerOverflow(1), 20);
+    scheduler.cancel(Event::TimerOverflow(0));
+    assert_eq!(scheduler.time_of(Event::TimerOverflow(0)), None);
+    assert_eq!(scheduler.next_event(), Some(20));
+    scheduler.reschedule(Event::TimerOverflow(1), 5);
+    scheduler.reschedule(Event::TimerOverflow(2), 7);
+    assert_eq!(scheduler.pop_due(100), Some((5, Event::TimerOverflow(1))));
+    assert_eq!(scheduler.pop_due(100), Some((7, Event::TimerOverflow(2))));
+    assert_eq!(scheduler.pop_due(100), None);
+}
+
+#[test]
+fn timer_writes_move_the_overflow() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0100, 0xFF00);
+    mem.write_16(0x0400_0102, 0x0080);
+    let started = mem.now();
+    assert_eq!(
+        mem.scheduler.time_of(Event::TimerOverflow(0)),
+        Some(started + 0x100)
+    );
+    //switching to F/64 keeps the counter, the remaining ticks get slower
+    mem.write_16(0x0400_0102, 0x0081);
+    let ticks = 0x1_0000 - mem.timers.timers[0].counter_at(mem.now()) as u64;
+    let overflow = mem.scheduler.time_of(Event::TimerOverflow(0)).unwrap();
+    assert!(overflow > mem.now() + (ticks - 1) * 64);
+    //stopping the timer drops the event
+    mem.write_16(0x0400_0102, 0x0000);
+    assert_eq!(mem.scheduler.time_of(Event::TimerOverflow(0)), None);
+}
+
+#[test]
+fn cpu_runs_until_the_target() {
+    let mut cpu: CPU<Memory> = CPU::new();
+    //mov r0, r0
+    cpu.memory
+        .init_bios([0x00, 0x00, 0xA0, 0xE1].repeat(0x1000));
+    cpu.memory.write_16(0x0400_0100, 0xFF00);
+    cpu.memory.write_16(0x0400_0102, 0x00C0);
+    cpu.fill_pipeline();
+    scheduler::run_until(&mut cpu, 0x100);
+    assert_eq!(cpu.memory.interrupt.requested, 0);
+    scheduler::run_until(&mut cpu, 0x110);
+    assert_eq!(cpu.memory.interrupt.requested, Interrupt::Timer0.mask());
+    assert!(cpu.memory.now() >= 0x110);
+    assert_eq!(cpu.cycles, cpu.memory.now());
+}
@@ -0,0 +1,284 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::cartridge::Cartridge;
+use gba::memory::Memory;
+use gba::video::png::{self, Compression};
+use gba::video::scale::Scaler;
+use gba::video::screenshot::{self, ImageFormat, Metadata};
+use gba::video::Image;
+
+const DISPCNT: u32 = 0x0400_0000;
+const VRAM: u32 = 0x0600_0000;
+
+///Reads deflate bits from the least significant bit of each byte
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> u32 {
+        let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+        self.position += 1;
+        bit as u32
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, i| value | (self.bit() << i))
+    }
+
+    ///Huffman codes come from their most significant bit
+    fn code(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |code, _| (code << 1) | self.bit())
+    }
+}
+
+///Inflates the stored and fixed Huffman blocks the encoder writes
+fn inflate(data: &[u8]) -> Vec<u8> {
+    const LENGTH_BASE: [usize; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const DISTANCE_BASE: [usize; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    let mut bits = Bits { data, position: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.read(1);
+        match bits.read(2) {
+            0 => {
+                bits.position = bits.position.div_ceil(8) * 8;
+                let start = bits.position / 8;
+                let len = u16::from_le_bytes([data[start], data[start + 1]]) as usize;
+                let nlen = u16::from_le_bytes([data[start + 2], data[start + 3]]) as usize;
+                assert_eq!(len, !nlen & 0xFFFF);
+                out.extend_from_slice(&data[start + 4..start + 4 + len]);
+                bits.position = (start + 4 + len) * 8;
+            }
+            1 => loop {
+                let mut code = bits.code(7);
+                let symbol = if code <= 0x17 {
+                    256 + code
+                } else {
+                    code = (code << 1) | bits.bit();
+                    match code {
+                        0x30..=0xBF => code - 0x30,
+                        0xC0..=0xC7 => 280 + code - 0xC0,
+                        _ => 144 + ((code << 1) | bits.bit()) - 0x190,
+                    }
+                } as usize;
+                if symbol < 256 {
+                    out.push(symbol as u8);
+                    continue;
+                }
+                if symbol == 256 {
+                    break;
+                }
+                let code = symbol - 257;
+                let length = if code == 28 {
+                    258
+                } else {
+                    LENGTH_BASE[code] + bits.read(code.saturating_sub(4) as u32 / 4) as usize
+                };
+                let code = bits.code(5) as usize;
+                let distance =
+                    DISTANCE_BASE[code] + bits.read(code.saturating_sub(2) as u32 / 2) as usize;
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            },
+            kind => panic!("block type {}", kind),
+        }
+        if last == 1 {
+            return out;
+        }
+    }
+}
+
+///Splits a PNG in its chunks, checking their CRC
+fn chunks(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+    assert_eq!(
+        data[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+    );
+    let mut chunks = Vec::new();
+    let mut position = 8;
+    while position < data.len() {
+        let len = u32::from_be_bytes(data[position..position + 4].try_into().unwrap()) as usize;
+        let body = &data[position + 4..position + 8 + len];
+        let crc = &data[position + 8 + len..position + 12 + len];
+        assert_eq!(png::crc32(body).to_be_bytes(), crc);
+        chunks.push((
+            String::from_utf8(body[..4].to_vec()).unwrap(),
+            body[4..].to_vec(),
+        ));
+        position += 12 + len;
+    }
+    chunks
+}
+
+///Pixels with some runs, and some noise
+fn sample(width: usize, height: usize) -> Image {
+    let mut image = Image::new(width, height);
+    for (i, pixel) in image.pixels.iter_mut().enumerate() {
+        let noise = (i as u32).wrapping_mul(2_654_435_761) >> 24;
+        *pixel = if i % 7 < 4 {
+            [40, 80, 120]
+        } else {
+            [noise as u8, (i / width) as u8, 255]
+        };
+    }
+    image
+}
+
+#[cfg(test)]
+#[test]
+fn checksums() {
+    assert_eq!(png::crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(png::crc32(b""), 0);
+    assert_eq!(png::adler32(b"Wikipedia"), 0x11E6_0398);
+    assert_eq!(png::adler32(b""), 1);
+    // sums are reduced before they overflow
+    assert_eq!(png::adler32(&vec![0xFF; 100_000]), 0x149A_302C);
+}
+
+#[test]
+fn deflate_round_trip() {
+    let runs: Vec<u8> = (0..200_000).map(|i| (i / 1000) as u8).collect();
+    let noise: Vec<u8> = (0..5000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    for data in [&runs[..], &noise[..], b"", b"ab", b"abcabcabcabcabcabcabc"] {
+        for compression in [Compression::Stored, Compression::Fixed] {
+            let compressed = png::deflate(data, compression);
+            assert!(
+                inflate(&compressed) == data,
+                "{:?} {}",
+                compression,
+                data.len()
+            );
+        }
+    }
+    // 200KB need 4 stored blocks
+    assert_eq!(
+        png::deflate(&runs, Compression::Stored).len(),
+        runs.len() + 4 * 5
+    );
+    assert!(png::deflate(&runs, Compression::Fixed).len() < runs.len() / 50);
+
+    let stream = png::zlib(&runs, Compression::Fixed);
+    assert_eq!(u16::from_be_bytes([stream[0], stream[1]]) % 31, 0);
+    assert_eq!(
+        stream[stream.len() - 4..],
+        png::adler32(&runs).to_be_bytes()
+    );
+}
+
+#[test]
+fn png_layout() {
+    let image = sample(13, 7);
+    let metadata = Metadata {
+        title: "POKEMON EMER".to_string(),
+        frame: 1234,
+        state_hash: 0x0123_4567_89AB_CDEF,
+        scaler: Some(Scaler::Scale2x),
+    };
+    let chunks = chunks(&screenshot::png(&image, &metadata));
+    let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        ["IHDR", "tEXt", "tEXt", "tEXt", "tEXt", "tEXt", "IDAT", "IEND"]
+    );
+    assert_eq!(chunks[0].1, [0, 0, 0, 13, 0, 0, 0, 7, 8, 2, 0, 0, 0]);
+    let text: Vec<&[u8]> = chunks[1..6].iter().map(|(_, data)| &data[..]).collect();
+    assert_eq!(
+        text,
+        [
+            &b"Software\0rusty_gba"[..],
+            b"Title\0POKEMON EMER",
+            b"Frame\x001234",
+            b"State hash\x000123456789ABCDEF",
+            b"Scaler\0scale2x",
+        ]
+    );
+    let zlib = &chunks[6].1;
+    let raw = inflate(&zlib[2..zlib.len() - 4]);
+    assert_eq!(raw.len(), 7 * (1 + 13 * 3));
+    for (row, pixels) in raw.chunks(1 + 13 * 3).zip(image.pixels.chunks(13)) {
+        assert_eq!(row[0], 0);
+        assert!(row[1..] == pixels.concat());
+    }
+}
+
+#[test]
+fn png_text_encoding() {
+    let image = sample(2, 2);
+    let text = |title: &str| {
+        let encoded = png::encode(&image, &[("Title", title.to_string())], Compression::Stored);
+        chunks(&encoded).swap_remove(1)
+    };
+    //Latin-1 text stays in tEXt, one byte per character
+    assert_eq!(
+        text("Pokémon"),
+        ("tEXt".to_string(), b"Title\0Pok\xE9mon".to_vec())
+    );
+    //anything else goes to iTXt, in UTF-8
+    let (kind, data) = text("ポケモン");
+    assert_eq!(kind, "iTXt");
+    assert_eq!(data[..10], *b"Title\0\0\0\0\0");
+    assert_eq!(std::str::from_utf8(&data[10..]).unwrap(), "ポケモン");
+}
+
+#[test]
+fn ppm_layout() {
+    let image = sample(3, 2);
+    let metadata = Metadata {
+        title: "BAD\nTITLE".to_string(),
+        frame: 5,
+        ..Default::default()
+    };
+    let data = screenshot::ppm(&image, &metadata);
+    let header = "P6\n# Software: rusty_gba\n# Title: BAD TITLE\n# Frame: 5\n\
+                  # State hash: 0000000000000000\n3 2\n255\n";
+    assert_eq!(String::from_utf8_lossy(&data[..header.len()]), header);
+    assert!(data[header.len()..] == image.pixels.concat());
+}
+
+#[test]
+fn capture() {
+    let mut rom = vec![0; 0x200];
+    rom[0xA0..0xA8].copy_from_slice(b"SNAPSHOT");
+    let mut mem = Memory::default();
+    mem.load_cartridge(&Cartridge::from_bytes(rom).unwrap());
+    mem.write_16(DISPCNT, 0x0403);
+    mem.write_16(VRAM, 0x7FFF);
+    let vblank = mem.lcd_next_vblank();
+    mem.stall((vblank - mem.now()) as u32);
+    mem.take_cycles();
+
+    let metadata = mem.screenshot_metadata();
+    assert_eq!(metadata.title, "SNAPSHOT");
+    assert_eq!(metadata.frame, 1);
+    assert_eq!(metadata.state_hash, mem.state_hash());
+    mem.write_16(VRAM + 2, 1);
+    assert_ne!(metadata.state_hash, mem.state_hash());
+
+    let directory = std::env::temp_dir().join(format!("rusty_gba_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("frame.PPM");
+    assert_eq!(ImageFormat::from_path(&path), Some(ImageFormat::Ppm));
+    mem.screenshot_scaled(&path, Scaler::Nearest(2)).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    assert!(String::from_utf8_lossy(&data).contains("# Title: SNAPSHOT\n"));
+    assert!(String::from_utf8_lossy(&data).contains("\n480 320\n255\n"));
+    assert_eq!(data[data.len() - 480 * 320 * 3..][..6], [255; 6]);
+
+    let path = directory.join("frame.png");
+    mem.screenshot(&path).unwrap();
+    let chunks = chunks(&std::fs::read(&path).unwrap());
+    assert_eq!(chunks[0].1[..8], [0, 0, 0, 240, 0, 0, 0, 160]);
+    let error = mem.screenshot(directory.join("frame.bmp")).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,211 @@
+use super::line;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::render::{self, TRANSPARENT};
+use gba::ppu::{LINE_CYCLES, SCREEN_WIDTH};
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG0HOFS: u32 = 0x0400_0010;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const BACKDROP: u16 = 0x1234;
+
+fn setup(dispcnt: u16) -> Memory {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE, BACKDROP);
+    mem.write_16(DISPCNT, dispcnt);
+    mem
+}
+
+///Fills a 4bpp tile with a single color index
+fn solid_tile_4bpp(mem: &mut Memory, char_base: u32, tile: u32, index: u8) {
+    let byte = index | (index << 4);
+    for i in 0..16 {
+        mem.write_16(
+            VRAM + char_base + tile * 32 + i * 2,
+            u16::from_le_bytes([byte; 2]),
+        );
+    }
+}
+
+///Writes a map entry of the first screen block at the given base
+fn entry(mem: &mut Memory, screen_base: u32, x: u32, y: u32, value: u16) {
+    mem.write_16(VRAM + screen_base + (y * 32 + x) * 2, value);
+}
+
+#[cfg(test)]
+#[test]
+fn tile_with_palette_bank() {
+    //BG0 on, char base 0, screen base 0x800(block 1)
+    let mut mem = setup(0x0100);
+    mem.write_16(BG0CNT, 0x0100);
+    solid_tile_4bpp(&mut mem, 0, 1, 3);
+    mem.write_16(PALETTE + (2 * 16 + 3) * 2, 0x7C1F);
+    entry(&mut mem, 0x800, 1, 0, 0x2001);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[7], BACKDROP);
+    assert_eq!(line[8], 0x7C1F);
+    assert_eq!(line[15], 0x7C1F);
+    assert_eq!(line[16], BACKDROP);
+}
+
+#[test]
+fn pixel_order_and_flips() {
+    let mut mem = setup(0x0100);
+    mem.write_16(BG0CNT, 0x0100);
+    for i in 1..=8 {
+        mem.write_16(PALETTE + i * 2, i as u16);
+    }
+    //tile 1: row r holds the indices 1..8 from left to right, only on row 0
+    mem.write_32(VRAM + 32, 0x8765_4321);
+    entry(&mut mem, 0x800, 0, 0, 0x0001);
+    entry(&mut mem, 0x800, 1, 0, 0x0401);
+    entry(&mut mem, 0x800, 2, 0, 0x0801);
+    let top = line(&mut mem, 0);
+    assert_eq!(top[0..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(top[8..16], [8, 7, 6, 5, 4, 3, 2, 1]);
+    //vertical flip moves the row to the bottom of the tile
+    assert_eq!(top[16], BACKDROP);
+    assert_eq!(line(&mut mem, 7)[16..24], [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn tiles_256_colors() {
+    let mut mem = setup(0x0100);
+    mem.write_16(BG0CNT, 0x0180);
+    mem.write_16(PALETTE + 0xAB * 2, 0x0ABC);
+    //tile 1 of 64 bytes, first 2 dots
+    mem.write_16(VRAM + 64, 0xAB00);
+    entry(&mut mem, 0x800, 0, 0, 0xF001);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[0], BACKDROP);
+    //the palette bank is ignored
+    assert_eq!(line[1], 0x0ABC);
+}
+
+#[test]
+fn scrolling_wraps_around() {
+    let mut mem = setup(0x0100);
+    mem.write_16(BG0CNT, 0x0100);
+    solid_tile_4bpp(&mut mem, 0, 1, 1);
+    mem.write_16(PALETTE + 2, 0x0001);
+    //last column of the first row
+    entry(&mut mem, 0x800, 31, 0, 0x0001);
+    mem.write_16(BG0HOFS, 248);
+    let line = line(&mut mem, 0);
+    assert_eq!(line[0..8], [1; 8]);
+    assert_eq!(line[8], BACKDROP);
+    //vertical offset: row 0 shows at line 4 when scrolled by 252
+    entry(&mut mem, 0x800, 0, 0, 0x0001);
+    mem.write_16(BG0HOFS, 0);
+    mem.write_16(BG0HOFS + 2, 252);
+    assert_eq!(self::line(&mut mem, 3)[0], BACKDROP);
+    assert_eq!(self::line(&mut mem, 4)[0], 1);
+}
+
+#[test]
+fn wide_map_uses_the_second_block() {
+    let mut mem = setup(0x0100);
+    //512x256, screen base block 2
+    mem.write_16(BG0CNT, 0x4200);
+    solid_tile_4bpp(&mut mem, 0, 1, 1);
+    mem.write_16(PALETTE + 2, 0x0001);
+    //first entry of block 3 = map x 256
+    entry(&mut mem, 0x1800, 0, 0, 0x0001);
+    assert_eq!(line(&mut mem, 0)[0], BACKDROP);
+    mem.write_16(BG0HOFS, 256);
+    assert_eq!(line(&mut mem, 0)[0], 1);
+    //the map is 512 wide: scrolled by 500, its left edge shows at x 12
+    entry(&mut mem, 0x1000, 0, 0, 0x0001);
+    mem.write_16(BG0HOFS, 500);
+    assert_eq!(line(&mut mem, 0)[11], BACKDROP);
+    assert_eq!(line(&mut mem, 0)[12], 1);
+}
+
+#[test]
+fn big_map_block_layout() {
+    let mut mem = setup(0x0100);
+    //512x512, screen base block 4
+    mem.write_16(BG0CNT, 0xC400);
+    solid_tile_4bpp(&mut mem, 0, 1, 1);
+    solid_tile_4bpp(&mut mem, 0, 2, 2);
+    solid_tile_4bpp(&mut mem, 0, 3, 3);
+    for i in 1..4 {
+        mem.write_16(PALETTE + i * 2, i as u16);
+    }
+    //SB1 top right, SB2 bottom left, SB3 bottom right
+    entry(&mut mem, 0x2000 + 0x800, 0, 0, 1);
+    entry(&mut mem, 0x2000 + 0x1000, 0, 0, 2);
+    entry(&mut mem, 0x2000 + 0x1800, 0, 0, 3);
+    mem.write_16(BG0HOFS, 256);
+    assert_eq!(line(&mut mem, 0)[0], 1);
+    mem.write_16(BG0HOFS, 0);
+    mem.write_16(BG0HOFS + 2, 256);
+    assert_eq!(line(&mut mem, 0)[0], 2);
+    mem.write_16(BG0HOFS, 256);
+    assert_eq!(line(&mut mem, 0)[0], 3);
+}
+
+#[test]
+fn priority_then_background_number() {
+    let mut mem = setup(0x0300);
+    solid_tile_4bpp(&mut mem, 0, 1, 1);
+    solid_tile_4bpp(&mut mem, 0, 2, 2);
+    mem.write_16(PALETTE + 2, 0x0001);
+    mem.write_16(PALETTE + 4, 0x0002);
+    entry(&mut mem, 0x800, 0, 0, 1);
+    entry(&mut mem, 0x1000, 0, 0, 2);
+    //same priority: BG0 on top
+    mem.write_16(BG0CNT, 0x0100);
+    mem.write_16(BG0CNT + 2, 0x0200);
+    assert_eq!(line(&mut mem, 0)[0], 1);
+    //BG1 with a better priority
+    mem.write_16(BG0CNT, 0x0101);
+    assert_eq!(line(&mut mem, 0)[0], 2);
+    let layers = render::render_backgrounds(&mem.video_memory(), &mem.ppu.affine);
+    assert_eq!(layers.iter().map(|l| l.bg).collect::<Vec<_>>(), [1, 0]);
+    assert_eq!(layers[1].pixels[8], TRANSPARENT);
+}
+
+#[test]
+fn mode1_has_only_two_text_layers() {
+    let mut mem = setup(0x0F01);
+    let layers = render::render_backgrounds(&mem.video_memory(), &mem.ppu.affine);
+    //BG2 is affine in mode 1, BG3 is off
+    assert!(layers.iter().all(|layer| layer.bg < 3));
+    mem.write_16(DISPCNT, 0x0F00);
+    let layers = render::render_backgrounds(&mem.video_memory(), &mem.ppu.affine);
+    assert_eq!(layers.len(), 4);
+}
+
+#[test]
+fn tiles_cannot_reach_obj_vram() {
+    let mut mem = setup(0x0100);
+    //char base 0xC000, tile 512 starts at 0x10000
+    mem.write_16(BG0CNT, 0x010C);
+    mem.write_16(PALETTE + 2, 0x0001);
+    solid_tile_4bpp(&mut mem, 0xC000, 512, 1);
+    entry(&mut mem, 0x800, 0, 0, 512);
+    assert_eq!(line(&mut mem, 0)[0], BACKDROP);
+}
+
+#[test]
+fn scroll_can_change_every_line() {
+    let mut mem = setup(0x0100);
+    mem.write_16(BG0CNT, 0x0100);
+    solid_tile_4bpp(&mut mem, 0, 1, 1);
+    mem.write_16(PALETTE + 2, 0x0001);
+    for y in 0..4 {
+        entry(&mut mem, 0x800, 0, y, 1);
+    }
+    //scrolled away on line 0, back for line 1
+    mem.write_16(BG0HOFS, 8);
+    mem.stall((LINE_CYCLES - mem.now()) as u32);
+    mem.take_cycles();
+    mem.write_16(BG0HOFS, 0);
+    mem.stall((LINE_CYCLES * 2 - mem.now()) as u32);
+    mem.take_cycles();
+    assert_eq!(mem.ppu.screen[0], BACKDROP);
+    assert_eq!(mem.ppu.screen[SCREEN_WIDTH], 1);
+}
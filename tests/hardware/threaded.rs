@@ -0,0 +1,101 @@
+use super::elapse_to;
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::thread::{self, BLOCK_SIZE};
+use gba::ppu::{LINE_CYCLES, TOTAL_LINES};
+use std::cell::RefCell;
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG0HOFS: u32 = 0x0400_0010;
+const VRAM: u32 = 0x0600_0000;
+const PALETTE: u32 = 0x0500_0000;
+const OAM: u32 = 0x0700_0000;
+
+///BG0 and a sprite over it, with palette, tiles, scroll and sprite position changed on every line
+fn animate(threaded: bool, frames: u64, check: impl Fn(u64, &Memory)) {
+    let mut mem = Memory::default();
+    mem.ppu_set_threaded(threaded);
+    assert_eq!(mem.ppu_threaded(), threaded);
+    for i in 0..1024u32 {
+        mem.write_16(VRAM + 0x800 + i * 2, (i % 8) as u16);
+    }
+    for i in 0..128u32 {
+        mem.write_16(VRAM + i * 2, (i * 0x3579) as u16);
+        mem.write_16(VRAM + 0x1_0000 + i * 2, (i * 0x1234) as u16);
+    }
+    mem.write_16(OAM, 0x0010);
+    mem.write_16(OAM + 4, 0x0002);
+    mem.write_16(BG0CNT, 0x0100);
+    mem.write_16(DISPCNT, 0x1140);
+    let lines = frames * TOTAL_LINES as u64;
+    // the setup above takes a few lines
+    for line in 4..lines {
+        elapse_to(&mut mem, line * LINE_CYCLES + 1000);
+        let n = line as u32;
+        mem.write_16(PALETTE + (n % 512) * 2, (n * 0x0421) as u16);
+        mem.write_16(VRAM + (n % 256) * 2, (n * 0x1111) as u16);
+        mem.write_16(OAM + 2, (n % 200) as u16);
+        mem.write_16(BG0HOFS, n as u16);
+        if line % TOTAL_LINES as u64 == 159 {
+            // the frame is complete once VBlank starts
+            elapse_to(&mut mem, (line + 1) * LINE_CYCLES + 10);
+            check(line / TOTAL_LINES as u64, &mem);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn matches_single_threaded() {
+    let frames = RefCell::new(Vec::new());
+    animate(false, 3, |_, mem| {
+        frames.borrow_mut().push(mem.ppu.screen.clone())
+    });
+    let frames = frames.into_inner();
+    assert_eq!(frames.len(), 3);
+    assert!(frames[0] != frames[1]);
+    animate(true, 3, |frame, mem| {
+        assert!(mem.ppu.screen == frames[frame as usize], "frame {}", frame);
+    });
+}
+
+#[test]
+fn switching_off_mid_frame() {
+    let mut single = Memory::default();
+    let mut threaded = Memory::default();
+    threaded.ppu_set_threaded(true);
+    for mem in [&mut single, &mut threaded] {
+        mem.write_16(DISPCNT, 0x0403);
+        for dot in 0..240 * 160u32 {
+            mem.write_16(VRAM + dot * 2, dot as u16);
+        }
+        elapse_to(mem, 80 * LINE_CYCLES);
+    }
+    // the lines already sent are collected
+    threaded.ppu_set_threaded(false);
+    assert!(!threaded.ppu_threaded());
+    assert!(threaded.ppu.screen[..80 * 240] == single.ppu.screen[..80 * 240]);
+    for mem in [&mut single, &mut threaded] {
+        mem.write_16(PALETTE, 0x7FFF);
+        let vblank = mem.lcd_next_vblank();
+        elapse_to(mem, vblank);
+    }
+    assert!(threaded.ppu.screen == single.ppu.screen);
+}
+
+#[test]
+fn tracking_blocks() {
+    assert_eq!(thread::block_of(0x0500_0000), Some(0));
+    assert_eq!(thread::block_of(0x0500_03FF), Some(3));
+    assert_eq!(thread::block_of(0x0600_0000), Some(4));
+    assert_eq!(
+        thread::block_of(0x0601_7FFF),
+        Some(3 + 96 * 1024 / BLOCK_SIZE)
+    );
+    assert_eq!(
+        thread::block_of(0x0700_0100),
+        Some(5 + 96 * 1024 / BLOCK_SIZE)
+    );
+    assert_eq!(thread::block_of(0x0300_0000), None);
+}
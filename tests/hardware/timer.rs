@@ -0,0 +1,142 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::interrupt::Interrupt;
+use gba::memory::Memory;
+
+const TM0CNT_L: u32 = 0x0400_0100;
+
+///Lets the given amount of cycles pass, like the CPU collecting them after an instruction
+fn elapse(mem: &mut Memory, cycles: u32) {
+    mem.stall(cycles);
+    mem.take_cycles();
+}
+
+///Writes reload and control of a timer, then drops the cycles of the writes themselves
+fn start(mem: &mut Memory, id: u32, reload: u16, control: u16) {
+    mem.write_16(TM0CNT_L + 4 * id, reload);
+    mem.write_16(TM0CNT_L + 4 * id + 2, control);
+    mem.take_cycles();
+}
+
+#[cfg(test)]
+#[test]
+fn counter_runs_from_reload() {
+    let mut mem = Memory::default();
+    start(&mut mem, 0, 0x1000, 0x80);
+    let begin = mem.read_16(TM0CNT_L);
+    elapse(&mut mem, 100);
+    //the read itself takes a cycle before the counter is sampled
+    assert_eq!(mem.read_16(TM0CNT_L), begin + 1 + 100);
+}
+
+#[test]
+fn reload_write_does_not_touch_counter() {
+    let mut mem = Memory::default();
+    start(&mut mem, 0, 0x1000, 0x80);
+    mem.write_16(TM0CNT_L, 0x8000);
+    assert!(mem.read_16(TM0CNT_L) < 0x1100);
+    assert_eq!(mem.timers.timers[0].reload, 0x8000);
+}
+
+#[test]
+fn prescaler_divides_the_clock() {
+    let mut mem = Memory::default();
+    //F/256
+    start(&mut mem, 1, 0, 0x82);
+    elapse(&mut mem, 256 * 10);
+    assert_eq!(mem.read_16(TM0CNT_L + 4), 10);
+    assert_eq!(mem.timers.timers[1].prescaler(), 256);
+}
+
+#[test]
+fn overflow_reloads_and_requests_irq() {
+    let mut mem = Memory::default();
+    start(&mut mem, 2, 0xFF00, 0xC0);
+    elapse(&mut mem, 0xFF);
+    assert_eq!(mem.interrupt.requested, 0);
+    elapse(&mut mem, 1);
+    assert_eq!(mem.interrupt.requested, Interrupt::Timer2.mask());
+    elapse(&mut mem, 0x10);
+    assert_eq!(mem.read_16(TM0CNT_L + 8), 0xFF10 + 1);
+}
+
+#[test]
+fn many_overflows_in_one_step() {
+    let mut mem = Memory::default();
+    start(&mut mem, 0, 0xFFF0, 0x80);
+    start(&mut mem, 1, 0, 0x84);
+    elapse(&mut mem, 16 * 5);
+    assert_eq!(mem.read_16(TM0CNT_L + 4), 5);
+}
+
+#[test]
+fn cascade_counts_overflows() {
+    let mut mem = Memory::default();
+    start(&mut mem, 1, 0xFFFE, 0xC4);
+    start(&mut mem, 0, 0xFFFF, 0x80);
+    elapse(&mut mem, 1);
+    //peek without a bus access, reads take cycles too
+    assert_eq!(mem.timers.timers[1].counter_at(mem.now()), 0xFFFF);
+    assert_eq!(mem.interrupt.requested, 0);
+    elapse(&mut mem, 1);
+    assert_eq!(mem.interrupt.requested, Interrupt::Timer1.mask());
+    assert_eq!(mem.timers.timers[1].counter_at(mem.now()), 0xFFFE);
+}
+
+#[test]
+fn stop_freezes_and_restart_reloads() {
+    let mut mem = Memory::default();
+    start(&mut mem, 3, 0x0100, 0x80);
+    elapse(&mut mem, 50);
+    mem.write_16(TM0CNT_L + 14, 0x00);
+    let frozen = mem.read_16(TM0CNT_L + 12);
+    elapse(&mut mem, 1000);
+    assert_eq!(mem.read_16(TM0CNT_L + 12), frozen);
+    start(&mut mem, 3, 0x0100, 0x80);
+    assert!(mem.read_16(TM0CNT_L + 12) < 0x0100 + 4);
+}
+
+#[test]
+fn timer_clocks_sound_fifo() {
+    let mut mem = Memory::default();
+    //master enable, FIFO A on timer 0
+    mem.write_16(0x0400_0084, 0x80);
+    mem.write_16(0x0400_0082, 0x0000);
+    mem.write_32(0x0400_00A0, 0x0403_0201);
+    assert_eq!(mem.apu.fifo[0].len(), 4);
+    start(&mut mem, 0, 0xFFF0, 0x80);
+    elapse(&mut mem, 16 * 2);
+    assert_eq!(mem.apu.fifo[0].len(), 2);
+    assert_eq!(mem.apu.fifo[0].current, 2);
+    //FIFO B follows timer 1, which is not running
+    assert_eq!(mem.apu.fifo[1].len(), 0);
+}
+
+#[test]
+fn fifo_running_low_requests_sound_dma() {
+    let mut mem = Memory::default();
+    for i in 0..16u32 {
+        mem.write_32(0x0300_0000 + i * 4, i);
+    }
+    mem.write_16(0x0400_0084, 0x80);
+    mem.write_16(0x0400_0082, 0x0000);
+    mem.write_32(0x0400_00BC, 0x0300_0000);
+    mem.write_32(0x0400_00C0, 0x0400_00A0);
+    mem.write_16(0x0400_00C6, 0xB600);
+    for _ in 0..5 {
+        mem.write_32(0x0400_00A0, 0);
+    }
+    //one sample every 64 cycles, much longer than the DMA stall
+    start(&mut mem, 0, 0xFFFF, 0x81);
+    //20 samples, the DMA kicks in when 16 are left
+    elapse(&mut mem, 4 * 64);
+    assert_eq!(mem.apu.fifo[0].len(), 32);
+    assert_eq!(mem.dma.channels[1].source, 0x0300_0010);
+}
+
+#[test]
+fn soundcnt_h_resets_fifo() {
+    let mut mem = Memory::default();
+    mem.write_32(0x0400_00A4, 0x0403_0201);
+    mem.write_16(0x0400_0082, 0x8000);
+    assert!(mem.apu.fifo[1].is_empty());
+}
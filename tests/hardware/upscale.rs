@@ -0,0 +1,170 @@
+use gba::video::scale::Scaler;
+use gba::video::{Image, Rgb};
+
+///Colors of the golden images: black, white, and the even blend the filters make of them
+const LEGEND: [(char, Rgb); 3] = [
+    ('.', [0, 0, 0]),
+    ('#', [255, 255, 255]),
+    ('o', [128, 128, 128]),
+];
+
+fn image(rows: &[&str]) -> Image {
+    let mut image = Image::new(rows[0].len(), rows.len());
+    for (pixel, c) in image.pixels.iter_mut().zip(rows.concat().chars()) {
+        *pixel = LEGEND.iter().find(|(key, _)| *key == c).unwrap().1;
+    }
+    image
+}
+
+fn rows(image: &Image) -> Vec<String> {
+    image
+        .pixels
+        .chunks(image.width)
+        .map(|row| {
+            row.iter()
+                .map(
+                    |pixel| match LEGEND.iter().find(|(_, color)| color == pixel) {
+                        Some((key, _)) => *key,
+                        None => panic!("unexpected color {:?}", pixel),
+                    },
+                )
+                .collect()
+        })
+        .collect()
+}
+
+const DIAGONAL: [&str; 4] = ["#....", ".#...", "..##.", "..##."];
+
+fn golden(name: &str, expected: &[&str]) {
+    let scaled = Scaler::from_name(name).unwrap().scale(&image(&DIAGONAL));
+    assert_eq!(rows(&scaled), expected, "{}", name);
+}
+
+#[cfg(test)]
+#[test]
+fn names() {
+    for scaler in [
+        Scaler::Nearest(1),
+        Scaler::Nearest(4),
+        Scaler::Scale2x,
+        Scaler::Scale3x,
+        Scaler::Hq2x,
+        Scaler::Xbr2x,
+    ] {
+        assert_eq!(Scaler::from_name(&scaler.to_string()), Some(scaler));
+    }
+    for name in ["nearest0x", "nearest9x", "nearest", "scale4x", ""] {
+        assert_eq!(Scaler::from_name(name), None, "{}", name);
+    }
+    assert_eq!(Scaler::Scale3x.factor(), 3);
+    assert_eq!(Scaler::Xbr2x.factor(), 2);
+}
+
+#[test]
+fn nearest() {
+    golden(
+        "nearest2x",
+        &[
+            "##........",
+            "##........",
+            "..##......",
+            "..##......",
+            "....####..",
+            "....####..",
+            "....####..",
+            "....####..",
+        ],
+    );
+    golden("nearest1x", &DIAGONAL);
+}
+
+#[test]
+fn scale2x() {
+    golden(
+        "scale2x",
+        &[
+            "##........",
+            "#.#.......",
+            ".###......",
+            "..###.....",
+            "...#.##...",
+            "....####..",
+            "....####..",
+            "....####..",
+        ],
+    );
+}
+
+#[test]
+fn scale3x() {
+    golden(
+        "scale3x",
+        &[
+            "###............",
+            "##.#...........",
+            "#..#...........",
+            ".#####.........",
+            "...###.........",
+            "...#####.......",
+            ".....#..##.....",
+            ".....#.####....",
+            "......######...",
+            "......######...",
+            "......######...",
+            "......######...",
+        ],
+    );
+}
+
+#[test]
+fn hq2x() {
+    golden(
+        "hq2x",
+        &[
+            "##........",
+            "#oo.......",
+            ".ooo......",
+            "..ooo.....",
+            "...oo##o..",
+            "....####..",
+            "....####..",
+            "....####..",
+        ],
+    );
+}
+
+#[test]
+fn xbr2x() {
+    golden(
+        "xbr2x",
+        &[
+            "##........",
+            "##o.......",
+            ".o#o......",
+            "..o#o.....",
+            "...o###o..",
+            "....####..",
+            "....####..",
+            "....####..",
+        ],
+    );
+}
+
+#[test]
+fn flat_areas() {
+    let mut flat = Image::new(240, 160);
+    flat.pixels.fill([12, 34, 56]);
+    for name in ["nearest3x", "scale2x", "scale3x", "hq2x", "xbr2x"] {
+        let scaler = Scaler::from_name(name).unwrap();
+        let scaled = scaler.scale(&flat);
+        assert_eq!(
+            (scaled.width, scaled.height),
+            (240 * scaler.factor(), 160 * scaler.factor())
+        );
+        assert!(
+            scaled.pixels.iter().all(|pixel| *pixel == [12, 34, 56]),
+            "{}",
+            name
+        );
+    }
+}
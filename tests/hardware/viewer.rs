@@ -0,0 +1,130 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::ppu::obj;
+use gba::video::viewer;
+
+const DISPCNT: u32 = 0x0400_0000;
+const BG0CNT: u32 = 0x0400_0008;
+const BG2CNT: u32 = 0x0400_000C;
+const PALETTE: u32 = 0x0500_0000;
+const VRAM: u32 = 0x0600_0000;
+const OAM: u32 = 0x0700_0000;
+
+#[cfg(test)]
+#[test]
+fn palette_swatch() {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE + 17 * 2, 0x001F);
+    mem.write_16(PALETTE + 511 * 2, 0x7C00);
+    let swatch = viewer::palette_swatch(&mem.video_memory());
+    assert_eq!((swatch.width, swatch.height), (128, 256));
+    // entry 17: second row, second column
+    assert_eq!(swatch.at(8, 8), [255, 0, 0]);
+    assert_eq!(swatch.at(15, 15), [255, 0, 0]);
+    assert_eq!(swatch.at(7, 8), [0, 0, 0]);
+    assert_eq!(swatch.at(127, 255), [0, 0, 255]);
+}
+
+#[test]
+fn tile_sheets() {
+    let mut mem = Memory::default();
+    // BG bank 2 color 3, OBJ bank 1 color 3, 256 colors entry 0x43
+    mem.write_16(PALETTE + (2 * 16 + 3) * 2, 0x03E0);
+    mem.write_16(PALETTE + (256 + 16 + 3) * 2, 0x7C00);
+    mem.write_16(PALETTE + 0x43 * 2, 0x7FFF);
+    mem.write_16(PALETTE, 0x001F);
+    // tile 33 of 32 bytes: second row of the sheet, second column
+    mem.write_16(VRAM + 33 * 32, 0x4303);
+    let video = mem.video_memory();
+
+    let sheet = viewer::tile_sheet(&video, false, 2);
+    assert_eq!((sheet.width, sheet.height), (256, 96 * 1024 / 32 / 32 * 8));
+    assert_eq!(sheet.at(8, 8), [0, 255, 0]);
+    // color 0 is transparent, showing the backdrop
+    assert_eq!(sheet.at(9, 8), [255, 0, 0]);
+    let sheet = viewer::tile_sheet(&video, false, 17);
+    assert_eq!(sheet.at(8, 8), [0, 0, 255]);
+
+    // the same bytes as 256 colors: tile 16, first row, 17th column
+    let sheet = viewer::tile_sheet(&video, true, 0);
+    assert_eq!((sheet.width, sheet.height), (256, 384));
+    assert_eq!(sheet.at(16 * 8 + 1, 4), [255, 255, 255]);
+}
+
+#[test]
+fn text_tilemap() {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE + 2, 0x001F);
+    mem.write_16(VRAM + 32, 0x1111);
+    // 512x256 map at screen block 8: entry 0 of block 9 is the dot at x 256
+    mem.write_16(BG0CNT, 0x4800);
+    mem.write_16(VRAM + 9 * 0x800, 1);
+    mem.write_16(DISPCNT, 0x0100);
+    let map = viewer::tilemap(&mem.video_memory(), 0).unwrap();
+    assert_eq!((map.width, map.height), (512, 256));
+    assert_eq!(map.at(256, 0), [255, 0, 0]);
+    assert_eq!(map.at(259, 0), [255, 0, 0]);
+    assert_eq!(map.at(260, 0), [0, 0, 0]);
+    assert_eq!(map.at(0, 0), [0, 0, 0]);
+    assert!(viewer::tilemap(&mem.video_memory(), 3).is_some());
+
+    // mode 1: BG3 does not exist, BG2 is affine
+    mem.write_16(DISPCNT, 0x0001);
+    mem.write_16(BG2CNT, 0xC000);
+    assert!(viewer::tilemap(&mem.video_memory(), 3).is_none());
+    let map = viewer::tilemap(&mem.video_memory(), 2).unwrap();
+    assert_eq!((map.width, map.height), (1024, 1024));
+}
+
+#[test]
+fn bitmap_page() {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE + 5 * 2, 0x7FFF);
+    mem.write_16(VRAM + 0xA000 + 240, 0x0500);
+    mem.write_16(DISPCNT, 0x0014);
+    let map = viewer::tilemap(&mem.video_memory(), 2).unwrap();
+    assert_eq!((map.width, map.height), (240, 160));
+    assert_eq!(map.at(1, 1), [255, 255, 255]);
+    assert_eq!(map.at(0, 1), [0, 0, 0]);
+    assert!(viewer::tilemap(&mem.video_memory(), 0).is_none());
+}
+
+#[test]
+fn sprites() {
+    let mut mem = Memory::default();
+    mem.write_16(PALETTE + 0x200 + (3 * 16 + 1) * 2, 0x001F);
+    // 16x8 sprite, h-flipped, palette 3, tile 2: its first dot is shown last
+    mem.write_16(OAM, 0x4000 | 20);
+    mem.write_16(OAM + 2, 0x1000 | 0x1F0);
+    mem.write_16(OAM + 4, 0x3402);
+    mem.write_16(VRAM + 0x1_0000 + 2 * 32, 0x0001);
+    let video = mem.video_memory();
+    let sprite = obj::sprites(&video)[0];
+    let image = viewer::sprite_image(&video, &sprite);
+    assert_eq!((image.width, image.height), (16, 8));
+    assert_eq!(image.at(15, 0), [255, 0, 0]);
+    assert_eq!(image.at(0, 0), [0, 0, 0]);
+
+    let listing = viewer::oam_listing(&video);
+    assert_eq!(listing.lines().count(), 128);
+    assert_eq!(
+        listing.lines().next().unwrap(),
+        "  0: x  -16 y  20 size 16x8 tile   2 (VRAM 0x10040) 16 colors palette  3 priority 1 \
+         normal h-flip"
+    );
+}
+
+#[test]
+fn dump_directory() {
+    let mut mem = Memory::default();
+    mem.write_16(DISPCNT, 0x0001);
+    let directory = std::env::temp_dir().join(format!("rusty_gba_vram_{}", std::process::id()));
+    let files = mem.dump_vram(&directory, 0).unwrap();
+    // 2 tile sheets, the palette, BG0-BG2 of mode 1, 128 sprites and the listing
+    assert_eq!(files.len(), 3 + 3 + 128 + 1);
+    for name in ["tiles_4bpp.png", "bg2.png", "obj_127.png", "oam.txt"] {
+        assert!(directory.join(name).exists(), "{}", name);
+    }
+    assert!(!directory.join("bg3.png").exists());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,153 @@
+use arm7tdmi::cpu::MemoryInterface;
+use gba::memory::Memory;
+use gba::waitstate::{AccessWidth, WaitControl, WaitState, PREFETCH_CAPACITY};
+
+#[cfg(test)]
+#[test]
+fn decode_waitcnt() {
+    //SRAM 8, WS0 2/1, WS1 4/4, WS2 8/1, prefetch on
+    let control = WaitControl::from_waitcnt(0b0100_0111_0001_1011);
+    assert_eq!(control.sram, 8);
+    assert_eq!(control.first, [2, 4, 8]);
+    assert_eq!(control.second, [1, 4, 1]);
+    assert!(control.prefetch);
+}
+
+#[test]
+fn rom_access_cycles() {
+    let mut ws = WaitState::default();
+    //WS0 defaults to 4/2: non sequential halfword is 1+4, sequential is 1+2
+    assert_eq!(ws.access(0x0800_0000, AccessWidth::Half, false), 5);
+    assert_eq!(ws.access(0x0800_0002, AccessWidth::Half, false), 3);
+    //a word is a non sequential halfword followed by a sequential one
+    assert_eq!(ws.access(0x0800_0100, AccessWidth::Word, false), 8);
+    //WS2 second access is 8 wait states
+    assert_eq!(ws.access(0x0C00_0000, AccessWidth::Word, false), 5 + 9);
+    assert_eq!(ws.take_cycles(), 5 + 3 + 8 + 14);
+    assert_eq!(ws.take_cycles(), 0);
+}
+
+#[test]
+fn rom_page_boundary_is_non_sequential() {
+    let mut ws = WaitState::default();
+    ws.access(0x0801_FFFE, AccessWidth::Half, false);
+    assert_eq!(ws.access(0x0802_0000, AccessWidth::Half, false), 5);
+}
+
+#[test]
+fn other_regions_cycles() {
+    let mut ws = WaitState::default();
+    assert_eq!(ws.access(0x0300_0000, AccessWidth::Word, false), 1);
+    assert_eq!(ws.access(0x0200_0000, AccessWidth::Half, false), 3);
+    assert_eq!(ws.access(0x0200_0000, AccessWidth::Word, false), 6);
+    assert_eq!(ws.access(0x0600_0000, AccessWidth::Word, false), 2);
+    assert_eq!(ws.access(0x0E00_0000, AccessWidth::Byte, false), 5);
+}
+
+#[test]
+fn prefetch_hits_buffered_opcodes() {
+    let mut ws = WaitState::default();
+    ws.write_waitcnt(0x4000);
+    //miss: normal non sequential fetch, then the buffer follows the stream
+    assert_eq!(ws.access(0x0800_0000, AccessWidth::Half, true), 5);
+    //the CPU works in IWRAM for a while, leaving the GamePak bus free
+    for _ in 0..12 {
+        ws.access(0x0300_0000, AccessWidth::Word, false);
+    }
+    assert_eq!(ws.prefetch.count, 4);
+    //hits take a single cycle
+    assert_eq!(ws.access(0x0800_0002, AccessWidth::Half, true), 1);
+    assert_eq!(ws.access(0x0800_0004, AccessWidth::Word, true), 1);
+    assert_eq!(ws.prefetch.count, 1);
+}
+
+#[test]
+fn prefetch_stops_when_full() {
+    let mut ws = WaitState::default();
+    ws.write_waitcnt(0x4000);
+    ws.access(0x0800_0000, AccessWidth::Half, true);
+    for _ in 0..100 {
+        ws.access(0x0300_0000, AccessWidth::Word, false);
+    }
+    assert_eq!(ws.prefetch.count, PREFETCH_CAPACITY);
+}
+
+#[test]
+fn prefetch_waits_for_halfword_in_progress() {
+    let mut ws = WaitState::default();
+    ws.write_waitcnt(0x4000);
+    ws.access(0x0800_0000, AccessWidth::Half, true);
+    ws.access(0x0300_0000, AccessWidth::Word, false);
+    //1 of the 3 cycles needed is already done
+    assert_eq!(ws.access(0x0800_0002, AccessWidth::Half, true), 2);
+    //the next one was started right after, so it costs a plain sequential read
+    assert_eq!(ws.access(0x0800_0004, AccessWidth::Half, true), 3);
+}
+
+#[test]
+fn prefetch_never_slows_down_rom_code() {
+    //thumb and arm straight-line code, with a couple of branches
+    let mut stream = vec![];
+    for i in 0..40 {
+        stream.push((0x0800_0100 + i * 2, AccessWidth::Half));
+    }
+    for i in 0..20 {
+        stream.push((0x0800_2000 + i * 4, AccessWidth::Word));
+    }
+    for i in 0..10 {
+        stream.push((0x0A00_0000 + i * 2, AccessWidth::Half));
+    }
+    //WS0 4/2, 3/1, 8/1, then WS1 8/4
+    for waitcnt in [0x0000, 0x0014, 0x001C, 0x0060] {
+        let mut on = WaitState::default();
+        let mut off = WaitState::default();
+        on.write_waitcnt(waitcnt | 0x4000);
+        off.write_waitcnt(waitcnt);
+        for &(address, width) in &stream {
+            let with = on.access(address, width, true);
+            let without = off.access(address, width, true);
+            assert!(
+                with <= without,
+                "{waitcnt:#X} {address:#X}: {with} > {without}"
+            );
+        }
+        assert!(on.take_cycles() <= off.take_cycles());
+    }
+}
+
+#[test]
+fn rom_data_access_flushes_prefetch() {
+    let mut ws = WaitState::default();
+    ws.write_waitcnt(0x4000);
+    ws.access(0x0800_0000, AccessWidth::Half, true);
+    for _ in 0..6 {
+        ws.access(0x0300_0000, AccessWidth::Word, false);
+    }
+    ws.access(0x0800_1000, AccessWidth::Word, false);
+    assert_eq!(ws.prefetch.count, 0);
+    assert_eq!(ws.access(0x0800_0002, AccessWidth::Half, true), 5);
+}
+
+#[test]
+fn prefetch_disabled_has_no_effect() {
+    let mut ws = WaitState::default();
+    ws.access(0x0800_0000, AccessWidth::Half, true);
+    for _ in 0..12 {
+        ws.access(0x0300_0000, AccessWidth::Word, false);
+    }
+    assert_eq!(ws.access(0x0800_0002, AccessWidth::Half, true), 5);
+}
+
+#[test]
+fn waitcnt_write_reaches_memory_timing() {
+    let mut mem = Memory::default();
+    mem.write_16(0x0400_0204, 0x4018); //WS0 2/1, prefetch on
+    mem.take_cycles();
+    assert!(mem.waitstate().control.prefetch);
+    mem.read_16(0x0800_0000);
+    mem.read_16(0x0800_0002);
+    assert_eq!(mem.take_cycles(), 3 + 2);
+    //bit 15(GamePak type) is read only
+    mem.write_16(0x0400_0204, 0xFFFF);
+    assert_eq!(mem.read_16(0x0400_0204), 0x5FFF);
+}
@@ -1,4 +1,5 @@
 pub mod arm32;
+pub mod hardware;
 
 // pub mod cpu;
 pub mod gba;